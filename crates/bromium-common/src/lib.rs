@@ -3,11 +3,19 @@
 mod timeout;
 pub use timeout::execute_with_timeout;
 
+#[cfg(windows)]
 mod uia;
+#[cfg(windows)]
 pub use uia::{RuntimeIdFilter, get_ui_automation_instance};
 
+#[cfg(windows)]
 pub mod rectangle;
 
+#[cfg(windows)]
+mod process;
+#[cfg(windows)]
+pub use process::process_executable_name;
+
 /// Formats a runtime ID slice as a dash-separated string (e.g., `"42-1-234-56"`).
 /// Returns `"0-0-0-0"` if the slice is empty, matching the fallback convention.
 pub fn format_runtime_id(id: &[i32]) -> String {
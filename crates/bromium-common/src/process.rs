@@ -0,0 +1,58 @@
+//! Process lookups used to scope tree walks to an application.
+
+use log::debug;
+use windows::{
+    Win32::Foundation::{CloseHandle, HANDLE},
+    Win32::System::Threading::{
+        OpenProcess, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION,
+        QueryFullProcessImageNameW,
+    },
+    core::PWSTR,
+};
+
+/// Closes the wrapped process handle on drop.
+struct ProcessHandle(HANDLE);
+
+impl Drop for ProcessHandle {
+    fn drop(&mut self) {
+        // SAFETY: The handle was returned by a successful `OpenProcess` call
+        // and is closed exactly once here.
+        if let Err(e) = unsafe { CloseHandle(self.0) } {
+            debug!("CloseHandle {:?} failed: {}", self.0, e);
+        }
+    }
+}
+
+/// Executable file name (e.g. `"notepad.exe"`) of the process with the given
+/// id, or `None` if the process cannot be queried.
+pub fn process_executable_name(process_id: u32) -> Option<String> {
+    // SAFETY: OpenProcess has no memory-safety preconditions; the returned
+    // handle is owned by `ProcessHandle` and closed on drop.
+    let handle = match unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, process_id) }
+    {
+        Ok(handle) => ProcessHandle(handle),
+        Err(e) => {
+            debug!("OpenProcess({}) failed: {}", process_id, e);
+            return None;
+        }
+    };
+
+    let mut buffer = [0u16; 1024];
+    let mut len = buffer.len() as u32;
+    // SAFETY: `buffer` is a valid, writable buffer of `len` UTF-16 units, and
+    // `len` is updated to the number of units written.
+    if let Err(e) = unsafe {
+        QueryFullProcessImageNameW(
+            handle.0,
+            PROCESS_NAME_WIN32,
+            PWSTR(buffer.as_mut_ptr()),
+            &mut len,
+        )
+    } {
+        debug!("QueryFullProcessImageNameW({}) failed: {}", process_id, e);
+        return None;
+    }
+
+    let path = String::from_utf16_lossy(&buffer[..len as usize]);
+    path.rsplit(['\\', '/']).next().map(str::to_string)
+}
@@ -5,7 +5,7 @@ bromium is a Python library for Windows UI Automation built on Rust + PyO3.
 It provides programmatic access to UI elements via the Windows UI Automation API.
 """
 
-from typing import Iterator, Literal, Optional
+from typing import Callable, Iterator, Literal, Optional, Union
 
 # ─── Exceptions ───────────────────────────────────────────────────────────────
 
@@ -159,6 +159,69 @@ class Element:
         """The bounding rectangle as (left, top, right, bottom)."""
         ...
 
+    @property
+    def is_enabled(self) -> bool:
+        """Whether the element was enabled when the UI tree was built."""
+        ...
+
+    @property
+    def is_offscreen(self) -> bool:
+        """Whether the element was scrolled or hidden out of view when the UI tree was built."""
+        ...
+
+    @property
+    def has_keyboard_focus(self) -> bool:
+        """Whether the element had the keyboard focus when the UI tree was built."""
+        ...
+
+    @property
+    def is_keyboard_focusable(self) -> bool:
+        """Whether the element can receive the keyboard focus."""
+        ...
+
+    @property
+    def help_text(self) -> str:
+        """The help text (tooltip) of the element."""
+        ...
+
+    @property
+    def accelerator_key(self) -> str:
+        """The accelerator key (e.g. "Ctrl+S") of the element."""
+        ...
+
+    @property
+    def access_key(self) -> str:
+        """The access key (e.g. "Alt+F") of the element."""
+        ...
+
+    @property
+    def process_id(self) -> int:
+        """The ID of the process owning the element."""
+        ...
+
+    @property
+    def value(self) -> Optional[str]:
+        """The current value, if the element supports the Value pattern."""
+        ...
+
+    @property
+    def toggle_state(self) -> Optional[str]:
+        """The toggle state ("On", "Off" or "Indeterminate"), if the element supports the Toggle pattern."""
+        ...
+
+    @property
+    def supported_patterns(self) -> list[str]:
+        """Names of the UI Automation control patterns the element supports (e.g. "Invoke", "Value")."""
+        ...
+
+    @property
+    def fingerprint(self) -> Optional[str]:
+        """
+        Stable identity of the element (control type, automation id, class name,
+        name and ancestor path), or None if the element was not taken from a UI tree.
+        """
+        ...
+
     # ─── Actions ──────────────────────────────────────────────────────────
 
     def send_click(self) -> None:
@@ -276,6 +339,42 @@ class ElementIterator:
     def __next__(self) -> Element: ...
     def __len__(self) -> int: ...
 
+# ─── LocatorCandidate ─────────────────────────────────────────────────────────
+
+class LocatorCandidate:
+    """
+    A generated XPath for an element. Returned by
+    ``WinDriver.get_locator_candidates()``.
+    """
+
+    def __repr__(self) -> str: ...
+    def __str__(self) -> str: ...
+
+    @property
+    def xpath(self) -> str:
+        """The XPath locator."""
+        ...
+
+    @property
+    def strategy(self) -> Literal["attribute", "anchored", "positional"]:
+        """How the locator was built."""
+        ...
+
+    @property
+    def score(self) -> float:
+        """Robustness between 0.0 and 1.0; higher is more stable."""
+        ...
+
+    @property
+    def match_count(self) -> int:
+        """Number of elements the locator matched in the tree it was generated from."""
+        ...
+
+    @property
+    def is_unique(self) -> bool:
+        """Whether the locator matched exactly one element."""
+        ...
+
 # ─── WinDriver ────────────────────────────────────────────────────────────────
 
 class WinDriver:
@@ -291,7 +390,18 @@ class WinDriver:
         - ``xpath in driver`` — check if an XPath exists in the tree
     """
 
-    def __init__(self, timeout_ms: int, window_title: Optional[str] = None) -> None:
+    def __init__(
+        self,
+        timeout_ms: int,
+        window_title: Optional[str] = None,
+        *,
+        process_id: Optional[int] = None,
+        executable: Optional[str] = None,
+        window_handle: Optional[int] = None,
+        class_name: Optional[str] = None,
+        title_pattern: Optional[str] = None,
+        tree_view: Literal["raw", "control", "content"] = "control",
+    ) -> None:
         """
         Create a new WinDriver, building the UI Automation tree.
 
@@ -299,8 +409,24 @@ class WinDriver:
             timeout_ms: Default timeout in milliseconds for element lookup retries.
             window_title: Optional window title to filter the tree. If None, the
                 full desktop tree (depth 2) is captured.
+            process_id: Only walk top-level windows owned by this process.
+            executable: Only walk top-level windows of this executable, e.g.
+                ``"notepad.exe"`` (case-insensitive, ``.exe`` optional).
+            window_handle: Only walk the top-level window with this handle.
+            class_name: Only walk top-level windows with this window class.
+            title_pattern: Only walk top-level windows whose title matches this
+                regular expression.
+
+            The scope options combine (all given ones must match) and apply to
+            every later refresh of this driver.
+
+            tree_view: UI Automation view to walk. ``"raw"`` also exposes
+                elements of legacy Win32 and custom-drawn applications that the
+                control view hides; ``"content"`` gives smaller trees.
 
         Raises:
+            ValueError: If ``title_pattern`` is not a valid regular expression,
+                or ``tree_view`` is not a known view.
             TreeConstructionError: If the UI tree cannot be built within 120 seconds.
         """
         ...
@@ -342,6 +468,14 @@ class WinDriver:
     @window_title.setter
     def window_title(self, value: Optional[str]) -> None: ...
 
+    @property
+    def tree_view(self) -> Literal["raw", "control", "content"]:
+        """The UI Automation view the tree is walked in; a change takes effect with the next refresh."""
+        ...
+
+    @tree_view.setter
+    def tree_view(self, value: Literal["raw", "control", "content"]) -> None: ...
+
     # ─── Element lookup ───────────────────────────────────────────────────
 
     def get_element_by_coordinates(self, x: int, y: int) -> Element:
@@ -360,7 +494,12 @@ class WinDriver:
         """
         ...
 
-    def get_element_by_xpath(self, xpath: str, timeout_ms: Optional[int] = None) -> Element:
+    def get_element_by_xpath(
+        self,
+        xpath: str,
+        timeout_ms: Optional[int] = None,
+        variables: Optional[dict[str, Union[str, int, float, bool]]] = None,
+    ) -> Element:
         """
         Find a single element by XPath.
 
@@ -368,10 +507,18 @@ class WinDriver:
         elapses. When ``timeout_ms`` is None, the driver's default ``timeout_ms``
         is used. Pass ``0`` to disable retrying.
 
+        Values in ``variables`` are bound to ``$name`` references in the
+        expression, so user-supplied text never needs quoting::
+
+            driver.get_element_by_xpath(
+                "//Button[@Name=$name]", variables={"name": "Don't save"}
+            )
+
         Args:
             xpath: The XPath locator string.
             timeout_ms: Per-call timeout override in milliseconds, or None to
                 use the driver default.
+            variables: XPath variables to bind, keyed by name without the ``$``.
 
         Returns:
             The matching Element.
@@ -379,15 +526,21 @@ class WinDriver:
         Raises:
             ElementNotFoundError: If no element matches after the timeout.
             TreeConstructionError: If tree refresh fails during retries.
+            TypeError: If a variable value is not a str, int, float or bool.
         """
         ...
 
-    def get_elements_by_xpath(self, xpath: str) -> list[Element]:
+    def get_elements_by_xpath(
+        self,
+        xpath: str,
+        variables: Optional[dict[str, Union[str, int, float, bool]]] = None,
+    ) -> list[Element]:
         """
         Find all elements matching an XPath expression.
 
         Args:
             xpath: The XPath locator string.
+            variables: XPath variables to bind, keyed by name without the ``$``.
 
         Returns:
             A list of matching Elements. Returns an empty list if none match.
@@ -419,6 +572,55 @@ class WinDriver:
         """
         ...
 
+    def relocate(self, element: Element, min_confidence: float = 0.6) -> Element:
+        """
+        Find an element in the current UI tree after its runtime ID changed,
+        e.g. after ``refresh()`` or an application restart.
+
+        The element is looked up by runtime ID first, then by the most similar
+        fingerprint.
+
+        Args:
+            element: An element obtained from this or an earlier UI tree.
+            min_confidence: Minimum fingerprint similarity between 0.0 and 1.0.
+
+        Returns:
+            The element in the current tree, with an up-to-date xpath.
+
+        Raises:
+            ElementNotFoundError: If no sufficiently similar element exists.
+
+        Examples:
+            >>> save = driver.get_element_by_xpath("//Button[@Name='Save']")
+            >>> driver.refresh()
+            >>> save = driver.relocate(save)
+        """
+        ...
+
+    def get_locator_candidates(self, element: Element) -> list[LocatorCandidate]:
+        """
+        Generate XPath locator candidates for an element, best first.
+
+        Candidates use the element's own attributes (``AutomationId``,
+        ``ClassName``, ``Name``), a path from an identifiable ancestor, or
+        sibling positions. Each one is evaluated against the current UI tree,
+        and unique candidates are listed before ambiguous ones.
+
+        Args:
+            element: An element of the current UI tree.
+
+        Returns:
+            The candidates, ordered by uniqueness, then score.
+
+        Raises:
+            ElementNotFoundError: If the element is not in the current tree.
+
+        Examples:
+            >>> best = driver.get_locator_candidates(element)[0]
+            >>> best.xpath, best.strategy, best.is_unique
+        """
+        ...
+
     # ─── Actions ──────────────────────────────────────────────────────────
 
     def get_cursor_pos(self) -> tuple[int, int]:
@@ -430,7 +632,11 @@ class WinDriver:
         """
         ...
 
-    def refresh(self, window_title: Optional[str] = None) -> None:
+    def refresh(
+        self,
+        window_title: Optional[str] = None,
+        progress: Optional[Callable[[int, str, int, float], None]] = None,
+    ) -> None:
         """
         Refresh the UI tree by re-scanning the current window state.
 
@@ -439,6 +645,9 @@ class WinDriver:
         Args:
             window_title: Optional title to filter by. If None, uses the
                 stored window_title (if any), or scans the full desktop.
+            progress: Optional callback invoked periodically during the scan
+                with (elements_processed, current_window, depth, elapsed_seconds).
+                Exceptions raised by the callback are logged and ignored.
 
         Raises:
             TreeConstructionError: If the refresh fails.
@@ -624,7 +833,19 @@ class Bromium:
     ) -> None: ...
 
     @staticmethod
-    def get_win_driver(timeout_ms: int, window_title: Optional[str] = None) -> WinDriver: ...
+    def get_win_driver(
+        timeout_ms: int,
+        window_title: Optional[str] = None,
+        *,
+        process_id: Optional[int] = None,
+        executable: Optional[str] = None,
+        window_handle: Optional[int] = None,
+        class_name: Optional[str] = None,
+        title_pattern: Optional[str] = None,
+        tree_view: Literal["raw", "control", "content"] = "control",
+    ) -> WinDriver:
+        """Create a WinDriver; takes the same arguments as ``WinDriver(...)``."""
+        ...
 
     @staticmethod
     def get_version() -> str: ...
@@ -5,7 +5,7 @@ use std::thread;
 use std::time::Duration;
 
 use log::{debug, error, info, trace, warn};
-use uitree::SaveUIElementXML;
+use uitree::UIElementInTreeXML;
 
 #[derive(Debug, thiserror::Error)]
 pub enum AppControlError {
@@ -26,7 +26,7 @@ pub fn launch_or_activate_application(
     win_driver: &mut WinDriver,
     app_path: &str,
     xpath: &str,
-) -> Result<SaveUIElementXML, AppControlError> {
+) -> Result<UIElementInTreeXML, AppControlError> {
     debug!("WinDriver instance is available");
     let ui_tree = win_driver.get_ui_tree();
     debug!(
@@ -34,12 +34,13 @@ pub fn launch_or_activate_application(
         ui_tree.get_elements().len()
     );
 
-    let element_opt = ui_tree.get_element_by_xpath(xpath);
+    let element_opt = ui_tree.get_element_in_tree_by_xpath(xpath);
     match element_opt {
         Some(element) => {
             info!("Found UI element for xpath: {}", xpath);
             info!("Activating application window for element: {:?}", element);
             element
+                .get_element_props()
                 .set_focus()
                 .map_err(|e| AppControlError::SetFocusFailed(format!("{:?}", e)))?;
             Ok(element.clone())
@@ -54,7 +55,7 @@ pub fn launch_or_activate_application(
                     let max_attempts: u32 = 20;
                     let mut attempt: u32 = 1;
                     let mut success: bool = false;
-                    let mut result: Result<SaveUIElementXML, AppControlError> =
+                    let mut result: Result<UIElementInTreeXML, AppControlError> =
                         Err(AppControlError::ElementNotFound {
                             xpath: xpath.to_string(),
                             attempts: max_attempts,
@@ -88,7 +89,7 @@ pub fn launch_or_activate_application(
                             ui_tree.get_elements().len()
                         );
 
-                        let element_opt = ui_tree.get_element_by_xpath(xpath);
+                        let element_opt = ui_tree.get_element_in_tree_by_xpath(xpath);
                         match element_opt {
                             Some(element) => {
                                 info!("Found UI element for xpath: {}", xpath);
@@ -96,7 +97,7 @@ pub fn launch_or_activate_application(
                                     "Activating (set focus) application window for element: {:?}",
                                     element
                                 );
-                                element.set_focus().map_err(|e| {
+                                element.get_element_props().set_focus().map_err(|e| {
                                     AppControlError::SetFocusFailed(format!("{:?}", e))
                                 })?;
                                 success = true;
@@ -1,10 +1,11 @@
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender, channel};
 use std::thread;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use pyo3::prelude::*;
+use pyo3::types::PyDict;
 
 use crate::exceptions::{AutomationError, ElementNotFoundError, TreeConstructionError};
 use crate::screen_context::ScreenContext;
@@ -12,7 +13,11 @@ use crate::uiauto::{
     get_ui_element_by_runtimeid, invoke_click, select_item, set_value, supports_invoke,
     supports_select, supports_value,
 };
-use uitree::{SaveUIElementXML, UITreeError, UITreeXML, get_all_elements_xml};
+use uitree::{
+    ElementFingerprint, ProgressUpdate, SaveUIElementXML, TreeView, UIElementInTreeXML,
+    UITreeError, UITreeXML, WalkOptions, WalkProgress, WindowScope, XpathCandidate, XpathValue,
+    XpathVariables, get_all_elements_xml_with_options,
+};
 
 use crate::app_control::launch_or_activate_application;
 
@@ -30,6 +35,7 @@ use uiautomation::types::ControlType;
 use bromium_common::get_ui_automation_instance;
 
 use log::{debug, error, info, trace, warn};
+use regex::Regex;
 
 /// Monotonic counter for unique screenshot filenames.
 static SCREENSHOT_COUNTER: AtomicU64 = AtomicU64::new(0);
@@ -67,16 +73,44 @@ impl Bromium {
         self.__repr__()
     }
     #[staticmethod]
+    #[pyo3(signature = (
+        timeout_ms,
+        window_title=None,
+        *,
+        process_id=None,
+        executable=None,
+        window_handle=None,
+        class_name=None,
+        title_pattern=None,
+        tree_view="control"
+    ))]
+    #[allow(clippy::too_many_arguments)]
     pub fn get_win_driver(
         py: Python<'_>,
         timeout_ms: u64,
         window_title: Option<String>,
+        process_id: Option<u32>,
+        executable: Option<String>,
+        window_handle: Option<isize>,
+        class_name: Option<String>,
+        title_pattern: Option<String>,
+        tree_view: &str,
     ) -> PyResult<WinDriver> {
         debug!(
             "Bromium::get_win_driver called with timeout: {}ms",
             timeout_ms
         );
-        let driver = WinDriver::new(py, timeout_ms, window_title)?;
+        let driver = WinDriver::new(
+            py,
+            timeout_ms,
+            window_title,
+            process_id,
+            executable,
+            window_handle,
+            class_name,
+            title_pattern,
+            tree_view,
+        )?;
         Ok(driver)
     }
 
@@ -128,6 +162,40 @@ impl Bromium {
     }
 }
 
+/// State properties of an `Element` as captured when the UI tree was built.
+#[derive(Debug, Clone, Default)]
+struct ElementState {
+    is_enabled: bool,
+    is_offscreen: bool,
+    has_keyboard_focus: bool,
+    is_keyboard_focusable: bool,
+    help_text: String,
+    accelerator_key: String,
+    access_key: String,
+    process_id: u32,
+    value: Option<String>,
+    toggle_state: Option<String>,
+    supported_patterns: Vec<String>,
+}
+
+impl From<&SaveUIElementXML> for ElementState {
+    fn from(props: &SaveUIElementXML) -> Self {
+        ElementState {
+            is_enabled: props.is_enabled(),
+            is_offscreen: props.is_offscreen(),
+            has_keyboard_focus: props.has_keyboard_focus(),
+            is_keyboard_focusable: props.is_keyboard_focusable(),
+            help_text: props.get_help_text().to_string(),
+            accelerator_key: props.get_accelerator_key().to_string(),
+            access_key: props.get_access_key().to_string(),
+            process_id: props.get_process_id(),
+            value: props.get_value().map(str::to_string),
+            toggle_state: props.get_toggle_state().map(str::to_string),
+            supported_patterns: props.get_supported_patterns().to_vec(),
+        }
+    }
+}
+
 #[pyclass]
 #[derive(Debug, Clone)]
 pub struct Element {
@@ -136,7 +204,13 @@ pub struct Element {
     handle: isize,
     control_type: String,
     runtime_id: Vec<i32>,
+    /// Key of the element in the UI tree (`RtID`), synthetic for elements
+    /// without a runtime ID.
+    rt_id: String,
     bounding_rectangle: RECT,
+    state: ElementState,
+    /// Identity of the element in the tree it was found in, used by `WinDriver.relocate`.
+    fingerprint: Option<ElementFingerprint>,
 }
 
 #[pymethods]
@@ -160,13 +234,21 @@ impl Element {
             right: bounding_rectangle.2,
             bottom: bounding_rectangle.3,
         };
+        let rt_id = if runtime_id.is_empty() {
+            String::new()
+        } else {
+            bromium_common::format_runtime_id(&runtime_id)
+        };
         Element {
             name,
             xpath,
             handle,
             control_type,
             runtime_id,
+            rt_id,
             bounding_rectangle,
+            state: ElementState::default(),
+            fingerprint: None,
         }
     }
 
@@ -242,6 +324,79 @@ impl Element {
         )
     }
 
+    /// Whether the element was enabled when the UI tree was built.
+    #[getter]
+    pub fn is_enabled(&self) -> bool {
+        self.state.is_enabled
+    }
+
+    /// Whether the element was scrolled or hidden out of view when the UI tree was built.
+    #[getter]
+    pub fn is_offscreen(&self) -> bool {
+        self.state.is_offscreen
+    }
+
+    /// Whether the element had the keyboard focus when the UI tree was built.
+    #[getter]
+    pub fn has_keyboard_focus(&self) -> bool {
+        self.state.has_keyboard_focus
+    }
+
+    /// Whether the element can receive the keyboard focus.
+    #[getter]
+    pub fn is_keyboard_focusable(&self) -> bool {
+        self.state.is_keyboard_focusable
+    }
+
+    /// The help text (tooltip) of the element.
+    #[getter]
+    pub fn help_text(&self) -> &str {
+        &self.state.help_text
+    }
+
+    /// The accelerator key (e.g. "Ctrl+S") of the element.
+    #[getter]
+    pub fn accelerator_key(&self) -> &str {
+        &self.state.accelerator_key
+    }
+
+    /// The access key (e.g. "Alt+F") of the element.
+    #[getter]
+    pub fn access_key(&self) -> &str {
+        &self.state.access_key
+    }
+
+    /// The ID of the process owning the element.
+    #[getter]
+    pub fn process_id(&self) -> u32 {
+        self.state.process_id
+    }
+
+    /// The current value, if the element supports the Value pattern.
+    #[getter]
+    pub fn value(&self) -> Option<String> {
+        self.state.value.clone()
+    }
+
+    /// The toggle state ("On", "Off" or "Indeterminate"), if the element supports the Toggle pattern.
+    #[getter]
+    pub fn toggle_state(&self) -> Option<String> {
+        self.state.toggle_state.clone()
+    }
+
+    /// Names of the UI Automation control patterns the element supports (e.g. "Invoke", "Value").
+    #[getter]
+    pub fn supported_patterns(&self) -> Vec<String> {
+        self.state.supported_patterns.clone()
+    }
+
+    /// Stable identity of the element (control type, automation id, class name,
+    /// name and ancestor path), or `None` if it was not taken from a UI tree.
+    #[getter]
+    pub fn fingerprint(&self) -> Option<String> {
+        self.fingerprint.as_ref().map(ElementFingerprint::key)
+    }
+
     // ─── Mouse methods ──────────────────────────────────────────────────
 
     pub fn send_click(&self) -> PyResult<()> {
@@ -423,12 +578,15 @@ impl Default for Element {
             handle: 0,
             control_type: String::new(),
             runtime_id: vec![],
+            rt_id: String::new(),
             bounding_rectangle: RECT {
                 left: 0,
                 top: 0,
                 right: 0,
                 bottom: 0,
             },
+            state: ElementState::default(),
+            fingerprint: None,
         }
     }
 }
@@ -506,9 +664,77 @@ impl ElementIterator {
     }
 }
 
+/// A generated XPath for an element, returned by `WinDriver.get_locator_candidates()`.
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct LocatorCandidate {
+    xpath: String,
+    strategy: String,
+    score: f64,
+    match_count: usize,
+}
+
+impl From<&XpathCandidate> for LocatorCandidate {
+    fn from(candidate: &XpathCandidate) -> Self {
+        Self {
+            xpath: candidate.get_xpath().to_string(),
+            strategy: candidate.get_strategy().name().to_string(),
+            score: candidate.get_score(),
+            match_count: candidate.get_match_count(),
+        }
+    }
+}
+
+#[pymethods]
+impl LocatorCandidate {
+    pub fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "<LocatorCandidate xpath=\"{}\" strategy='{}' score={:.2} match_count={}>",
+            self.xpath, self.strategy, self.score, self.match_count
+        ))
+    }
+
+    pub fn __str__(&self) -> PyResult<String> {
+        Ok(self.xpath.clone())
+    }
+
+    /// The XPath locator.
+    #[getter]
+    pub fn xpath(&self) -> &str {
+        &self.xpath
+    }
+
+    /// How the locator was built: `"attribute"`, `"anchored"` or `"positional"`.
+    #[getter]
+    pub fn strategy(&self) -> &str {
+        &self.strategy
+    }
+
+    /// Robustness between 0 and 1; higher is more stable.
+    #[getter]
+    pub fn score(&self) -> f64 {
+        self.score
+    }
+
+    /// Number of elements the locator matched in the tree it was generated from.
+    #[getter]
+    pub fn match_count(&self) -> usize {
+        self.match_count
+    }
+
+    /// Whether the locator matched exactly one element.
+    #[getter]
+    pub fn is_unique(&self) -> bool {
+        self.match_count == 1
+    }
+}
+
 /// Default timeout (in seconds) for tree-construction `recv_timeout` calls.
 const DEFAULT_TREE_TIMEOUT_SECS: u64 = 120;
 
+/// Interval (in milliseconds) at which progress is forwarded to a Python callback.
+const PROGRESS_POLL_MILLIS: u64 = 200;
+
 #[pyclass]
 #[derive(Debug, Clone)]
 pub struct WinDriver {
@@ -517,6 +743,10 @@ pub struct WinDriver {
     tree_timeout_secs: u64,
     ui_tree: UITreeXML,
     window_title: Option<String>,
+    /// Restricts tree walks to the top-level windows of one application or window.
+    window_scope: Option<WindowScope>,
+    /// UI Automation view (raw, control or content) every tree walk follows.
+    tree_view: TreeView,
     /// Cancellation flag for the most recently spawned tree-construction thread.
     /// Set to `true` on timeout to signal the orphaned thread to exit early.
     cancel_flag: Arc<AtomicBool>,
@@ -527,10 +757,11 @@ impl WinDriver {
         &self.ui_tree
     }
 
-    /// Convert a `SaveUIElement` (from the uitree crate) into a Python-facing `Element`.
-    fn element_from_save_ui(props: &SaveUIElementXML) -> Element {
+    /// Convert a `SaveUIElement` (from the uitree crate) with the tree key `rt_id`
+    /// into a Python-facing `Element`.
+    fn element_from_save_ui(props: &SaveUIElementXML, rt_id: &str) -> Element {
         let bounding_rect = props.get_bounding_rectangle();
-        Element::new(
+        let mut element = Element::new(
             props.get_name().to_string(),
             props.get_xpath().unwrap_or_default().to_string(),
             props.get_handle(),
@@ -542,7 +773,21 @@ impl WinDriver {
                 bounding_rect.get_right(),
                 bounding_rect.get_bottom(),
             ),
-        )
+        );
+        element.rt_id = rt_id.to_string();
+        element.state = ElementState::from(props);
+        element
+    }
+
+    /// Convert an element of the UI tree into a Python-facing `Element`.
+    fn element_from_tree(elem: &UIElementInTreeXML) -> Element {
+        Self::element_from_save_ui(elem.get_element_props(), elem.get_rt_id())
+    }
+
+    /// Attach the element's fingerprint in the current tree, looked up by `RtID`.
+    fn with_fingerprint(&self, mut element: Element) -> Element {
+        element.fingerprint = self.ui_tree.fingerprint_by_rt_id(&element.rt_id);
+        element
     }
 
     /// Collect all elements in the tree as Python `Element` objects.
@@ -550,15 +795,117 @@ impl WinDriver {
         self.ui_tree
             .get_elements()
             .iter()
-            .map(|uit| Self::element_from_save_ui(uit.get_element_props()))
+            .map(|uit| self.with_fingerprint(Self::element_from_tree(uit)))
             .collect()
     }
+
+    /// Build the window scope from the constructor arguments; `None` when none are given.
+    fn window_scope_from_args(
+        process_id: Option<u32>,
+        executable: Option<String>,
+        window_handle: Option<isize>,
+        class_name: Option<String>,
+        title_pattern: Option<String>,
+    ) -> PyResult<Option<WindowScope>> {
+        let mut scope = WindowScope::default();
+        let mut scoped = false;
+        if let Some(process_id) = process_id {
+            scope = scope.with_process_id(process_id);
+            scoped = true;
+        }
+        if let Some(executable) = executable {
+            scope = scope.with_executable(executable);
+            scoped = true;
+        }
+        if let Some(handle) = window_handle {
+            scope = scope.with_handle(handle);
+            scoped = true;
+        }
+        if let Some(class_name) = class_name {
+            scope = scope.with_class_name(class_name);
+            scoped = true;
+        }
+        if let Some(pattern) = title_pattern {
+            let title = Regex::new(&pattern).map_err(|e| {
+                pyo3::exceptions::PyValueError::new_err(format!(
+                    "Invalid title pattern '{}': {}",
+                    pattern, e
+                ))
+            })?;
+            scope = scope.with_title(title);
+            scoped = true;
+        }
+        Ok(scoped.then_some(scope))
+    }
+
+    /// Parse a tree view name from Python (`"raw"`, `"control"` or `"content"`).
+    fn tree_view_from_arg(view: &str) -> PyResult<TreeView> {
+        TreeView::from_name(view).ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "Invalid tree view '{}', expected 'raw', 'control' or 'content'",
+                view
+            ))
+        })
+    }
+
+    /// Convert a Python dict of XPath variables into typed bindings.
+    /// Values may be `str`, `int`, `float` or `bool`.
+    fn xpath_variables_from_arg(variables: Option<&Bound<'_, PyDict>>) -> PyResult<XpathVariables> {
+        let mut bindings = XpathVariables::new();
+        let Some(variables) = variables else {
+            return Ok(bindings);
+        };
+        for (key, value) in variables.iter() {
+            let name: String = key.extract().map_err(|_| {
+                pyo3::exceptions::PyTypeError::new_err("XPath variable names must be strings")
+            })?;
+            // bool is checked first because Python bools also extract as int
+            let value = if let Ok(b) = value.extract::<bool>() {
+                XpathValue::Boolean(b)
+            } else if let Ok(i) = value.extract::<i64>() {
+                XpathValue::Integer(i)
+            } else if let Ok(d) = value.extract::<f64>() {
+                XpathValue::Double(d)
+            } else if let Ok(text) = value.extract::<String>() {
+                XpathValue::String(text)
+            } else {
+                return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+                    "Unsupported value for XPath variable '{}': expected str, int, float or bool",
+                    name
+                )));
+            };
+            bindings.set(name, value);
+        }
+        Ok(bindings)
+    }
 }
 
 #[pymethods]
 impl WinDriver {
     #[new]
-    pub fn new(py: Python<'_>, timeout_ms: u64, window_title: Option<String>) -> PyResult<Self> {
+    #[pyo3(signature = (
+        timeout_ms,
+        window_title=None,
+        *,
+        process_id=None,
+        executable=None,
+        window_handle=None,
+        class_name=None,
+        title_pattern=None,
+        tree_view="control"
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        py: Python<'_>,
+        timeout_ms: u64,
+        window_title: Option<String>,
+        process_id: Option<u32>,
+        executable: Option<String>,
+        window_handle: Option<isize>,
+        class_name: Option<String>,
+        title_pattern: Option<String>,
+        tree_view: &str,
+    ) -> PyResult<Self> {
         if let Some(title) = window_title.as_deref() {
             debug!(
                 "Creating new WinDriver with timeout: {}ms and window title filter: '{}'",
@@ -568,13 +915,28 @@ impl WinDriver {
             debug!("Creating new WinDriver with timeout: {}ms", timeout_ms);
         }
 
+        let window_scope = Self::window_scope_from_args(
+            process_id,
+            executable,
+            window_handle,
+            class_name,
+            title_pattern,
+        )?;
+        if let Some(scope) = window_scope.as_ref() {
+            debug!("Scoping UI tree to windows matching {:?}", scope);
+        }
+        let tree_view = Self::tree_view_from_arg(tree_view)?;
+
         let cancel_flag = Arc::new(AtomicBool::new(false));
         let window_title_clone = window_title.clone();
+        let window_scope_clone = window_scope.clone();
 
         let tree_result = py.allow_threads(move || {
             Self::spawn_tree_construction(
                 cancel_flag,
                 window_title_clone,
+                window_scope_clone,
+                tree_view,
                 Some(2),
                 Duration::from_secs(DEFAULT_TREE_TIMEOUT_SECS),
             )
@@ -602,6 +964,8 @@ impl WinDriver {
             tree_timeout_secs: DEFAULT_TREE_TIMEOUT_SECS,
             ui_tree,
             window_title,
+            window_scope,
+            tree_view,
             cancel_flag: Arc::new(AtomicBool::new(false)),
         };
 
@@ -666,6 +1030,19 @@ impl WinDriver {
         self.window_title = window_title;
     }
 
+    /// The UI Automation view the tree is walked in: "raw", "control" or "content".
+    #[getter]
+    pub fn tree_view(&self) -> &'static str {
+        self.tree_view.name()
+    }
+
+    /// Set the tree view; takes effect with the next refresh.
+    #[setter]
+    pub fn set_tree_view(&mut self, tree_view: &str) -> PyResult<()> {
+        self.tree_view = Self::tree_view_from_arg(tree_view)?;
+        Ok(())
+    }
+
     // ─── Collection protocols (R-08) ───────────────────────────────────���────
 
     /// Returns the number of UI elements in the tree (`len(driver)`).
@@ -735,13 +1112,83 @@ impl WinDriver {
                 }
                 true
             })
-            .map(|uit| Self::element_from_save_ui(uit.get_element_props()))
+            .map(|uit| self.with_fingerprint(Self::element_from_tree(uit)))
             .collect();
 
         debug!("find_elements returned {} results", results.len());
         Ok(results)
     }
 
+    /// Find `element` in the current UI tree after its runtime ID changed, e.g.
+    /// after `refresh()` or an application restart. The element is looked up by
+    /// runtime ID first, then by the most similar fingerprint with a confidence
+    /// of at least `min_confidence`.
+    #[pyo3(signature = (element, min_confidence=0.6))]
+    pub fn relocate(&self, element: &Element, min_confidence: f64) -> PyResult<Element> {
+        debug!(
+            "WinDriver::relocate called for element '{}' with min confidence {}",
+            element.name, min_confidence
+        );
+
+        let by_runtime_id = self
+            .ui_tree
+            .get_tree()
+            .get_element_by_runtime_id(&element.rt_id)
+            .map(|node| node.index)
+            .filter(|&index| self.ui_tree.node(index).1.get_control_type() == element.control_type);
+        let index = by_runtime_id.or_else(|| {
+            let fingerprint = element.fingerprint.as_ref()?;
+            let (index, confidence) = self
+                .ui_tree
+                .find_by_fingerprint(fingerprint, min_confidence)?;
+            debug!(
+                "Relocated element by fingerprint with confidence {:.2}",
+                confidence
+            );
+            Some(index)
+        });
+
+        let Some(index) = index else {
+            warn!("Could not relocate element '{}'", element.name);
+            return Err(ElementNotFoundError::new_err(format!(
+                "Element '{}' not found in the current UI tree (runtime_id={:?})",
+                element.name, element.runtime_id
+            )));
+        };
+        let rt_id = self.ui_tree.get_tree().node(index).get_runtime_id();
+        let mut found = Self::element_from_save_ui(self.ui_tree.node(index).1, rt_id);
+        found.xpath = self
+            .ui_tree
+            .get_xpath_for_element(index, true)
+            .unwrap_or_default();
+        Ok(self.with_fingerprint(found))
+    }
+
+    /// Generate XPath locator candidates for `element`, best first. Each
+    /// candidate records how many elements it matched in the current tree.
+    pub fn get_locator_candidates(&self, element: &Element) -> PyResult<Vec<LocatorCandidate>> {
+        debug!(
+            "WinDriver::get_locator_candidates called for element '{}'",
+            element.name
+        );
+        let index = self
+            .ui_tree
+            .get_tree()
+            .get_element_by_runtime_id(&element.rt_id)
+            .map(|node| node.index)
+            .ok_or_else(|| {
+                ElementNotFoundError::new_err(format!(
+                    "Element '{}' not found in the current UI tree (runtime_id={:?})",
+                    element.name, element.runtime_id
+                ))
+            })?;
+        let candidates = self
+            .ui_tree
+            .get_xpath_candidates_for_element(index)
+            .map_err(|e| AutomationError::new_err(format!("Locator generation failed: {}", e)))?;
+        Ok(candidates.iter().map(LocatorCandidate::from).collect())
+    }
+
     // ─── Actions ─────────────────────────────────────────────────────────────
 
     pub fn get_cursor_pos(&self) -> PyResult<(i32, i32)> {
@@ -756,9 +1203,15 @@ impl WinDriver {
         Ok((point.x, point.y))
     }
 
-    pub fn refresh(&mut self, py: Python<'_>, window_title: Option<String>) -> PyResult<()> {
+    #[pyo3(signature = (window_title=None, progress=None))]
+    pub fn refresh(
+        &mut self,
+        py: Python<'_>,
+        window_title: Option<String>,
+        progress: Option<PyObject>,
+    ) -> PyResult<()> {
         debug!("WinDriver::refresh called.");
-        self.refresh_ui_tree(py, window_title)
+        self.refresh_ui_tree(py, window_title, progress)
     }
 
     pub fn get_element_by_coordinates(&self, x: i32, y: i32) -> PyResult<Element> {
@@ -770,7 +1223,7 @@ impl WinDriver {
         let cursor_position = POINT { x, y };
 
         if let Some(ui_element_in_tree) =
-            crate::rectangle::get_point_bounding_rect(&cursor_position, self.ui_tree.get_elements())
+            crate::rectangle::get_point_bounding_rect(&cursor_position, &self.ui_tree)
         {
             let xpath = self
                 .ui_tree
@@ -778,28 +1231,13 @@ impl WinDriver {
                 .unwrap_or_default();
             trace!("Found element with xpath: {}", xpath);
 
-            let ui_element_props = ui_element_in_tree.get_element_props();
-            let bounding_rect = ui_element_props.get_bounding_rectangle();
-            let control_type = ui_element_props.get_control_type();
-
-            let element = Element::new(
-                ui_element_props.get_name().to_string(),
-                xpath,
-                ui_element_props.get_handle(),
-                control_type.to_string(),
-                ui_element_props.get_runtime_id().to_vec(),
-                (
-                    bounding_rect.get_left(),
-                    bounding_rect.get_top(),
-                    bounding_rect.get_right(),
-                    bounding_rect.get_bottom(),
-                ),
-            );
+            let mut element = Self::element_from_tree(ui_element_in_tree);
+            element.xpath = xpath;
             info!(
                 "Successfully found element at ({}, {}): {}",
                 x, y, element.name
             );
-            Ok(element)
+            Ok(self.with_fingerprint(element))
         } else {
             warn!("No element found at coordinates ({}, {})", x, y);
             Err(ElementNotFoundError::new_err(format!(
@@ -812,17 +1250,23 @@ impl WinDriver {
     /// Find a single element by XPath. If not found immediately, retries
     /// until `timeout_ms` elapses. When `timeout_ms` is `None`, the driver's
     /// default `timeout_ms` is used; pass `Some(0)` to disable retrying.
+    /// `variables` binds `$name` references in the expression.
+    #[pyo3(signature = (xpath, timeout_ms=None, variables=None))]
     pub fn get_element_by_xpath(
         &mut self,
         py: Python<'_>,
         xpath: String,
         timeout_ms: Option<u64>,
+        variables: Option<&Bound<'_, PyDict>>,
     ) -> PyResult<Element> {
         debug!("WinDriver::get_element_by_xpath called.");
 
+        let variables = Self::xpath_variables_from_arg(variables)?;
         debug!("Searching for element with xpath: {}", xpath);
         trace!("UI Tree has {} elements", self.ui_tree.get_elements().len());
-        let ui_elem = self.ui_tree.get_element_by_xpath(xpath.as_str());
+        let ui_elem = self
+            .ui_tree
+            .get_element_in_tree_by_xpath_with_variables(xpath.as_str(), &variables);
 
         if ui_elem.is_none() {
             // Resolve effective timeout: explicit param > driver default
@@ -841,7 +1285,11 @@ impl WinDriver {
                 }
 
                 while start_time.elapsed().as_millis() < effective_timeout as u128 {
-                    let window_title_filter = self.window_title.clone();
+                    let options = WalkOptions::default()
+                        .with_element_state(true)
+                        .with_target_window_caption(self.window_title.clone())
+                        .with_window_scope(self.window_scope.clone())
+                        .with_tree_view(self.tree_view);
                     let scoped_root_clone = scoped_root.clone();
                     // Cancel any previously orphaned tree-construction thread
                     self.cancel_flag.store(true, Ordering::Relaxed);
@@ -852,12 +1300,10 @@ impl WinDriver {
                             channel();
                         let cancel_clone = Some(cancel_flag.clone());
                         thread::spawn(move || {
-                            get_all_elements_xml(
+                            get_all_elements_xml_with_options(
                                 tx,
                                 scoped_root_clone,
-                                None,
-                                None,
-                                window_title_filter,
+                                options,
                                 cancel_clone,
                             );
                         });
@@ -878,23 +1324,14 @@ impl WinDriver {
                             TreeConstructionError::new_err(format!("UI tree refresh failed: {}", e))
                         })?;
 
-                    let ui_elem_retry = self.ui_tree.get_element_by_xpath(xpath.as_str());
+                    let ui_elem_retry = self
+                        .ui_tree
+                        .get_element_in_tree_by_xpath_with_variables(xpath.as_str(), &variables);
                     if let Some(element) = ui_elem_retry {
                         debug!("Element found after refresh.");
-                        let bounding_rectangle = element.get_bounding_rectangle();
-                        return Ok(Element::new(
-                            element.get_name().to_string(),
-                            xpath.clone(),
-                            element.get_handle(),
-                            element.get_control_type().to_string(),
-                            element.get_runtime_id().to_vec(),
-                            (
-                                bounding_rectangle.get_left(),
-                                bounding_rectangle.get_top(),
-                                bounding_rectangle.get_right(),
-                                bounding_rectangle.get_bottom(),
-                            ),
-                        ));
+                        let mut found = Self::element_from_tree(element);
+                        found.xpath = xpath.clone();
+                        return Ok(self.with_fingerprint(found));
                     }
                     trace!("Element still not found after refresh, trying again.");
                     py.allow_threads(|| thread::sleep(Duration::from_millis(250)));
@@ -916,31 +1353,27 @@ impl WinDriver {
             }
         }
 
-        let element = ui_elem.unwrap();
-        let bounding_rectangle = element.get_bounding_rectangle();
-        Ok(Element::new(
-            element.get_name().to_string(),
-            xpath,
-            element.get_handle(),
-            element.get_control_type().to_string(),
-            element.get_runtime_id().to_vec(),
-            (
-                bounding_rectangle.get_left(),
-                bounding_rectangle.get_top(),
-                bounding_rectangle.get_right(),
-                bounding_rectangle.get_bottom(),
-            ),
-        ))
+        let mut found = Self::element_from_tree(ui_elem.unwrap());
+        found.xpath = xpath;
+        Ok(self.with_fingerprint(found))
     }
 
-    pub fn get_elements_by_xpath(&self, xpath: String) -> PyResult<Vec<Element>> {
+    /// Find all elements matching an XPath expression. `variables` binds
+    /// `$name` references in the expression.
+    #[pyo3(signature = (xpath, variables=None))]
+    pub fn get_elements_by_xpath(
+        &self,
+        xpath: String,
+        variables: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Vec<Element>> {
         debug!("WinDriver::get_elements_by_xpath called.");
 
+        let variables = Self::xpath_variables_from_arg(variables)?;
         debug!("Searching for elements with xpath: {}", xpath);
         trace!("UI Tree has {} elements", self.ui_tree.get_elements().len());
         let elements = self
             .ui_tree
-            .get_elements_by_xpath(xpath.as_str())
+            .get_elements_in_tree_by_xpath_with_variables(xpath.as_str(), &variables)
             .unwrap_or_default();
 
         if elements.is_empty() {
@@ -950,20 +1383,9 @@ impl WinDriver {
         let results: Vec<Element> = elements
             .iter()
             .map(|element| {
-                let bounding_rectangle = element.get_bounding_rectangle();
-                Element::new(
-                    element.get_name().to_string(),
-                    xpath.clone(),
-                    element.get_handle(),
-                    element.get_control_type().to_string(),
-                    element.get_runtime_id().to_vec(),
-                    (
-                        bounding_rectangle.get_left(),
-                        bounding_rectangle.get_top(),
-                        bounding_rectangle.get_right(),
-                        bounding_rectangle.get_bottom(),
-                    ),
-                )
+                let mut found = Self::element_from_tree(element);
+                found.xpath = xpath.clone();
+                self.with_fingerprint(found)
             })
             .collect();
         Ok(results)
@@ -1061,8 +1483,8 @@ impl WinDriver {
         match result {
             Ok(save_ui_elem) => {
                 info!("Application launched or activated successfully.");
-                let ui_elem = Self::element_from_save_ui(&save_ui_elem);
-                Ok(ui_elem)
+                let ui_elem = Self::element_from_tree(&save_ui_elem);
+                Ok(self.with_fingerprint(ui_elem))
             }
             Err(e) => {
                 error!("Error launching or activating application: {}", e);
@@ -1074,10 +1496,12 @@ impl WinDriver {
         }
     }
 
+    #[pyo3(signature = (window_title=None, progress=None))]
     pub fn refresh_ui_tree(
         &mut self,
         py: Python<'_>,
         window_title: Option<String>,
+        progress: Option<PyObject>,
     ) -> PyResult<()> {
         debug!("WinDriver::refresh_ui_tree called (GIL-releasing).");
 
@@ -1086,10 +1510,41 @@ impl WinDriver {
         self.cancel_flag = Arc::clone(&cancel_flag);
 
         let window_title_filter = window_title.or_else(|| self.window_title.clone());
+        let window_scope = self.window_scope.clone();
+        let tree_view = self.tree_view;
         let tree_timeout = Duration::from_secs(self.tree_timeout_secs);
 
-        let tree_result = py.allow_threads(move || {
-            Self::spawn_tree_construction(cancel_flag, window_title_filter, None, tree_timeout)
+        let tree_result = py.allow_threads(move || match progress {
+            Some(callback) => Self::spawn_tree_construction_with_progress(
+                cancel_flag,
+                window_title_filter,
+                window_scope,
+                tree_view,
+                tree_timeout,
+                |update| {
+                    Python::with_gil(|py| {
+                        if let Err(e) = callback.call1(
+                            py,
+                            (
+                                update.elements_processed,
+                                update.current_window.clone(),
+                                update.depth,
+                                update.elapsed.as_secs_f64(),
+                            ),
+                        ) {
+                            warn!("Progress callback failed: {}", e);
+                        }
+                    })
+                },
+            ),
+            None => Self::spawn_tree_construction(
+                cancel_flag,
+                window_title_filter,
+                window_scope,
+                tree_view,
+                None,
+                tree_timeout,
+            ),
         });
 
         self.ui_tree = tree_result
@@ -1120,16 +1575,23 @@ impl WinDriver {
     fn spawn_tree_construction(
         cancel_flag: Arc<AtomicBool>,
         window_title: Option<String>,
+        window_scope: Option<WindowScope>,
+        tree_view: TreeView,
         max_depth: Option<usize>,
         timeout: Duration,
     ) -> Result<Result<UITreeXML, UITreeError>, std::sync::mpsc::RecvTimeoutError> {
-        Self::spawn_tree_construction_scoped(cancel_flag, window_title, max_depth, timeout, None)
+        let options = WalkOptions::default()
+            .with_element_state(true)
+            .with_max_depth(max_depth)
+            .with_target_window_caption(window_title)
+            .with_window_scope(window_scope)
+            .with_tree_view(tree_view);
+        Self::spawn_tree_construction_scoped(cancel_flag, options, timeout, None)
     }
 
     fn spawn_tree_construction_scoped(
         cancel_flag: Arc<AtomicBool>,
-        window_title: Option<String>,
-        max_depth: Option<usize>,
+        options: WalkOptions,
         timeout: Duration,
         root_element: Option<SaveUIElementXML>,
     ) -> Result<Result<UITreeXML, UITreeError>, std::sync::mpsc::RecvTimeoutError> {
@@ -1137,7 +1599,7 @@ impl WinDriver {
         let cancel_clone = Some(Arc::clone(&cancel_flag));
         thread::spawn(move || {
             debug!("Spawning thread to get UI tree");
-            get_all_elements_xml(tx, root_element, max_depth, None, window_title, cancel_clone);
+            get_all_elements_xml_with_options(tx, root_element, options, cancel_clone);
         });
 
         let result = rx.recv_timeout(timeout);
@@ -1147,6 +1609,53 @@ impl WinDriver {
         result
     }
 
+    /// Like [`Self::spawn_tree_construction`], but calls `on_progress` with the
+    /// latest walk progress while waiting for the result.
+    fn spawn_tree_construction_with_progress(
+        cancel_flag: Arc<AtomicBool>,
+        window_title: Option<String>,
+        window_scope: Option<WindowScope>,
+        tree_view: TreeView,
+        timeout: Duration,
+        mut on_progress: impl FnMut(&ProgressUpdate),
+    ) -> Result<Result<UITreeXML, UITreeError>, RecvTimeoutError> {
+        let (tx, rx): (Sender<_>, Receiver<Result<UITreeXML, UITreeError>>) = channel();
+        let (tx_progress, rx_progress) = channel();
+        let options = WalkOptions::default()
+            .with_element_state(true)
+            .with_target_window_caption(window_title)
+            .with_window_scope(window_scope)
+            .with_tree_view(tree_view)
+            .with_progress(tx_progress);
+        let cancel_clone = Some(Arc::clone(&cancel_flag));
+        thread::spawn(move || {
+            debug!("Spawning thread to get UI tree with progress reporting");
+            get_all_elements_xml_with_options(tx, None, options, cancel_clone);
+        });
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let latest = rx_progress
+                .try_iter()
+                .fold(None, |latest, event| match event {
+                    WalkProgress::Update(update) => Some(update),
+                    WalkProgress::WindowCompleted(_) => latest,
+                });
+            if let Some(update) = latest {
+                on_progress(&update);
+            }
+
+            match rx.recv_timeout(Duration::from_millis(PROGRESS_POLL_MILLIS)) {
+                Ok(result) => return Ok(result),
+                Err(RecvTimeoutError::Timeout) if Instant::now() < deadline => continue,
+                Err(e) => {
+                    cancel_flag.store(true, Ordering::Relaxed);
+                    return Err(e);
+                }
+            }
+        }
+    }
+
     /// Refresh the UI tree without requiring a Python GIL token.
     /// Used by internal callers (e.g. `launch_or_activate_application`).
     pub fn refresh_ui_tree_internal(
@@ -1162,8 +1671,14 @@ impl WinDriver {
         let window_title_filter = window_title.or_else(|| self.window_title.clone());
         let tree_timeout = Duration::from_secs(self.tree_timeout_secs);
 
-        let tree_result =
-            Self::spawn_tree_construction(cancel_flag, window_title_filter, None, tree_timeout);
+        let tree_result = Self::spawn_tree_construction(
+            cancel_flag,
+            window_title_filter,
+            self.window_scope.clone(),
+            self.tree_view,
+            None,
+            tree_timeout,
+        );
 
         self.ui_tree = tree_result
             .map_err(|e| {
@@ -1195,8 +1710,14 @@ impl WinDriver {
 
         let tree_timeout = Duration::from_secs(self.tree_timeout_secs);
 
-        let tree_result =
-            Self::spawn_tree_construction(cancel_flag, None, Some(2_usize), tree_timeout);
+        let tree_result = Self::spawn_tree_construction(
+            cancel_flag,
+            None,
+            self.window_scope.clone(),
+            self.tree_view,
+            Some(2_usize),
+            tree_timeout,
+        );
 
         self.ui_tree = tree_result
             .map_err(|e| {
@@ -1306,6 +1827,113 @@ mod tests {
         assert_eq!(elem.bounding_rectangle(), (0, 0, 0, 0));
     }
 
+    #[test]
+    fn test_element_from_save_ui_copies_state() {
+        let props = SaveUIElementXML::default()
+            .with_name("Username")
+            .with_control_type("Edit")
+            .with_enabled(true)
+            .with_keyboard_focus(true)
+            .with_process_id(1234)
+            .with_value(Some("alice".to_string()))
+            .with_supported_patterns(vec!["Value".to_string()]);
+        let elem = WinDriver::element_from_save_ui(&props, "");
+        assert_eq!(elem.name(), "Username");
+        assert!(elem.is_enabled());
+        assert!(elem.has_keyboard_focus());
+        assert!(!elem.is_offscreen());
+        assert_eq!(elem.process_id(), 1234);
+        assert_eq!(elem.value(), Some("alice".to_string()));
+        assert_eq!(elem.toggle_state(), None);
+        assert_eq!(elem.supported_patterns(), vec!["Value".to_string()]);
+    }
+
+    /// Driver over a walked fake desktop with a focused, filled-in Edit.
+    fn fake_driver() -> WinDriver {
+        let mut backend = uitree::FakeBackend::new(
+            SaveUIElementXML::default()
+                .with_name("Desktop")
+                .with_control_type("Pane")
+                .with_runtime_id(vec![42, 1]),
+        );
+        let app = backend.add_child(
+            0,
+            SaveUIElementXML::default()
+                .with_name("App")
+                .with_control_type("Window")
+                .with_runtime_id(vec![42, 2]),
+        );
+        backend.add_child(
+            app,
+            SaveUIElementXML::default()
+                .with_name("Username")
+                .with_control_type("Edit")
+                .with_runtime_id(vec![42, 3])
+                .with_enabled(true)
+                .with_keyboard_focus(true)
+                .with_value(Some("alice".to_string()))
+                .with_supported_patterns(vec!["Value".to_string()]),
+        );
+        // no runtime ID, keyed by a synthetic RtID in the tree
+        backend.add_child(
+            app,
+            SaveUIElementXML::default()
+                .with_name("Sign in")
+                .with_control_type("Button"),
+        );
+        let (tx, rx) = channel();
+        let options = WalkOptions::default();
+        uitree::get_all_elements_xml_with_backend(tx, &backend, None, &options, None);
+        WinDriver {
+            timeout_ms: 0,
+            tree_timeout_secs: DEFAULT_TREE_TIMEOUT_SECS,
+            ui_tree: rx.recv().unwrap().unwrap(),
+            window_title: None,
+            window_scope: None,
+            tree_view: TreeView::default(),
+            cancel_flag: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    #[test]
+    fn test_element_found_by_xpath_has_state() {
+        let driver = fake_driver();
+        let found = driver.get_elements_by_xpath("//Edit".to_string(), None).unwrap();
+        assert_eq!(found.len(), 1);
+        let edit = &found[0];
+        assert_eq!(edit.xpath(), "//Edit");
+        assert!(edit.is_enabled());
+        assert!(edit.has_keyboard_focus());
+        assert_eq!(edit.value(), Some("alice".to_string()));
+        assert_eq!(edit.supported_patterns(), vec!["Value".to_string()]);
+    }
+
+    #[test]
+    fn test_element_without_runtime_id_keeps_tree_key() {
+        let driver = fake_driver();
+        let found = driver.get_elements_by_xpath("//Button".to_string(), None).unwrap();
+        let button = &found[0];
+        assert!(button.runtime_id().is_empty());
+        assert_ne!(button.rt_id, bromium_common::format_runtime_id(&[]));
+        assert!(button.fingerprint.is_some());
+
+        assert!(!driver.get_locator_candidates(button).unwrap().is_empty());
+        let relocated = driver.relocate(button, 0.6).unwrap();
+        assert_eq!(relocated.rt_id, button.rt_id);
+        assert_eq!(relocated.name, "Sign in");
+
+        // a hand-made element without runtime ID does not match any tree key
+        let made = Element::new(
+            "Sign in".to_string(),
+            "//Button".to_string(),
+            0,
+            "Button".to_string(),
+            vec![],
+            (0, 0, 0, 0),
+        );
+        assert!(driver.get_locator_candidates(&made).is_err());
+    }
+
     #[test]
     fn test_element_repr_contains_fields() {
         let elem = make_element("OK", "/Root/Button", "Button", 99);
@@ -1362,7 +1990,7 @@ mod tests {
     #[test]
     fn test_element_from_save_ui_default() {
         let save = SaveUIElementXML::default();
-        let elem = WinDriver::element_from_save_ui(&save);
+        let elem = WinDriver::element_from_save_ui(&save, "");
         assert_eq!(elem.name(), "");
         assert_eq!(elem.xpath(), "");
         assert_eq!(elem.control_type(), "");
@@ -1375,7 +2003,7 @@ mod tests {
     fn test_element_from_save_ui_with_xpath() {
         let mut save = SaveUIElementXML::default();
         save.set_xpath("//Button[@Name='OK']".to_string());
-        let elem = WinDriver::element_from_save_ui(&save);
+        let elem = WinDriver::element_from_save_ui(&save, "");
         assert_eq!(elem.xpath(), "//Button[@Name='OK']");
     }
 
@@ -15,7 +15,10 @@ use windows::Win32::UI::WindowsAndMessaging::{GA_ROOT, GetAncestor, GetCursorPos
 
 #[allow(unused)]
 use crate::{AppContext, border_window::BorderWindow, rectangle}; //winevent
-use uitree::{SaveUIElementXML, UIElementInTreeXML, UITreeError, UITreeXML, get_all_elements_xml}; //SaveUIElement,
+use uitree::{
+    SaveUIElementXML, TreeView, UITreeError, UITreeXML, WalkOptions, XpathCandidate,
+    get_all_elements_xml_with_options,
+}; //SaveUIElement,
 use winevent_monitor::WinEventMonitor;
 
 #[derive(Clone, Debug)]
@@ -200,7 +203,10 @@ pub struct UIExplorer {
     xpath_input: Option<String>,
     xpath_eval_result: Option<XpathResult>,
     xpath_highlighting: bool,
+    /// Locator candidates of the selected tree node, keyed by its index.
+    xpath_candidates: Option<(usize, Vec<XpathCandidate>)>,
     ui_tree: UITreeXML,
+    tree_view: TreeView,
     tree_state: Option<TreeState>,
     history: DeduplicatedHistory,
     status_msg: Option<AppStatusMsg>,
@@ -217,8 +223,11 @@ impl UIExplorer {
         let cancel_flag = Arc::new(AtomicBool::new(false));
         let cancel_clone = Some(Arc::clone(&cancel_flag));
         let (tx, rx): (Sender<_>, Receiver<Result<UITreeXML, UITreeError>>) = channel();
+        let options = WalkOptions::default()
+            .with_calling_window_caption(Some(app_name))
+            .with_element_state(true);
         thread::spawn(move || {
-            get_all_elements_xml(tx, None, None, Some(app_name), None, cancel_clone);
+            get_all_elements_xml_with_options(tx, None, options, cancel_clone);
         });
 
         const TREE_TIMEOUT_SECS: u64 = 120;
@@ -262,6 +271,8 @@ impl UIExplorer {
             xpath_input: None,
             xpath_eval_result: None,
             xpath_highlighting: false,
+            xpath_candidates: None,
+            tree_view: ui_tree.get_tree_view(),
             ui_tree,
             tree_state: None,
             history: DeduplicatedHistory::default(),
@@ -292,6 +303,8 @@ impl UIExplorer {
             xpath_input: None,
             xpath_eval_result: None,
             xpath_highlighting: false,
+            xpath_candidates: None,
+            tree_view: ui_tree.get_tree_view(),
             ui_tree,
             tree_state: None,
             history: DeduplicatedHistory::default(),
@@ -488,7 +501,7 @@ impl UIExplorer {
 
                     // for the visual event summary
                     if self.show_history {
-                        let summary = event_summary(event, self.ui_tree.get_elements());
+                        let summary = event_summary(event, &self.ui_tree);
                         let full = format!("{event:#?}");
                         self.history.add(summary, full);
                     }
@@ -546,6 +559,24 @@ impl UIExplorer {
                         ui.label(" | ");
                         ui.add_space(2.0);
 
+                        let prev_view = self.tree_view;
+                        egui::ComboBox::from_label("View")
+                            .selected_text(self.tree_view.name())
+                            .show_ui(ui, |ui| {
+                                for view in TreeView::ALL {
+                                    ui.selectable_value(&mut self.tree_view, view, view.name());
+                                }
+                            })
+                            .response
+                            .on_hover_text("The UI Automation view the tree is walked in. The raw view also shows elements that legacy and custom-drawn applications only expose there, the content view gives a smaller tree.");
+                        if self.tree_view != prev_view {
+                            self.app_mode = AppMode::NeedsTreeRefresh;
+                            self.set_status(format!("Walking the {} view...", self.tree_view.name()), Duration::seconds(5));
+                        }
+                        ui.add_space(2.0);
+                        ui.label(" | ");
+                        ui.add_space(2.0);
+
                         ui.checkbox(&mut self.simple_xpath, "Simple XPath").on_hover_text("When enabled, the generated XPath will avoid using the Name attribute even if it is unique. This can be useful when a pure positional path is desired.");
 
                         ui.add_space(2.0);
@@ -696,13 +727,75 @@ impl UIExplorer {
                             ui.label(active_element.get_automation_id().to_owned());
                             ui.end_row();
 
-                            let xpath = self
-                                .ui_tree
-                                .get_xpath_for_element(
-                                    state.active_ui_element.unwrap_or(0),
-                                    self.simple_xpath,
-                                )
-                                .unwrap_or_default();
+                            ui.label("Enabled:");
+                            ui.label(active_element.is_enabled().to_string());
+                            ui.end_row();
+
+                            ui.label("Offscreen:");
+                            ui.label(active_element.is_offscreen().to_string());
+                            ui.end_row();
+
+                            ui.label("Keyboard Focus:");
+                            ui.label(format!(
+                                "{} (focusable: {})",
+                                active_element.has_keyboard_focus(),
+                                active_element.is_keyboard_focusable()
+                            ));
+                            ui.end_row();
+
+                            ui.label("Process ID:");
+                            ui.label(active_element.get_process_id().to_string());
+                            ui.end_row();
+
+                            ui.label("Help Text:");
+                            ui.label(active_element.get_help_text());
+                            ui.end_row();
+
+                            ui.label("Accelerator / Access Key:");
+                            ui.label(format!(
+                                "{} / {}",
+                                active_element.get_accelerator_key(),
+                                active_element.get_access_key()
+                            ));
+                            ui.end_row();
+
+                            if let Some(value) = active_element.get_value() {
+                                ui.label("Value:");
+                                ui.label(value);
+                                if ui.button("📋").clicked() {
+                                    ui.ctx().copy_text(value.to_owned());
+                                    self.set_status(
+                                        "Value copied to clipboard".to_string(),
+                                        Duration::seconds(2),
+                                    );
+                                }
+                                ui.end_row();
+                            }
+
+                            if let Some(toggle_state) = active_element.get_toggle_state() {
+                                ui.label("Toggle State:");
+                                ui.label(toggle_state);
+                                ui.end_row();
+                            }
+
+                            ui.label("Patterns:");
+                            ui.label(active_element.get_supported_patterns().join(", "));
+                            ui.end_row();
+
+                            let index = state.active_ui_element.unwrap_or(0);
+                            let candidates = if self.simple_xpath {
+                                Vec::new()
+                            } else {
+                                self.get_xpath_candidates(index)
+                            };
+                            // show the best candidate unless none of them is unique
+                            let xpath = match candidates.first() {
+                                Some(best) if best.is_unique() => best.get_xpath().to_string(),
+                                _ => self
+                                    .ui_tree
+                                    .get_xpath_for_element(index, self.simple_xpath)
+                                    .unwrap_or_default(),
+                            };
                             ui.label("XPath:");
                             ui.label(xpath.clone());
                             if ui.button("📋").clicked() {
@@ -712,6 +805,39 @@ impl UIExplorer {
                                     Duration::seconds(2),
                                 );
                             }
+
+                            if candidates.len() > 1 {
+                                ui.end_row();
+                                ui.label("Candidates:");
+                                let mut copied = None;
+                                ui.vertical(|ui| {
+                                    for candidate in &candidates {
+                                        ui.horizontal(|ui| {
+                                            if ui.small_button("📋").clicked() {
+                                                copied = Some(candidate.get_xpath().to_string());
+                                            }
+                                            let matches = match candidate.get_match_count() {
+                                                1 => "unique".to_string(),
+                                                n => format!("⚠ {} matches", n),
+                                            };
+                                            ui.label(candidate.get_xpath());
+                                            ui.weak(format!(
+                                                "{}, score {:.2}, {}",
+                                                candidate.get_strategy().name(),
+                                                candidate.get_score(),
+                                                matches
+                                            ));
+                                        });
+                                    }
+                                });
+                                if let Some(xpath) = copied {
+                                    ui.ctx().copy_text(xpath);
+                                    self.set_status(
+                                        "XPath copied to clipboard".to_string(),
+                                        Duration::seconds(2),
+                                    );
+                                }
+                            }
                         });
                 } else {
                     ui.label("No active element");
@@ -742,7 +868,7 @@ impl UIExplorer {
                 egui::TextEdit::singleline(xpath_input)
                     .hint_text(placeholder)
                     .desired_width(elem_width)
-            );
+            ).on_hover_text("Locator functions: b:icontains(s, sub), b:matches-ci(s, re), b:normalize-name([s]), b:inside(x, y, w, h), b:visible()");
 
             // Render the theme selector
             let mut theme =
@@ -883,11 +1009,9 @@ impl UIExplorer {
                     self.ui_tree.get_elements(),
                 );
 
-                if let Some(ui_element_props) = rectangle::get_point_bounding_rect(
-                    &cursor_position,
-                    self.ui_tree.get_elements(),
-                    target_z,
-                ) {
+                if let Some(ui_element_props) =
+                    rectangle::get_point_bounding_rect(&cursor_position, &self.ui_tree, target_z)
+                {
                     state.update_state(
                         ui_element_props.get_element_props().clone(),
                         ui_element_props.get_tree_index(),
@@ -967,6 +1091,22 @@ impl UIExplorer {
             .map(|e| e.get_element_props().get_z_order())
     }
 
+    /// Locator candidates for the tree node at `index`, cached until another
+    /// node is selected or the tree is refreshed.
+    fn get_xpath_candidates(&mut self, index: usize) -> Vec<XpathCandidate> {
+        if let Some((cached_index, candidates)) = &self.xpath_candidates
+            && *cached_index == index
+        {
+            return candidates.clone();
+        }
+        let candidates = self
+            .ui_tree
+            .get_xpath_candidates_for_element(index)
+            .unwrap_or_default();
+        self.xpath_candidates = Some((index, candidates.clone()));
+        candidates
+    }
+
     fn set_status(&mut self, msg: String, duration: Duration) {
         let status_msg = AppStatusMsg::new_with_duration(msg, duration);
         self.status_msg = Some(status_msg);
@@ -1028,10 +1168,13 @@ impl eframe::App for UIExplorer {
                 }
             }
             AppMode::NeedsTreeRefresh => {
-                let app_name = self.app_name.clone();
+                let options = WalkOptions::default()
+                    .with_calling_window_caption(Some(self.app_name.clone()))
+                    .with_element_state(true)
+                    .with_tree_view(self.tree_view);
                 let (tx, rx): (Sender<_>, Receiver<Result<UITreeXML, UITreeError>>) = channel();
-                thread::spawn(|| {
-                    get_all_elements_xml(tx, None, None, Some(app_name), None, None);
+                thread::spawn(move || {
+                    get_all_elements_xml_with_options(tx, None, options, None);
                 });
                 self.app_mode = AppMode::IsRefreshingTree(rx);
                 state.active_element = None;
@@ -1040,6 +1183,7 @@ impl eframe::App for UIExplorer {
                 match rx.try_recv() {
                     Ok(Ok(new_ui_tree)) => {
                         self.ui_tree = new_ui_tree;
+                        self.xpath_candidates = None;
                         state = TreeState::new(); // reset the tree state
                         self.tree_state = Some(state);
                         self.app_mode = AppMode::Normal(LastRefresh {
@@ -1095,7 +1239,7 @@ impl eframe::App for UIExplorer {
     }
 }
 
-fn event_summary(event: &egui::Event, ui_elements: &[UIElementInTreeXML]) -> String {
+fn event_summary(event: &egui::Event, ui_tree: &UITreeXML) -> String {
     match event {
         egui::Event::PointerMoved { .. } => "PointerMoved { .. }".to_owned(),
         egui::Event::MouseMoved { .. } => {
@@ -1106,7 +1250,7 @@ fn event_summary(event: &egui::Event, ui_elements: &[UIElementInTreeXML]) -> Str
             };
 
             if let Some(ui_element_props) =
-                rectangle::get_point_bounding_rect(&cursor_position, ui_elements, None)
+                rectangle::get_point_bounding_rect(&cursor_position, ui_tree, None)
             {
                 // format!("MouseMoved {{ x: {}, y: {} }} over {}", cursor_position.x, cursor_position.y, ui_element_props.name)
                 let ui_element_props = ui_element_props.get_element_props();
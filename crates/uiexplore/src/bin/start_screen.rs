@@ -41,6 +41,10 @@ impl eframe::App for MyApp {
                 ui.heading("UI Explore");
                 ui.add_space(20.0);
                 ui.label("Please wait while we prepare the data...");
+                if let Some(progress) = signal_file::read_progress() {
+                    ui.add_space(10.0);
+                    ui.label(progress);
+                }
             });
         });
 
@@ -48,5 +52,7 @@ impl eframe::App for MyApp {
         if signal_file::termination_signal() {
             ctx.send_viewport_cmd(egui::viewport::ViewportCommand::Close);
         }
+        // poll the progress file while waiting
+        ctx.request_repaint_after(std::time::Duration::from_millis(250));
     }
 }
@@ -17,13 +17,15 @@ mod app_ui;
 use app_ui::UIExplorer;
 
 use ::uiexplore::signal_file;
-use uitree::{UITreeError, UITreeXML, get_all_elements_xml};
+use uitree::{
+    UITreeError, UITreeXML, WalkOptions, WalkProgress, get_all_elements_xml_with_options,
+};
 
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender, channel};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use eframe::{NativeOptions, Renderer, egui};
 
@@ -34,8 +36,13 @@ fn main() -> eframe::Result {
     let cancel_flag = Arc::new(AtomicBool::new(false));
     let cancel_clone = Some(Arc::clone(&cancel_flag));
     let (tx, rx): (Sender<_>, Receiver<Result<UITreeXML, UITreeError>>) = channel();
+    let (tx_progress, rx_progress) = channel();
+    let walk_options = WalkOptions::default()
+        .with_calling_window_caption(Some(app_name.to_string()))
+        .with_element_state(true)
+        .with_progress(tx_progress);
     thread::spawn(move || {
-        get_all_elements_xml(tx, None, None, Some(app_name.to_string()), None, cancel_clone);
+        get_all_elements_xml_with_options(tx, None, walk_options, cancel_clone);
     });
     println!("Spawned separate thread to get ui tree");
 
@@ -43,23 +50,48 @@ fn main() -> eframe::Result {
     let start_screen_pid = launch_start_screen();
 
     const TREE_TIMEOUT_SECS: u64 = 120;
-    let ui_tree = match rx.recv_timeout(Duration::from_secs(TREE_TIMEOUT_SECS)) {
-        Ok(Ok(tree)) => tree,
-        Ok(Err(e)) => {
-            cancel_flag.store(true, Ordering::Relaxed);
-            eprintln!("UI tree construction failed: {}", e);
-            UITreeXML::empty()
+    const PROGRESS_POLL_MILLIS: u64 = 250;
+    let deadline = Instant::now() + Duration::from_secs(TREE_TIMEOUT_SECS);
+    let ui_tree = loop {
+        // Forward the latest progress to the start screen while waiting
+        let latest = rx_progress
+            .try_iter()
+            .fold(None, |latest, event| match event {
+                WalkProgress::Update(update) => Some(update),
+                WalkProgress::WindowCompleted(_) => latest,
+            });
+        if let (Some(pid), Some(update)) = (start_screen_pid, latest) {
+            let _ = signal_file::write_progress_for_pid(
+                pid,
+                &format!(
+                    "{} elements in {:.0}s - {}",
+                    update.elements_processed,
+                    update.elapsed.as_secs_f64(),
+                    update.current_window
+                ),
+            );
         }
-        Err(e) => {
-            cancel_flag.store(true, Ordering::Relaxed);
-            eprintln!("UI tree construction timed out or channel error: {}", e);
-            UITreeXML::empty()
+
+        match rx.recv_timeout(Duration::from_millis(PROGRESS_POLL_MILLIS)) {
+            Ok(Ok(tree)) => break tree,
+            Ok(Err(e)) => {
+                cancel_flag.store(true, Ordering::Relaxed);
+                eprintln!("UI tree construction failed: {}", e);
+                break UITreeXML::empty();
+            }
+            Err(RecvTimeoutError::Timeout) if Instant::now() < deadline => continue,
+            Err(e) => {
+                cancel_flag.store(true, Ordering::Relaxed);
+                eprintln!("UI tree construction timed out or channel error: {}", e);
+                break UITreeXML::empty();
+            }
         }
     };
 
     // Signal the start_screen child (if launched) to close
     if let Some(pid) = start_screen_pid {
         let _ = signal_file::create_signal_file_for_pid(pid);
+        signal_file::remove_progress_file_for_pid(pid);
     }
     println!("UI Tree retrieved, setting up UIExplorer app...");
 
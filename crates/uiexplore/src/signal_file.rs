@@ -10,6 +10,12 @@ fn signal_file_path() -> PathBuf {
     env::temp_dir().join(format!("uiexplore_signal_{}.txt", pid))
 }
 
+/// Path of the file through which the parent reports tree-walk progress
+/// to the `start_screen` process with the given PID.
+fn progress_file_path(pid: u32) -> PathBuf {
+    env::temp_dir().join(format!("uiexplore_progress_{}.txt", pid))
+}
+
 fn write_to_file(file_name: &PathBuf, text_out: &str) -> Result<(), Error> {
     let mut output = File::create(file_name)?;
     write!(output, "{}", text_out)?;
@@ -52,3 +58,18 @@ pub fn termination_signal() -> bool {
         false
     }
 }
+
+/// Report a one-line progress message to the `start_screen` process with the given PID.
+pub fn write_progress_for_pid(pid: u32, text: &str) -> Result<(), Error> {
+    write_to_file(&progress_file_path(pid), text)
+}
+
+/// Read the latest progress message reported to this process, if any.
+pub fn read_progress() -> Option<String> {
+    read_to_string(&progress_file_path(std::process::id())).ok()
+}
+
+/// Remove the progress file of the `start_screen` process with the given PID.
+pub fn remove_progress_file_for_pid(pid: u32) {
+    let _ = std::fs::remove_file(progress_file_path(pid));
+}
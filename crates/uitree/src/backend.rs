@@ -0,0 +1,300 @@
+use crate::error::UITreeError;
+use crate::save_ui_element::SaveUIElement;
+#[cfg(windows)]
+use crate::walk_options::TreeView;
+
+#[cfg(windows)]
+use bromium_common::get_ui_automation_instance;
+#[cfg(windows)]
+use uiautomation::{UIAutomation, UIElement, UITreeWalker};
+
+#[cfg(windows)]
+use log::error;
+
+/// Source of accessibility elements for the tree walker.
+///
+/// The walker only needs to navigate the tree (root, first child, next
+/// sibling) and read element properties, so anything that can answer those
+/// questions can be walked: the live Windows UI Automation tree, or an
+/// in-memory tree built in tests.
+pub trait UIBackend {
+    /// Handle to a single element in the backend's tree.
+    type Element: Clone;
+
+    /// Returns the root of the tree (the desktop for UI Automation).
+    fn root(&self) -> Result<Self::Element, UITreeError>;
+
+    /// Resolves a previously captured element back into a backend handle.
+    fn resolve(&self, props: &SaveUIElement) -> Result<Self::Element, UITreeError>;
+
+    /// Returns the first child of `element`, if any.
+    fn first_child(&self, element: &Self::Element) -> Option<Self::Element>;
+
+    /// Returns the next sibling of `element`, if any.
+    fn next_sibling(&self, element: &Self::Element) -> Option<Self::Element>;
+
+    /// Returns the name of `element`, or an empty string if it cannot be read.
+    fn name(&self, element: &Self::Element) -> String;
+
+    /// Reads all properties of `element` into a `SaveUIElement` placed at the
+    /// given tree level and z-order.
+    fn properties(&self, element: &Self::Element, level: usize, z_order: usize) -> SaveUIElement;
+
+    /// Returns the executable file name of the process, if it can be determined.
+    fn executable_name(&self, _process_id: u32) -> Option<String> {
+        None
+    }
+}
+
+/// Backend reading the live Windows UI Automation tree through the tree
+/// walker of one [`TreeView`].
+#[cfg(windows)]
+pub struct UIABackend {
+    automation: UIAutomation,
+    walker: UITreeWalker,
+    element_state: bool,
+}
+
+#[cfg(windows)]
+impl UIABackend {
+    /// Creates a UI Automation instance and its control view walker.
+    pub fn new() -> Result<Self, UITreeError> {
+        Self::with_view(TreeView::Control)
+    }
+
+    /// Creates a UI Automation instance and the tree walker for `view`.
+    pub fn with_view(view: TreeView) -> Result<Self, UITreeError> {
+        let automation = get_ui_automation_instance().map_err(|e| {
+            error!("Failed to create UIAutomation instance: {}", e);
+            UITreeError::NoUIAutomation
+        })?;
+        let walker = match view {
+            TreeView::Raw => automation.get_raw_view_walker(),
+            TreeView::Control => automation.get_control_view_walker(),
+            TreeView::Content => automation.get_content_view_walker(),
+        }
+        .map_err(|e| {
+            error!("Failed to get {} view walker: {}", view.name(), e);
+            UITreeError::UIAutomation(e.to_string())
+        })?;
+        Ok(UIABackend {
+            automation,
+            walker,
+            element_state: false,
+        })
+    }
+
+    /// Also reads the element state of every element, see
+    /// [`crate::WalkOptions::with_element_state`].
+    pub fn with_element_state(mut self, element_state: bool) -> Self {
+        self.element_state = element_state;
+        self
+    }
+}
+
+#[cfg(windows)]
+impl UIBackend for UIABackend {
+    type Element = UIElement;
+
+    fn root(&self) -> Result<UIElement, UITreeError> {
+        self.automation.get_root_element().map_err(|e| {
+            error!("Failed to get root element: {}", e);
+            UITreeError::UIAutomation(e.to_string())
+        })
+    }
+
+    fn resolve(&self, props: &SaveUIElement) -> Result<UIElement, UITreeError> {
+        props.get_ui_automation_ui_element().ok_or_else(|| {
+            error!("Failed to resolve root UIElement from SaveUIElement");
+            UITreeError::UIAutomation(
+                "Failed to resolve root UIElement from SaveUIElement".to_string(),
+            )
+        })
+    }
+
+    fn first_child(&self, element: &UIElement) -> Option<UIElement> {
+        self.walker.get_first_child(element).ok()
+    }
+
+    fn next_sibling(&self, element: &UIElement) -> Option<UIElement> {
+        self.walker.get_next_sibling(element).ok()
+    }
+
+    fn name(&self, element: &UIElement) -> String {
+        element.get_name().unwrap_or_default()
+    }
+
+    fn properties(&self, element: &UIElement, level: usize, z_order: usize) -> SaveUIElement {
+        let mut props = SaveUIElement::new(element, level, z_order);
+        if self.element_state {
+            props.read_state(element);
+        }
+        props
+    }
+
+    fn executable_name(&self, process_id: u32) -> Option<String> {
+        bromium_common::process_executable_name(process_id)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct FakeNode {
+    props: SaveUIElement,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+/// In-memory backend for exercising the walker without a desktop session.
+///
+/// Elements are indices into an arena; node `0` is the root passed to
+/// [`FakeBackend::new`]. Level and z-order of the stored properties are
+/// ignored and assigned by the walker, just as with UI Automation.
+#[derive(Debug, Clone)]
+pub struct FakeBackend {
+    nodes: Vec<FakeNode>,
+    executables: Vec<(u32, String)>,
+}
+
+impl FakeBackend {
+    pub fn new(root: SaveUIElement) -> Self {
+        FakeBackend {
+            nodes: vec![FakeNode {
+                props: root,
+                parent: None,
+                children: Vec::new(),
+            }],
+            executables: Vec::new(),
+        }
+    }
+
+    /// Reports `executable` as the executable of the process `process_id`.
+    pub fn with_executable(mut self, process_id: u32, executable: impl Into<String>) -> Self {
+        self.executables.push((process_id, executable.into()));
+        self
+    }
+
+    /// Appends a child to `parent` and returns the index of the new element.
+    ///
+    /// # Panics
+    /// Panics if `parent` is not an index previously returned by this backend.
+    pub fn add_child(&mut self, parent: usize, props: SaveUIElement) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(FakeNode {
+            props,
+            parent: Some(parent),
+            children: Vec::new(),
+        });
+        self.nodes[parent].children.push(index);
+        index
+    }
+
+    /// Number of elements in the backend, including the root.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+impl UIBackend for FakeBackend {
+    type Element = usize;
+
+    fn root(&self) -> Result<usize, UITreeError> {
+        Ok(0)
+    }
+
+    fn resolve(&self, props: &SaveUIElement) -> Result<usize, UITreeError> {
+        self.nodes
+            .iter()
+            .position(|n| n.props.get_runtime_id() == props.get_runtime_id())
+            .ok_or_else(|| {
+                UITreeError::UIAutomation(format!(
+                    "No fake element with runtime id {:?}",
+                    props.get_runtime_id()
+                ))
+            })
+    }
+
+    fn first_child(&self, element: &usize) -> Option<usize> {
+        self.nodes.get(*element)?.children.first().copied()
+    }
+
+    fn next_sibling(&self, element: &usize) -> Option<usize> {
+        let parent = self.nodes.get(*element)?.parent?;
+        let siblings = &self.nodes[parent].children;
+        let pos = siblings.iter().position(|&c| c == *element)?;
+        siblings.get(pos + 1).copied()
+    }
+
+    fn name(&self, element: &usize) -> String {
+        self.nodes
+            .get(*element)
+            .map(|n| n.props.get_name().to_string())
+            .unwrap_or_default()
+    }
+
+    fn properties(&self, element: &usize, level: usize, z_order: usize) -> SaveUIElement {
+        let mut props = self
+            .nodes
+            .get(*element)
+            .map(|n| n.props.clone())
+            .unwrap_or_default();
+        props.set_position(level, z_order);
+        props
+    }
+
+    fn executable_name(&self, process_id: u32) -> Option<String> {
+        self.executables
+            .iter()
+            .find(|(pid, _)| *pid == process_id)
+            .map(|(_, name)| name.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn elem(name: &str, rtid: i32) -> SaveUIElement {
+        SaveUIElement::default()
+            .with_name(name)
+            .with_runtime_id(vec![rtid])
+    }
+
+    #[test]
+    fn test_fake_backend_navigation() {
+        let mut backend = FakeBackend::new(elem("Desktop", 1));
+        let a = backend.add_child(0, elem("A", 2));
+        let b = backend.add_child(0, elem("B", 3));
+        let a1 = backend.add_child(a, elem("A1", 4));
+
+        let root = backend.root().unwrap();
+        assert_eq!(backend.first_child(&root), Some(a));
+        assert_eq!(backend.next_sibling(&a), Some(b));
+        assert_eq!(backend.next_sibling(&b), None);
+        assert_eq!(backend.first_child(&a), Some(a1));
+        assert_eq!(backend.first_child(&b), None);
+        assert_eq!(backend.next_sibling(&root), None);
+        assert_eq!(backend.name(&a1), "A1");
+        assert_eq!(backend.len(), 4);
+    }
+
+    #[test]
+    fn test_fake_backend_properties_use_walker_position() {
+        let backend = FakeBackend::new(elem("Desktop", 1));
+        let props = backend.properties(&0, 3, 7);
+        assert_eq!(props.get_name(), "Desktop");
+        assert_eq!(props.get_level(), 3);
+        assert_eq!(props.get_z_order(), 7);
+    }
+
+    #[test]
+    fn test_fake_backend_resolve_by_runtime_id() {
+        let mut backend = FakeBackend::new(elem("Desktop", 1));
+        let a = backend.add_child(0, elem("A", 2));
+        assert_eq!(backend.resolve(&elem("whatever", 2)).unwrap(), a);
+        assert!(backend.resolve(&elem("missing", 99)).is_err());
+    }
+}
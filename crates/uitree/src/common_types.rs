@@ -1,23 +1,31 @@
 use crate::save_ui_element::SaveUIElement;
+use bromium_common::format_runtime_id;
 use std::hash::{Hash, Hasher};
 
 #[derive(Debug, Clone)]
 pub struct UIElementInTree {
-    runtime_id: Vec<i32>,
+    rt_id: String,
     element_props: SaveUIElement,
     tree_index: usize,
 }
 
 impl UIElementInTree {
     pub fn new(element_props: SaveUIElement, tree_index: usize) -> Self {
-        let rt_id = element_props.get_runtime_id().to_vec();
+        let rt_id = format_runtime_id(element_props.get_runtime_id());
         UIElementInTree {
-            runtime_id: rt_id,
+            rt_id,
             element_props,
             tree_index,
         }
     }
 
+    /// Sets the key of the element, e.g. a synthetic ID for an element
+    /// without a runtime ID.
+    pub(crate) fn with_rt_id(mut self, rt_id: String) -> Self {
+        self.rt_id = rt_id;
+        self
+    }
+
     pub fn get_element_props(&self) -> &SaveUIElement {
         &self.element_props
     }
@@ -25,11 +33,22 @@ impl UIElementInTree {
     pub fn get_tree_index(&self) -> usize {
         self.tree_index
     }
+
+    pub(crate) fn set_tree_index(&mut self, tree_index: usize) {
+        self.tree_index = tree_index;
+    }
+
+    /// Key of the element in the `UITreeMap` and its `RtID` in the XML: the
+    /// formatted runtime ID, or an ID starting with `syn-` that is unique in
+    /// this tree for elements without a runtime ID.
+    pub fn get_rt_id(&self) -> &str {
+        &self.rt_id
+    }
 }
 
 impl PartialEq for UIElementInTree {
     fn eq(&self, other: &Self) -> bool {
-        self.runtime_id == other.runtime_id
+        self.rt_id == other.rt_id
     }
 }
 
@@ -37,6 +56,49 @@ impl Eq for UIElementInTree {}
 
 impl Hash for UIElementInTree {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.runtime_id.hash(state);
+        self.rt_id.hash(state);
+    }
+}
+
+/// Why a top-level window could not be fully walked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IncompleteReason {
+    /// The walk of the window exceeded the per-window time budget.
+    TimedOut,
+    /// The window stopped responding within its time budget and its walk was
+    /// abandoned.
+    Unresponsive,
+    /// The walk of the window failed with the given error.
+    Failed(String),
+}
+
+/// A top-level window whose subtree is missing from a walk result, or only
+/// partially there. The window element itself is still part of the tree.
+#[derive(Debug, Clone)]
+pub struct IncompleteWindow {
+    name: String,
+    runtime_id: String,
+    reason: IncompleteReason,
+}
+
+impl IncompleteWindow {
+    pub fn new(name: String, runtime_id: String, reason: IncompleteReason) -> Self {
+        IncompleteWindow {
+            name,
+            runtime_id,
+            reason,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_runtime_id(&self) -> &str {
+        &self.runtime_id
+    }
+
+    pub fn get_reason(&self) -> &IncompleteReason {
+        &self.reason
     }
 }
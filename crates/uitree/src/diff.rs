@@ -0,0 +1,560 @@
+//! Structural diff between two `UITree`s.
+//!
+//! Elements are matched by runtime ID first. Runtime IDs are not stable across
+//! application restarts, so the remaining elements are matched by identical
+//! [`ElementFingerprint`](crate::ElementFingerprint)s.
+
+use crate::UIHashMap;
+use crate::fingerprint::fingerprints;
+use crate::json;
+use crate::save_ui_element::SaveUIElement;
+use crate::uiexplore_xml::UITree;
+use bromium_common::format_runtime_id;
+use xmlutil::xpath_gen::{XpathGenDoc, get_xpath_on_doc};
+
+use std::cell::OnceCell;
+use std::collections::VecDeque;
+use std::fmt;
+
+use log::debug;
+
+/// Kind of change reported for a single element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiffKind {
+    /// Present only in the new tree.
+    Added,
+    /// Present only in the old tree.
+    Removed,
+    /// Present in both trees, but under a different parent.
+    Moved,
+    /// Present in both trees at the same place, with different properties.
+    PropertyChanged,
+}
+
+impl DiffKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiffKind::Added => "added",
+            DiffKind::Removed => "removed",
+            DiffKind::Moved => "moved",
+            DiffKind::PropertyChanged => "changed",
+        }
+    }
+}
+
+/// A single property whose value differs between the two trees.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropertyChange {
+    pub property: &'static str,
+    pub old_value: String,
+    pub new_value: String,
+}
+
+/// One changed element. A moved element also lists its property changes, if any.
+#[derive(Debug, Clone)]
+pub struct DiffEntry {
+    pub kind: DiffKind,
+    /// `RtID` of the element, synthetic for elements without a runtime ID.
+    pub runtime_id: String,
+    pub control_type: String,
+    pub name: String,
+    /// XPath of the element in the old tree (`None` for added elements).
+    pub old_xpath: Option<String>,
+    /// XPath of the element in the new tree (`None` for removed elements).
+    pub new_xpath: Option<String>,
+    pub changes: Vec<PropertyChange>,
+}
+
+/// Result of [`UITree::diff`].
+#[derive(Debug, Clone, Default)]
+pub struct UITreeDiff {
+    entries: Vec<DiffEntry>,
+}
+
+impl UITreeDiff {
+    pub fn entries(&self) -> &[DiffEntry] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Number of entries of the given kind.
+    pub fn count(&self, kind: DiffKind) -> usize {
+        self.entries.iter().filter(|e| e.kind == kind).count()
+    }
+
+    /// Machine-readable form of the diff as a JSON document.
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{");
+        for kind in [
+            DiffKind::Added,
+            DiffKind::Removed,
+            DiffKind::Moved,
+            DiffKind::PropertyChanged,
+        ] {
+            out.push_str(&format!("\"{}\":{},", kind.as_str(), self.count(kind)));
+        }
+        out.push_str("\"entries\":[");
+        for (i, entry) in self.entries.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str("{\"kind\":");
+            json::push_str(&mut out, entry.kind.as_str());
+            out.push_str(",\"runtime_id\":");
+            json::push_str(&mut out, &entry.runtime_id);
+            out.push_str(",\"control_type\":");
+            json::push_str(&mut out, &entry.control_type);
+            out.push_str(",\"name\":");
+            json::push_str(&mut out, &entry.name);
+            out.push_str(",\"old_xpath\":");
+            json::push_opt_str(&mut out, entry.old_xpath.as_deref());
+            out.push_str(",\"new_xpath\":");
+            json::push_opt_str(&mut out, entry.new_xpath.as_deref());
+            out.push_str(",\"changes\":[");
+            for (j, change) in entry.changes.iter().enumerate() {
+                if j > 0 {
+                    out.push(',');
+                }
+                out.push_str("{\"property\":");
+                json::push_str(&mut out, change.property);
+                out.push_str(",\"old\":");
+                json::push_str(&mut out, &change.old_value);
+                out.push_str(",\"new\":");
+                json::push_str(&mut out, &change.new_value);
+                out.push('}');
+            }
+            out.push_str("]}");
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+impl fmt::Display for UITreeDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} added, {} removed, {} moved, {} changed",
+            self.count(DiffKind::Added),
+            self.count(DiffKind::Removed),
+            self.count(DiffKind::Moved),
+            self.count(DiffKind::PropertyChanged)
+        )?;
+        for entry in &self.entries {
+            let marker = match entry.kind {
+                DiffKind::Added => '+',
+                DiffKind::Removed => '-',
+                DiffKind::Moved => '>',
+                DiffKind::PropertyChanged => '~',
+            };
+            write!(
+                f,
+                "{} {} '{}' ({})",
+                marker, entry.control_type, entry.name, entry.runtime_id
+            )?;
+            match (&entry.old_xpath, &entry.new_xpath) {
+                (Some(old), Some(new)) if old != new => write!(f, " {} -> {}", old, new)?,
+                (_, Some(xpath)) | (Some(xpath), None) => write!(f, " {}", xpath)?,
+                (None, None) => {}
+            }
+            writeln!(f)?;
+            for change in &entry.changes {
+                writeln!(
+                    f,
+                    "    {}: '{}' -> '{}'",
+                    change.property, change.old_value, change.new_value
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Properties compared between matched elements.
+fn compared_properties(props: &SaveUIElement) -> [(&'static str, String); 11] {
+    let rect = props.get_bounding_rectangle();
+    [
+        ("Name", props.get_name().to_string()),
+        ("ClassName", props.get_classname().to_string()),
+        ("ControlType", props.get_control_type().to_string()),
+        (
+            "LocalizedControlType",
+            props.get_localized_control_type().to_string(),
+        ),
+        ("FrameworkId", props.get_framework_id().to_string()),
+        ("AutomationId", props.get_automation_id().to_string()),
+        (
+            "BoundingRectangle",
+            format!(
+                "{},{},{},{}",
+                rect.get_left(),
+                rect.get_top(),
+                rect.get_right(),
+                rect.get_bottom()
+            ),
+        ),
+        ("IsEnabled", props.is_enabled().to_string()),
+        ("IsOffscreen", props.is_offscreen().to_string()),
+        ("Value", props.get_value().unwrap_or_default().to_string()),
+        (
+            "ToggleState",
+            props.get_toggle_state().unwrap_or_default().to_string(),
+        ),
+    ]
+}
+
+/// Flattened view of the alive nodes of one tree, in pre-order.
+struct DiffSide<'a> {
+    tree: &'a UITree,
+    order: Vec<usize>,
+    rtid: UIHashMap<usize, String>,
+    fingerprint: UIHashMap<usize, String>,
+    /// The tree's XML, parsed once when the first XPath is needed.
+    xml: OnceCell<Option<XpathGenDoc<'a>>>,
+}
+
+impl<'a> DiffSide<'a> {
+    fn new(tree: &'a UITree) -> Self {
+        let mut order = Vec::new();
+        let mut rtid = UIHashMap::default();
+        let mut fingerprint = UIHashMap::default();
+
+        for (idx, fp) in fingerprints(tree) {
+            let props = tree.node(idx).1;
+            if !props.get_runtime_id().is_empty() {
+                rtid.insert(idx, format_runtime_id(props.get_runtime_id()));
+            }
+            fingerprint.insert(idx, fp.key());
+            order.push(idx);
+        }
+
+        DiffSide {
+            tree,
+            order,
+            rtid,
+            fingerprint,
+            xml: OnceCell::new(),
+        }
+    }
+
+    fn props(&self, idx: usize) -> &SaveUIElement {
+        self.tree.node(idx).1
+    }
+
+    fn xpath(&self, idx: usize) -> Option<String> {
+        let xml = self
+            .xml
+            .get_or_init(|| XpathGenDoc::new(self.tree.get_xml_dom_tree()).ok())
+            .as_ref()?;
+        get_xpath_on_doc(&self.tree.get_tree().node(idx).runtime_id, xml, false).ok()
+    }
+
+    /// Index of the closest ancestor that takes part in the diff.
+    fn parent(&self, idx: usize) -> Option<usize> {
+        let mut current = idx;
+        while current != self.tree.root() {
+            current = self.tree.get_tree().node(current).parent;
+            if self.fingerprint.contains_key(&current) {
+                return Some(current);
+            }
+        }
+        None
+    }
+
+    fn entry(&self, kind: DiffKind, idx: usize) -> DiffEntry {
+        let props = self.props(idx);
+        DiffEntry {
+            kind,
+            runtime_id: self.tree.get_tree().node(idx).runtime_id.to_string(),
+            control_type: props.get_control_type().to_string(),
+            name: props.get_name().to_string(),
+            old_xpath: None,
+            new_xpath: None,
+            changes: Vec::new(),
+        }
+    }
+}
+
+impl UITree {
+    /// Compares this (old) tree with `other` (new) and reports added, removed,
+    /// moved and property-changed elements.
+    pub fn diff(&self, other: &UITree) -> UITreeDiff {
+        let old = DiffSide::new(self);
+        let new = DiffSide::new(other);
+
+        // Pass 1: runtime IDs
+        let new_by_rtid: UIHashMap<&str, usize> =
+            new.rtid.iter().map(|(&i, r)| (r.as_str(), i)).collect();
+        let mut old_to_new: UIHashMap<usize, usize> = UIHashMap::default();
+        let mut matched_new = std::collections::HashSet::new();
+        for &o in &old.order {
+            if let Some(rtid) = old.rtid.get(&o)
+                && let Some(&n) = new_by_rtid.get(rtid.as_str())
+            {
+                old_to_new.insert(o, n);
+                matched_new.insert(n);
+            }
+        }
+
+        // Pass 2: fingerprints for everything still unmatched, in document order
+        let mut new_by_fingerprint: UIHashMap<&str, VecDeque<usize>> = UIHashMap::default();
+        for &n in new.order.iter().filter(|n| !matched_new.contains(*n)) {
+            new_by_fingerprint
+                .entry(new.fingerprint[&n].as_str())
+                .or_default()
+                .push_back(n);
+        }
+        let unmatched_old: Vec<usize> = old
+            .order
+            .iter()
+            .copied()
+            .filter(|o| !old_to_new.contains_key(o))
+            .collect();
+        for o in unmatched_old {
+            if let Some(n) = new_by_fingerprint
+                .get_mut(old.fingerprint[&o].as_str())
+                .and_then(|q| q.pop_front())
+            {
+                old_to_new.insert(o, n);
+                matched_new.insert(n);
+            }
+        }
+        let new_to_old: UIHashMap<usize, usize> =
+            old_to_new.iter().map(|(&o, &n)| (n, o)).collect();
+        debug!(
+            "Diff matched {} of {} old and {} new elements",
+            old_to_new.len(),
+            old.order.len(),
+            new.order.len()
+        );
+
+        let mut entries = Vec::new();
+        for &o in &old.order {
+            let Some(&n) = old_to_new.get(&o) else {
+                let mut entry = old.entry(DiffKind::Removed, o);
+                entry.old_xpath = old.xpath(o);
+                entries.push(entry);
+                continue;
+            };
+
+            let changes: Vec<PropertyChange> = compared_properties(old.props(o))
+                .into_iter()
+                .zip(compared_properties(new.props(n)))
+                .filter(|((_, a), (_, b))| a != b)
+                .map(|((property, old_value), (_, new_value))| PropertyChange {
+                    property,
+                    old_value,
+                    new_value,
+                })
+                .collect();
+
+            let moved = match (old.parent(o), new.parent(n)) {
+                (Some(op), Some(np)) => match (old_to_new.get(&op), new_to_old.get(&np)) {
+                    // The parent was replaced (removed and added) in place
+                    (None, None) => false,
+                    (mapped, _) => mapped != Some(&np),
+                },
+                (None, None) => false,
+                _ => true,
+            };
+
+            let kind = if moved {
+                DiffKind::Moved
+            } else if !changes.is_empty() {
+                DiffKind::PropertyChanged
+            } else {
+                continue;
+            };
+            let mut entry = new.entry(kind, n);
+            entry.old_xpath = old.xpath(o);
+            entry.new_xpath = new.xpath(n);
+            entry.changes = changes;
+            entries.push(entry);
+        }
+
+        for &n in new.order.iter().filter(|n| !matched_new.contains(*n)) {
+            let mut entry = new.entry(DiffKind::Added, n);
+            entry.new_xpath = new.xpath(n);
+            entries.push(entry);
+        }
+
+        UITreeDiff { entries }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FakeBackend;
+    use crate::uiexplore_xml::get_all_elements_xml_with_backend;
+    use crate::walk_options::WalkOptions;
+    use std::sync::mpsc::channel;
+
+    fn elem(name: &str, ct: &str, rtid: i32) -> SaveUIElement {
+        SaveUIElement::default()
+            .with_name(name)
+            .with_control_type(ct)
+            .with_automation_id(format!("auto-{}", name))
+            .with_runtime_id(vec![42, rtid])
+    }
+
+    fn walk(backend: &FakeBackend) -> UITree {
+        let (tx, rx) = channel();
+        get_all_elements_xml_with_backend(tx, backend, None, &WalkOptions::default(), None);
+        rx.recv().unwrap().unwrap()
+    }
+
+    /// Desktop → App (OK, Username) and Other (Close)
+    fn base() -> (FakeBackend, usize, usize) {
+        let mut backend = FakeBackend::new(elem("Desktop", "Pane", 1));
+        let app = backend.add_child(0, elem("App", "Window", 2));
+        backend.add_child(app, elem("OK", "Button", 3));
+        backend.add_child(app, elem("Username", "Edit", 4));
+        let other = backend.add_child(0, elem("Other", "Window", 5));
+        backend.add_child(other, elem("Close", "Button", 6));
+        (backend, app, other)
+    }
+
+    #[test]
+    fn test_diff_identical_trees_is_empty() {
+        let (backend, _, _) = base();
+        let diff = walk(&backend).diff(&walk(&backend));
+        assert!(diff.is_empty(), "{}", diff);
+    }
+
+    #[test]
+    fn test_diff_reports_added_removed_changed_moved() {
+        let (before, _, _) = base();
+
+        let mut after = FakeBackend::new(elem("Desktop", "Pane", 1));
+        let app = after.add_child(0, elem("App", "Window", 2));
+        after.add_child(app, elem("OK", "Button", 3).with_classname("NewClass"));
+        after.add_child(app, elem("Help", "Button", 7));
+        after.add_child(0, elem("Other", "Window", 5));
+        // "Close" moved from Other to App
+        after.add_child(app, elem("Close", "Button", 6));
+
+        let diff = walk(&before).diff(&walk(&after));
+        assert_eq!(diff.count(DiffKind::Added), 1, "{}", diff);
+        assert_eq!(diff.count(DiffKind::Removed), 1, "{}", diff);
+        assert_eq!(diff.count(DiffKind::Moved), 1, "{}", diff);
+        assert_eq!(diff.count(DiffKind::PropertyChanged), 1, "{}", diff);
+
+        let find = |kind| diff.entries().iter().find(|e| e.kind == kind).unwrap();
+        assert_eq!(find(DiffKind::Added).name, "Help");
+        assert!(find(DiffKind::Added).new_xpath.is_some());
+        assert_eq!(find(DiffKind::Removed).name, "Username");
+        assert!(find(DiffKind::Removed).old_xpath.is_some());
+        assert_eq!(find(DiffKind::Moved).name, "Close");
+        let changed = find(DiffKind::PropertyChanged);
+        assert_eq!(changed.name, "OK");
+        assert_eq!(
+            changed.changes,
+            vec![PropertyChange {
+                property: "ClassName",
+                old_value: String::new(),
+                new_value: "NewClass".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_diff_replaced_parent_does_not_move_children() {
+        let (before, _, _) = base();
+        // App gets a new runtime ID and automation ID, its children keep theirs
+        let mut after = FakeBackend::new(elem("Desktop", "Pane", 1));
+        let app = after.add_child(0, elem("App v2", "Window", 12));
+        after.add_child(app, elem("OK", "Button", 3));
+        let other = after.add_child(0, elem("Other", "Window", 5));
+        after.add_child(other, elem("Close", "Button", 6));
+        // Username really moved, to a window present in both trees
+        after.add_child(other, elem("Username", "Edit", 4));
+
+        let diff = walk(&before).diff(&walk(&after));
+        assert_eq!(diff.count(DiffKind::Removed), 1, "{}", diff);
+        assert_eq!(diff.count(DiffKind::Added), 1, "{}", diff);
+        assert_eq!(diff.count(DiffKind::Moved), 1, "{}", diff);
+        let moved = diff.entries().iter().find(|e| e.kind == DiffKind::Moved);
+        assert_eq!(moved.unwrap().name, "Username");
+    }
+
+    #[test]
+    fn test_diff_reports_state_changes() {
+        let mut before = FakeBackend::new(elem("Desktop", "Pane", 1));
+        let app = before.add_child(0, elem("App", "Window", 2));
+        before.add_child(app, elem("OK", "Button", 3).with_enabled(true));
+        let mut after = FakeBackend::new(elem("Desktop", "Pane", 1));
+        let app = after.add_child(0, elem("App", "Window", 2));
+        after.add_child(
+            app,
+            elem("OK", "Button", 3).with_value(Some("pressed".to_string())),
+        );
+
+        let diff = walk(&before).diff(&walk(&after));
+        let changes: Vec<_> = diff.entries()[0]
+            .changes
+            .iter()
+            .map(|c| c.property)
+            .collect();
+        assert_eq!(changes, vec!["IsEnabled", "Value"]);
+    }
+
+    #[test]
+    fn test_diff_falls_back_to_fingerprint() {
+        let (before, _, _) = base();
+        // Same UI after an application restart: every runtime ID changed
+        let mut after = FakeBackend::new(elem("Desktop", "Pane", 1));
+        let app = after.add_child(0, elem("App", "Window", 102));
+        after.add_child(app, elem("OK", "Button", 103));
+        after.add_child(app, elem("Username", "Edit", 104));
+        let other = after.add_child(0, elem("Other", "Window", 105));
+        after.add_child(other, elem("Close", "Button", 106));
+
+        let diff = walk(&before).diff(&walk(&after));
+        assert!(diff.is_empty(), "{}", diff);
+    }
+
+    #[test]
+    fn test_diff_entries_use_synthetic_ids() {
+        let (before, app, _) = base();
+        let mut after = before.clone();
+        for name in ["First", "Second"] {
+            after.add_child(
+                app,
+                SaveUIElement::default()
+                    .with_name(name)
+                    .with_control_type("Text"),
+            );
+        }
+
+        let diff = walk(&before).diff(&walk(&after));
+        let ids: Vec<&str> = diff
+            .entries()
+            .iter()
+            .map(|e| e.runtime_id.as_str())
+            .collect();
+        assert_eq!(ids.len(), 2, "{}", diff);
+        assert_ne!(ids[0], ids[1]);
+        assert!(!ids.contains(&"0-0-0-0"));
+        assert!(diff.entries().iter().all(|e| e.new_xpath.is_some()));
+    }
+
+    #[test]
+    fn test_diff_outputs() {
+        let (before, app, _) = base();
+        let mut after = before.clone();
+        after.add_child(app, elem("Say \"hi\"", "Text", 8));
+
+        let diff = walk(&before).diff(&walk(&after));
+        let text = diff.to_string();
+        assert!(text.starts_with("1 added, 0 removed, 0 moved, 0 changed\n"));
+        assert!(text.contains("+ Text 'Say \"hi\"' (42-8)"));
+
+        let json = diff.to_json();
+        assert!(json.starts_with(r#"{"added":1,"removed":0,"moved":0,"changed":0,"entries":[{"kind":"added","runtime_id":"42-8","control_type":"Text","name":"Say \"hi\"","old_xpath":null,"new_xpath":"#));
+        assert!(json.ends_with(r#","changes":[]}]}"#));
+    }
+}
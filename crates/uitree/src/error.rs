@@ -20,4 +20,16 @@ pub enum UITreeError {
     /// Tree construction was cancelled (e.g. due to timeout on the receiver side).
     #[error("Tree construction cancelled")]
     Cancelled,
+
+    /// A walk did not finish within its time budget.
+    #[error("Time budget exceeded: {0}")]
+    Timeout(String),
+
+    /// Reading or writing a file failed.
+    #[error("I/O error: {0}")]
+    Io(String),
+
+    /// A tree snapshot could not be read (malformed or unsupported version).
+    #[error("Invalid snapshot: {0}")]
+    Snapshot(String),
 }
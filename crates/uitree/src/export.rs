@@ -0,0 +1,362 @@
+//! Exporters for sharing a `UITree` outside of uiexplore: a nested JSON
+//! document, a Graphviz DOT graph and a standalone HTML report.
+
+use crate::json;
+use crate::save_ui_element::SaveUIElement;
+use crate::uiexplore_xml::UITree;
+
+use quick_xml::escape::escape;
+use std::fmt::Write;
+
+/// Longest element name shown in a DOT node label before it is shortened.
+const DOT_LABEL_MAX_CHARS: usize = 40;
+
+const HTML_STYLE: &str = "\
+body{font-family:'Segoe UI',sans-serif;margin:0;display:flex;height:100vh}
+#tree{flex:1;overflow:auto;padding:8px 16px;border-right:1px solid #ccc}
+#props{width:40%;overflow:auto;padding:8px 16px}
+details{margin-left:16px}
+summary{cursor:pointer;white-space:nowrap}
+summary.leaf{list-style:none;margin-left:12px}
+summary.selected{background:#cde}
+.ct{color:#05a}
+table{border-collapse:collapse;width:100%}
+td{border:1px solid #ddd;padding:2px 6px;vertical-align:top;word-break:break-all}
+td:first-child{font-weight:bold;white-space:nowrap}";
+
+const HTML_SCRIPT: &str = "\
+const props = JSON.parse(document.getElementById('data').textContent);
+let selected = null;
+document.getElementById('tree').addEventListener('click', e => {
+  const summary = e.target.closest('summary');
+  if (!summary) return;
+  if (selected) selected.classList.remove('selected');
+  selected = summary;
+  summary.classList.add('selected');
+  const table = document.createElement('table');
+  for (const [key, value] of Object.entries(props[summary.dataset.node])) {
+    const row = table.insertRow();
+    row.insertCell().textContent = key;
+    row.insertCell().textContent =
+      value !== null && typeof value === 'object' ? JSON.stringify(value) : String(value);
+  }
+  document.getElementById('props').replaceChildren(table);
+});";
+
+impl UITree {
+    /// Renders the tree as a nested JSON document with every `SaveUIElement`
+    /// property; the children of an element are in its `children` array.
+    pub fn to_json(&self) -> String {
+        let mut out = format!(
+            "{{\"element_count\":{},\"complete\":{},\"tree_view\":\"{}\",\"root\":",
+            self.get_elements().len(),
+            self.is_complete(),
+            self.get_tree_view().name()
+        );
+        self.push_json_subtree(&mut out, self.root());
+        out.push('}');
+        out
+    }
+
+    fn push_json_subtree(&self, out: &mut String, index: usize) {
+        out.push('{');
+        push_json_props(out, self.element_for_node(index).get_element_props());
+        out.push_str(",\"children\":[");
+        let mut first = true;
+        for &child in self.children(index) {
+            if !first {
+                out.push(',');
+            }
+            first = false;
+            self.push_json_subtree(out, child);
+        }
+        out.push_str("]}");
+    }
+
+    /// Renders the whole tree as a Graphviz DOT digraph.
+    pub fn to_dot(&self) -> String {
+        self.subtree_to_dot(self.root())
+    }
+
+    /// Renders the subtree below the node at `index` (e.g. a single dialog)
+    /// as a Graphviz DOT digraph.
+    pub fn subtree_to_dot(&self, index: usize) -> String {
+        let mut out = String::from(
+            "digraph UITree {\n  rankdir=LR;\n  node [shape=box, fontname=\"Segoe UI\"];\n",
+        );
+        let mut stack = vec![index];
+        while let Some(node) = stack.pop() {
+            let props = self.element_for_node(node).get_element_props();
+            let _ = writeln!(
+                out,
+                "  n{} [label=\"{}\"];",
+                node,
+                dot_escape(&dot_label(props))
+            );
+            for &child in self.children(node) {
+                let _ = writeln!(out, "  n{} -> n{};", node, child);
+            }
+            stack.extend(self.children(node).iter().rev());
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Renders a single-file HTML report with a collapsible tree; selecting an
+    /// element shows all of its properties in a table.
+    pub fn to_html_report(&self) -> String {
+        let mut out = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>UI tree</title>\n<style>\n",
+        );
+        out.push_str(HTML_STYLE);
+        out.push_str("\n</style>\n</head>\n<body>\n<div id=\"tree\">\n");
+        let _ = writeln!(
+            out,
+            "<p>{} elements{}</p>",
+            self.get_elements().len(),
+            if self.is_complete() {
+                ""
+            } else {
+                " (some windows incomplete)"
+            }
+        );
+        self.push_html_subtree(&mut out, self.root());
+        out.push_str("</div>\n<div id=\"props\"><p>Select an element.</p></div>\n");
+
+        // node index -> properties, read by the script to fill the table
+        let mut data = String::from("{");
+        let mut first = true;
+        for (index, node) in self.get_tree().nodes().iter().enumerate() {
+            if !node.is_alive {
+                continue;
+            }
+            if !first {
+                data.push(',');
+            }
+            first = false;
+            let _ = write!(data, "\"{}\":{{", index);
+            push_json_props(&mut data, self.element_for_node(index).get_element_props());
+            data.push('}');
+        }
+        data.push('}');
+        out.push_str("<script type=\"application/json\" id=\"data\">");
+        // keep element names such as "</script>" from ending the block early
+        out.push_str(&data.replace("</", "<\\/"));
+        out.push_str("</script>\n<script>\n");
+        out.push_str(HTML_SCRIPT);
+        out.push_str("\n</script>\n</body>\n</html>\n");
+        out
+    }
+
+    fn push_html_subtree(&self, out: &mut String, index: usize) {
+        let props = self.element_for_node(index).get_element_props();
+        let children = self.children(index);
+        let label = format!(
+            "<span class=\"ct\">{}</span> {}",
+            escape(props.get_control_type()),
+            escape(format!("\"{}\"", props.get_name()))
+        );
+        if children.is_empty() {
+            let _ = writeln!(
+                out,
+                "<details><summary class=\"leaf\" data-node=\"{}\">{}</summary></details>",
+                index, label
+            );
+            return;
+        }
+        // top-level windows and above start expanded
+        let open = if props.get_level() <= 1 { " open" } else { "" };
+        let _ = writeln!(
+            out,
+            "<details{}><summary data-node=\"{}\">{}</summary>",
+            open, index, label
+        );
+        for &child in children {
+            self.push_html_subtree(out, child);
+        }
+        out.push_str("</details>\n");
+    }
+}
+
+/// Appends the properties of `props` as JSON object members (without braces).
+fn push_json_props(out: &mut String, props: &SaveUIElement) {
+    let rect = props.get_bounding_rectangle();
+    out.push_str("\"name\":");
+    json::push_str(out, props.get_name());
+    out.push_str(",\"class_name\":");
+    json::push_str(out, props.get_classname());
+    out.push_str(",\"control_type\":");
+    json::push_str(out, props.get_control_type());
+    out.push_str(",\"localized_control_type\":");
+    json::push_str(out, props.get_localized_control_type());
+    out.push_str(",\"framework_id\":");
+    json::push_str(out, props.get_framework_id());
+    let _ = write!(out, ",\"runtime_id\":{:?}", props.get_runtime_id());
+    out.push_str(",\"automation_id\":");
+    json::push_str(out, props.get_automation_id());
+    let _ = write!(
+        out,
+        ",\"handle\":{},\"bounding_rectangle\":{{\"left\":{},\"top\":{},\"right\":{},\"bottom\":{}}}",
+        props.get_handle(),
+        rect.get_left(),
+        rect.get_top(),
+        rect.get_right(),
+        rect.get_bottom()
+    );
+    let _ = write!(
+        out,
+        ",\"level\":{},\"z_order\":{}",
+        props.get_level(),
+        props.get_z_order()
+    );
+    out.push_str(",\"xpath\":");
+    json::push_opt_str(out, props.get_xpath());
+    let _ = write!(
+        out,
+        ",\"is_enabled\":{},\"is_offscreen\":{},\"has_keyboard_focus\":{},\"is_keyboard_focusable\":{}",
+        props.is_enabled(),
+        props.is_offscreen(),
+        props.has_keyboard_focus(),
+        props.is_keyboard_focusable()
+    );
+    out.push_str(",\"help_text\":");
+    json::push_str(out, props.get_help_text());
+    out.push_str(",\"accelerator_key\":");
+    json::push_str(out, props.get_accelerator_key());
+    out.push_str(",\"access_key\":");
+    json::push_str(out, props.get_access_key());
+    let _ = write!(out, ",\"process_id\":{}", props.get_process_id());
+    out.push_str(",\"value\":");
+    json::push_opt_str(out, props.get_value());
+    out.push_str(",\"toggle_state\":");
+    json::push_opt_str(out, props.get_toggle_state());
+    out.push_str(",\"supported_patterns\":[");
+    for (i, pattern) in props.get_supported_patterns().iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        json::push_str(out, pattern);
+    }
+    out.push(']');
+}
+
+/// Node label: the control type, then the (shortened) name and automation id.
+fn dot_label(props: &SaveUIElement) -> String {
+    let mut label = props.get_control_type().to_string();
+    let name = props.get_name();
+    if !name.is_empty() {
+        let mut short: String = name.chars().take(DOT_LABEL_MAX_CHARS).collect();
+        if short.len() < name.len() {
+            short.push('…');
+        }
+        let _ = write!(label, "\n\"{}\"", short);
+    }
+    if !props.get_automation_id().is_empty() {
+        let _ = write!(label, "\n#{}", props.get_automation_id());
+    }
+    label
+}
+
+/// Escapes `value` for a double-quoted DOT string.
+fn dot_escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FakeBackend;
+    use crate::walk_options::WalkOptions;
+    use std::sync::mpsc::channel;
+
+    fn elem(name: &str, control_type: &str, rtid: i32) -> SaveUIElement {
+        SaveUIElement::default()
+            .with_name(name)
+            .with_control_type(control_type)
+            .with_runtime_id(vec![42, rtid])
+    }
+
+    /// Desktop → `Save "As"` dialog with a `</script>` edit and an OK button.
+    fn dialog() -> UITree {
+        let mut fake = FakeBackend::new(elem("Desktop", "Pane", 1));
+        let dialog = fake.add_child(0, elem("Save \"As\"", "Window", 2));
+        fake.add_child(
+            dialog,
+            elem("</script>", "Edit", 3).with_automation_id("file"),
+        );
+        fake.add_child(dialog, elem("OK", "Button", 4));
+
+        let (tx, rx) = channel();
+        crate::get_all_elements_xml_with_backend(tx, &fake, None, &WalkOptions::default(), None);
+        rx.recv().unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_to_json_nests_children_with_all_properties() {
+        let tree = dialog();
+        let json = tree.to_json();
+        assert!(json.starts_with("{\"element_count\":"));
+        assert!(json.contains("\"tree_view\":\"control\""));
+        assert!(json.contains("\"name\":\"Save \\\"As\\\"\""));
+        assert!(json.contains("\"runtime_id\":[42, 3]"));
+        assert!(json.contains("\"automation_id\":\"file\""));
+        assert!(json.contains("\"supported_patterns\":[]"));
+        // the OK button is a leaf nested below the dialog
+        let dialog_at = json.find("Save \\\"As\\\"").unwrap();
+        let ok_at = json.find("\"name\":\"OK\"").unwrap();
+        assert!(ok_at > dialog_at);
+        assert!(json[ok_at..].contains("\"children\":[]}"));
+        assert_eq!(json.matches('{').count(), json.matches('}').count());
+    }
+
+    #[test]
+    fn test_to_dot_lists_nodes_and_edges() {
+        let tree = dialog();
+        let dot = tree.to_dot();
+        assert!(dot.starts_with("digraph UITree {"));
+        assert!(dot.contains("label=\"Window\\n\\\"Save \\\"As\\\"\\\"\""));
+        assert!(dot.contains("label=\"Edit\\n\\\"</script>\\\"\\n#file\""));
+        let edges = dot.lines().filter(|l| l.contains(" -> ")).count();
+        assert_eq!(edges, tree.get_tree().node_count() - 1);
+
+        let window = tree
+            .get_elements()
+            .iter()
+            .find(|e| e.get_element_props().get_control_type() == "Window")
+            .unwrap()
+            .get_tree_index();
+        let sub = tree.subtree_to_dot(window);
+        assert_eq!(sub.lines().filter(|l| l.contains(" -> ")).count(), 2);
+        assert!(!sub.contains("Desktop"));
+    }
+
+    #[test]
+    fn test_html_report_escapes_names() {
+        let html = dialog().to_html_report();
+        assert!(html.contains("&quot;Save &quot;As&quot;&quot;"));
+        assert!(html.contains("&lt;/script&gt;"));
+        // only the two real script end tags remain
+        assert_eq!(html.matches("</script>").count(), 2);
+        assert!(html.contains("<\\/script>"));
+    }
+
+    #[test]
+    fn test_dot_label_shortens_long_names() {
+        let long = "x".repeat(DOT_LABEL_MAX_CHARS + 10);
+        let label = dot_label(&elem(&long, "Text", 1));
+        assert_eq!(
+            label,
+            format!("Text\n\"{}…\"", "x".repeat(DOT_LABEL_MAX_CHARS))
+        );
+    }
+}
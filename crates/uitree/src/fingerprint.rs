@@ -0,0 +1,500 @@
+//! Element identity that survives tree refreshes and application restarts.
+//!
+//! Runtime IDs are only stable while a control lives. An [`ElementFingerprint`]
+//! describes an element by its control type, automation id, class name, name
+//! and ancestor path instead, and [`UITree::match_elements`] uses it to map
+//! the elements of an old tree onto a new one.
+
+use crate::UIHashMap;
+use crate::save_ui_element::SaveUIElement;
+use crate::uiexplore_xml::UITree;
+
+use std::collections::{HashSet, VecDeque};
+
+use log::debug;
+
+const WEIGHT_AUTOMATION_ID: f64 = 0.3;
+const WEIGHT_NAME: f64 = 0.3;
+const WEIGHT_CLASS_NAME: f64 = 0.1;
+const WEIGHT_ANCESTOR_PATH: f64 = 0.3;
+
+/// Upper bound on the candidate pairs scored per control type during fuzzy
+/// matching; larger groups are only matched exactly.
+const MAX_FUZZY_PAIRS: usize = 250_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ElementFingerprint {
+    control_type: String,
+    automation_id: String,
+    class_name: String,
+    name: String,
+    /// One `ControlType[id]` segment per ancestor below the desktop, outermost
+    /// first. `id` is the automation id, or the normalized name without one;
+    /// for the top-level window it is the class name, since titles change.
+    ancestor_path: Vec<String>,
+}
+
+impl ElementFingerprint {
+    /// Fingerprint of `props` below `ancestors` (outermost first). Desktop
+    /// (level 0) ancestors are ignored.
+    pub fn new(props: &SaveUIElement, ancestors: &[&SaveUIElement]) -> Self {
+        ElementFingerprint {
+            control_type: props.get_control_type().to_string(),
+            automation_id: props.get_automation_id().to_string(),
+            class_name: props.get_classname().to_string(),
+            name: normalize_name(props.get_name()),
+            ancestor_path: ancestors
+                .iter()
+                .filter(|a| a.get_level() != 0)
+                .map(|a| path_segment(a))
+                .collect(),
+        }
+    }
+
+    pub fn get_control_type(&self) -> &str {
+        &self.control_type
+    }
+
+    pub fn get_automation_id(&self) -> &str {
+        &self.automation_id
+    }
+
+    pub fn get_class_name(&self) -> &str {
+        &self.class_name
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_ancestor_path(&self) -> &[String] {
+        &self.ancestor_path
+    }
+
+    /// Single-string form; equal fingerprints have equal keys.
+    pub fn key(&self) -> String {
+        format!(
+            "{}|{}|{}|{}|/{}",
+            self.control_type,
+            self.automation_id,
+            self.class_name,
+            self.name,
+            self.ancestor_path.join("/")
+        )
+    }
+
+    /// Confidence between 0.0 and 1.0 that both fingerprints describe the same
+    /// element. Elements of different control types never match.
+    pub fn similarity(&self, other: &ElementFingerprint) -> f64 {
+        if self.control_type != other.control_type {
+            return 0.0;
+        }
+        let mut score = 0.0;
+        if self.automation_id == other.automation_id {
+            score += WEIGHT_AUTOMATION_ID;
+        }
+        if self.name == other.name {
+            score += WEIGHT_NAME;
+        }
+        if self.class_name == other.class_name {
+            score += WEIGHT_CLASS_NAME;
+        }
+        let longest = self.ancestor_path.len().max(other.ancestor_path.len());
+        if longest == 0 {
+            score += WEIGHT_ANCESTOR_PATH;
+        } else {
+            let same = self
+                .ancestor_path
+                .iter()
+                .zip(&other.ancestor_path)
+                .filter(|(a, b)| a == b)
+                .count();
+            score += WEIGHT_ANCESTOR_PATH * same as f64 / longest as f64;
+        }
+        score
+    }
+}
+
+/// Trims the name and collapses runs of whitespace.
+fn normalize_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn path_segment(props: &SaveUIElement) -> String {
+    let id = if props.get_level() == 1 {
+        props.get_classname().to_string()
+    } else if props.get_automation_id().is_empty() {
+        normalize_name(props.get_name())
+    } else {
+        props.get_automation_id().to_string()
+    };
+    format!("{}[{}]", props.get_control_type(), id)
+}
+
+/// An element of the old tree mapped onto an element of the new tree.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ElementMatch {
+    old_index: usize,
+    new_index: usize,
+    confidence: f64,
+}
+
+impl ElementMatch {
+    /// Tree map index of the element in the old tree.
+    pub fn get_old_index(&self) -> usize {
+        self.old_index
+    }
+
+    /// Tree map index of the element in the new tree.
+    pub fn get_new_index(&self) -> usize {
+        self.new_index
+    }
+
+    pub fn get_confidence(&self) -> f64 {
+        self.confidence
+    }
+}
+
+/// Fingerprints of the alive nodes of `tree` in document order. The walker
+/// records the root twice; only the first occurrence of a runtime ID is kept.
+pub(crate) fn fingerprints(tree: &UITree) -> Vec<(usize, ElementFingerprint)> {
+    let map = tree.get_tree();
+    let mut result = Vec::new();
+    if map.node_count() == 0 {
+        return result;
+    }
+    let mut seen_rtids = HashSet::new();
+    let mut ancestors: Vec<&SaveUIElement> = Vec::new();
+    // (node, depth): ancestors[..depth] are the node's ancestors
+    let mut stack = vec![(map.root(), 0usize)];
+    while let Some((index, depth)) = stack.pop() {
+        let node = map.node(index);
+        if !node.is_alive {
+            continue;
+        }
+        ancestors.truncate(depth);
+        let (_, props) = tree.node(index);
+        if props.get_runtime_id().is_empty() || seen_rtids.insert(props.get_runtime_id()) {
+            result.push((index, ElementFingerprint::new(props, &ancestors)));
+        }
+        ancestors.push(props);
+        stack.extend(node.children.iter().rev().map(|&c| (c, depth + 1)));
+    }
+    result
+}
+
+impl UITree {
+    /// Fingerprint of the element at tree map `index`.
+    pub fn fingerprint(&self, index: usize) -> ElementFingerprint {
+        let map = self.get_tree();
+        let mut ancestors = Vec::new();
+        let mut current = index;
+        while current != map.root() {
+            current = map.node(current).parent;
+            ancestors.push(self.node(current).1);
+        }
+        ancestors.reverse();
+        ElementFingerprint::new(self.node(index).1, &ancestors)
+    }
+
+    /// Fingerprint of the element with the given `RtID` key (see
+    /// [`crate::UIElementInTree::get_rt_id`]), if it is in the tree.
+    pub fn fingerprint_by_rt_id(&self, rt_id: &str) -> Option<ElementFingerprint> {
+        self.get_tree()
+            .get_element_by_runtime_id(rt_id)
+            .map(|node| self.fingerprint(node.index))
+    }
+
+    /// The element most similar to `fingerprint` with a confidence of at least
+    /// `min_confidence`; ties go to the first element in document order.
+    pub fn find_by_fingerprint(
+        &self,
+        fingerprint: &ElementFingerprint,
+        min_confidence: f64,
+    ) -> Option<(usize, f64)> {
+        let mut best: Option<(usize, f64)> = None;
+        for (index, candidate) in fingerprints(self) {
+            let confidence = fingerprint.similarity(&candidate);
+            if confidence >= min_confidence && best.is_none_or(|(_, c)| confidence > c) {
+                best = Some((index, confidence));
+            }
+        }
+        best
+    }
+
+    /// Maps the elements of this (old) tree onto the elements of `new`.
+    ///
+    /// Elements are matched by runtime ID first, then by identical fingerprint
+    /// (both with confidence 1.0), then greedily by fingerprint similarity.
+    /// Matches below `min_confidence` are dropped. Every element is matched at
+    /// most once; the result is in the old tree's document order.
+    pub fn match_elements(&self, new: &UITree, min_confidence: f64) -> Vec<ElementMatch> {
+        let old_fps = fingerprints(self);
+        let new_fps = fingerprints(new);
+        let mut old_to_new: UIHashMap<usize, (usize, f64)> = UIHashMap::default();
+        let mut matched_new = HashSet::new();
+
+        // Pass 1: runtime IDs
+        let new_by_rtid: UIHashMap<&[i32], usize> = new_fps
+            .iter()
+            .filter(|(i, _)| !new.node(*i).1.get_runtime_id().is_empty())
+            .map(|(i, _)| (new.node(*i).1.get_runtime_id(), *i))
+            .collect();
+        let new_fp_by_index: UIHashMap<usize, &ElementFingerprint> =
+            new_fps.iter().map(|(i, fp)| (*i, fp)).collect();
+        for (o, fp) in &old_fps {
+            let rt_id = self.node(*o).1.get_runtime_id();
+            if rt_id.is_empty() {
+                continue;
+            }
+            if let Some(&n) = new_by_rtid.get(rt_id)
+                && new_fp_by_index[&n].control_type == fp.control_type
+            {
+                old_to_new.insert(*o, (n, 1.0));
+                matched_new.insert(n);
+            }
+        }
+
+        // Pass 2: identical fingerprints, in document order
+        let mut new_by_key: UIHashMap<String, VecDeque<usize>> = UIHashMap::default();
+        for (n, fp) in new_fps.iter().filter(|(n, _)| !matched_new.contains(n)) {
+            new_by_key.entry(fp.key()).or_default().push_back(*n);
+        }
+        for (o, fp) in &old_fps {
+            if old_to_new.contains_key(o) {
+                continue;
+            }
+            if let Some(n) = new_by_key.get_mut(&fp.key()).and_then(|q| q.pop_front()) {
+                old_to_new.insert(*o, (n, 1.0));
+                matched_new.insert(n);
+            }
+        }
+
+        // Pass 3: best similarity first, per control type
+        let mut groups: UIHashMap<&str, (Vec<usize>, Vec<usize>)> = UIHashMap::default();
+        for (pos, (o, fp)) in old_fps.iter().enumerate() {
+            if !old_to_new.contains_key(o) {
+                groups.entry(&fp.control_type).or_default().0.push(pos);
+            }
+        }
+        for (pos, (n, fp)) in new_fps.iter().enumerate() {
+            if !matched_new.contains(n)
+                && let Some(group) = groups.get_mut(fp.control_type.as_str())
+            {
+                group.1.push(pos);
+            }
+        }
+        for (control_type, (olds, news)) in groups {
+            if olds.len() * news.len() > MAX_FUZZY_PAIRS {
+                debug!(
+                    "Skipping fuzzy matching of {} x {} '{}' elements",
+                    olds.len(),
+                    news.len(),
+                    control_type
+                );
+                continue;
+            }
+            let mut pairs = Vec::new();
+            for &op in &olds {
+                for &np in &news {
+                    let confidence = old_fps[op].1.similarity(&new_fps[np].1);
+                    if confidence >= min_confidence {
+                        pairs.push((confidence, op, np));
+                    }
+                }
+            }
+            pairs.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+            for (confidence, op, np) in pairs {
+                let (o, n) = (old_fps[op].0, new_fps[np].0);
+                if !old_to_new.contains_key(&o) && matched_new.insert(n) {
+                    old_to_new.insert(o, (n, confidence));
+                }
+            }
+        }
+
+        old_fps
+            .iter()
+            .filter_map(|(o, _)| {
+                old_to_new
+                    .get(o)
+                    .filter(|(_, confidence)| *confidence >= min_confidence)
+                    .map(|&(n, confidence)| ElementMatch {
+                        old_index: *o,
+                        new_index: n,
+                        confidence,
+                    })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FakeBackend;
+    use crate::uiexplore_xml::get_all_elements_xml_with_backend;
+    use crate::walk_options::WalkOptions;
+    use std::sync::mpsc::channel;
+
+    fn elem(name: &str, ct: &str, rtid: i32) -> SaveUIElement {
+        SaveUIElement::default()
+            .with_name(name)
+            .with_control_type(ct)
+            .with_runtime_id(vec![42, rtid])
+    }
+
+    fn walk(backend: &FakeBackend) -> UITree {
+        let (tx, rx) = channel();
+        get_all_elements_xml_with_backend(tx, backend, None, &WalkOptions::default(), None);
+        rx.recv().unwrap().unwrap()
+    }
+
+    /// Desktop → Editor (Save button, text area, status bar with a dynamic text).
+    /// `offset` shifts every runtime ID, as after an application restart.
+    fn editor(offset: i32, status: &str) -> FakeBackend {
+        let mut backend = FakeBackend::new(elem("Desktop", "Pane", 1));
+        let app = backend.add_child(
+            0,
+            elem("Editor", "Window", offset + 2).with_classname("EditorFrame"),
+        );
+        backend.add_child(
+            app,
+            elem("Save", "Button", offset + 3).with_automation_id("save"),
+        );
+        backend.add_child(
+            app,
+            elem("Text", "Edit", offset + 4).with_automation_id("text"),
+        );
+        let bar = backend.add_child(app, elem("Status", "StatusBar", offset + 5));
+        backend.add_child(bar, elem(status, "Text", offset + 6));
+        backend
+    }
+
+    fn name_of(tree: &UITree, index: usize) -> &str {
+        tree.node(index).1.get_name()
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_whitespace_and_desktop() {
+        let tree = walk(&editor(0, "Ready"));
+        let save = tree
+            .get_tree()
+            .get_element_by_runtime_id("42-3")
+            .unwrap()
+            .index;
+        let fp = tree.fingerprint(save);
+        assert_eq!(fp.get_ancestor_path(), ["Window[EditorFrame]"]);
+        assert_eq!(fp.key(), "Button|save||Save|/Window[EditorFrame]");
+        assert_eq!(
+            ElementFingerprint::new(
+                &elem("  Save \t", "Button", 9).with_automation_id("save"),
+                &[tree
+                    .node(
+                        tree.get_tree()
+                            .get_element_by_runtime_id("42-2")
+                            .unwrap()
+                            .index
+                    )
+                    .1]
+            ),
+            fp
+        );
+        assert_eq!(tree.fingerprint_by_rt_id("42-3"), Some(fp));
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_window_title() {
+        let save_in = |title: &str| {
+            let mut backend = FakeBackend::new(elem("Desktop", "Pane", 1));
+            let app = backend.add_child(0, elem(title, "Window", 2).with_classname("EditorFrame"));
+            let pane = backend.add_child(app, elem("Toolbar", "Pane", 3));
+            backend.add_child(pane, elem("Save", "Button", 4).with_automation_id("save"));
+            walk(&backend).fingerprint_by_rt_id("42-4").unwrap()
+        };
+        let fp = save_in("notes.txt - Editor");
+        assert_eq!(fp, save_in("*notes.txt - Editor"));
+        // deeper ancestors are still identified by name
+        assert_eq!(
+            fp.get_ancestor_path(),
+            ["Window[EditorFrame]", "Pane[Toolbar]"]
+        );
+    }
+
+    #[test]
+    fn test_similarity() {
+        let tree = walk(&editor(0, "Ready"));
+        let fp = |rt: &str| {
+            tree.fingerprint(tree.get_tree().get_element_by_runtime_id(rt).unwrap().index)
+        };
+        assert_eq!(fp("42-3").similarity(&fp("42-3")), 1.0);
+        // different control types never match
+        assert_eq!(fp("42-3").similarity(&fp("42-4")), 0.0);
+
+        let renamed = ElementFingerprint {
+            name: "Save all".to_string(),
+            ..fp("42-3")
+        };
+        assert!((fp("42-3").similarity(&renamed) - 0.7).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_match_elements_after_restart() {
+        let old = walk(&editor(0, "Ready"));
+        let new = walk(&editor(100, "Saved 3 files"));
+        let matches = old.match_elements(&new, 0.5);
+
+        // every element is matched, the status text with a lower confidence
+        assert_eq!(matches.len(), fingerprints(&old).len());
+        for m in &matches {
+            let old_props = old.node(m.get_old_index()).1;
+            let new_props = new.node(m.get_new_index()).1;
+            assert_eq!(old_props.get_control_type(), new_props.get_control_type());
+            if old_props.get_control_type() == "Text" {
+                assert_eq!(name_of(&new, m.get_new_index()), "Saved 3 files");
+                assert!(m.get_confidence() < 1.0 && m.get_confidence() >= 0.5);
+            } else {
+                assert_eq!(old_props.get_name(), new_props.get_name());
+                assert_eq!(m.get_confidence(), 1.0);
+            }
+        }
+
+        // with a high threshold only exact matches remain
+        let strict = old.match_elements(&new, 0.9);
+        assert_eq!(strict.len(), matches.len() - 1);
+    }
+
+    #[test]
+    fn test_match_elements_prefers_runtime_id() {
+        let old = walk(&editor(0, "Ready"));
+        let mut renamed = editor(0, "Ready");
+        renamed.add_child(0, elem("Save", "Button", 50).with_automation_id("save"));
+        let new = walk(&renamed);
+        let save = old
+            .get_tree()
+            .get_element_by_runtime_id("42-3")
+            .unwrap()
+            .index;
+        let m = old
+            .match_elements(&new, 0.5)
+            .into_iter()
+            .find(|m| m.get_old_index() == save)
+            .unwrap();
+        assert_eq!(new.node(m.get_new_index()).1.get_runtime_id(), [42, 3]);
+    }
+
+    #[test]
+    fn test_find_by_fingerprint() {
+        let old = walk(&editor(0, "Ready"));
+        let new = walk(&editor(100, "Busy"));
+        let status = old.fingerprint(
+            old.get_tree()
+                .get_element_by_runtime_id("42-6")
+                .unwrap()
+                .index,
+        );
+        let (index, confidence) = new.find_by_fingerprint(&status, 0.5).unwrap();
+        assert_eq!(name_of(&new, index), "Busy");
+        assert!(confidence < 1.0);
+        assert!(new.find_by_fingerprint(&status, 0.95).is_none());
+    }
+}
@@ -0,0 +1,45 @@
+//! Interning of the element properties that repeat across a tree.
+//!
+//! Control types, class names and framework ids take only a few hundred
+//! distinct values even on desktops with tens of thousands of elements, so
+//! every element of a walk shares one allocation per distinct value.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+#[derive(Debug, Default)]
+pub(crate) struct StringInterner {
+    strings: HashSet<Arc<str>>,
+}
+
+impl StringInterner {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// The shared copy of `value`, added on first use.
+    pub(crate) fn intern(&mut self, value: &str) -> Arc<str> {
+        if let Some(interned) = self.strings.get(value) {
+            return Arc::clone(interned);
+        }
+        let interned: Arc<str> = Arc::from(value);
+        self.strings.insert(Arc::clone(&interned));
+        interned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern_shares_equal_strings() {
+        let mut interner = StringInterner::new();
+        let a = interner.intern("Button");
+        let b = interner.intern(&String::from("Button"));
+        let c = interner.intern("Edit");
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &c));
+        assert_eq!(interner.strings.len(), 2);
+    }
+}
@@ -0,0 +1,49 @@
+//! Minimal JSON output helpers for the machine-readable reports of this crate.
+
+use std::fmt::Write;
+
+/// Appends `value` to `out` as a quoted JSON string literal.
+pub(crate) fn push_str(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Appends `value` as a JSON string literal, or `null` for `None`.
+pub(crate) fn push_opt_str(out: &mut String, value: Option<&str>) {
+    match value {
+        Some(v) => push_str(out, v),
+        None => out.push_str("null"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_str_escapes_special_characters() {
+        let mut out = String::new();
+        push_str(&mut out, "a\"b\\c\nd\u{1}é");
+        assert_eq!(out, r#""a\"b\\c\nd\u0001é""#);
+    }
+
+    #[test]
+    fn test_push_opt_str_null() {
+        let mut out = String::new();
+        push_opt_str(&mut out, None);
+        assert_eq!(out, "null");
+    }
+}
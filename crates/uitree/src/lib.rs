@@ -6,13 +6,18 @@ pub use error::UITreeError;
 mod tree_map;
 use tree_map::UITreeMap;
 
+mod interner;
+
+mod rect;
+pub use rect::Rect;
+
 mod save_ui_element;
 pub use save_ui_element::SaveUIElement;
 /// Backward-compatible alias for the canonical `SaveUIElement` type.
 pub type SaveUIElementXML = SaveUIElement;
 
 mod common_types;
-pub use common_types::UIElementInTree;
+pub use common_types::{IncompleteReason, IncompleteWindow, UIElementInTree};
 /// Backward-compatible alias — all three tree walkers now share one `UIElementInTree`.
 pub type UIElementInTreeXML = UIElementInTree;
 /// Backward-compatible alias — all three tree walkers now share one `UIElementInTree`.
@@ -20,8 +25,51 @@ pub type UIElementInTreeIter = UIElementInTree;
 
 mod walker_common;
 
+mod walk_options;
+pub use walk_options::{PruningPolicy, TreeView, WalkOptions, WindowScope, XmlAttribute};
+
+mod progress;
+pub use progress::{ProgressUpdate, WalkProgress};
+
+mod backend;
+#[cfg(windows)]
+pub use backend::UIABackend;
+pub use backend::{FakeBackend, UIBackend};
+
 mod uiexplore_xml;
-pub use uiexplore_xml::{UITree, get_all_elements_par_xml, get_all_elements_xml};
+pub use uiexplore_xml::{
+    UITree, get_all_elements_par_xml_with_backend, get_all_elements_xml_with_backend,
+};
+#[cfg(windows)]
+pub use uiexplore_xml::{
+    get_all_elements_par_xml, get_all_elements_par_xml_scoped,
+    get_all_elements_par_xml_with_options, get_all_elements_xml, get_all_elements_xml_scoped,
+    get_all_elements_xml_with_options,
+};
+
+/// Re-exported so callers can bind XPath variables and inspect locator
+/// candidates without depending on `xmlutil`.
+pub use xmlutil::xpath_eval::{XpathValue, XpathVariables};
+pub use xmlutil::xpath_gen::{LocatorStrategy, XpathCandidate};
+
+mod spatial_index;
+pub use spatial_index::{Bounds, RegionMatch};
+
+mod query;
+pub use query::Query;
+
+mod snapshot;
+pub use snapshot::SNAPSHOT_VERSION;
+
+mod json;
+
+mod export;
+
+mod fingerprint;
+pub use fingerprint::{ElementFingerprint, ElementMatch};
+
+mod diff;
+pub use diff::{DiffEntry, DiffKind, PropertyChange, UITreeDiff};
 
 /// Deprecated: use `UITree` directly.
 pub type UITreeXML = UITree;
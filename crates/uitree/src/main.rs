@@ -1,99 +1,410 @@
 use chrono::Utc;
-use std::fs::{File, OpenOptions};
+use std::collections::BTreeMap;
+use std::fs::File;
 use std::io::{BufWriter, Write};
+use std::process::ExitCode;
 use std::time::{Duration, Instant};
 
+#[cfg(windows)]
 use std::sync::mpsc::channel;
+#[cfg(windows)]
 use std::sync::mpsc::{Receiver, Sender};
+#[cfg(windows)]
 use std::thread;
-use uitree::{UITreeError, UITreeXML, get_all_elements_par_xml, get_all_elements_xml};
+use uitree::{TreeView, UITreeError, UITreeXML};
+#[cfg(windows)]
+use uitree::{
+    WalkOptions, get_all_elements_par_xml_with_options, get_all_elements_xml_with_options,
+};
 
-struct FileWriter {
-    // outfile_name: PathBuf,
-    outfile_writer: BufWriter<File>,
+const USAGE: &str = "\
+Dumps the UI Automation tree of the desktop.
+
+Usage: uitree [OPTIONS]
+
+Options:
+  -m, --mode <serial|parallel>     Tree walker to use [default: parallel]
+  -d, --max-depth <N>              Stop descending below depth N
+  -w, --window <CAPTION>           Only keep windows whose caption contains CAPTION
+  -v, --view <raw|control|content> UI Automation view to walk [default: control]
+  -f, --format <FORMAT>            Output format: xml, text, snapshot, json, dot or html
+                                   [default: xml]
+  -o, --output <PATH>              Output file, '-' for stdout
+                                   [default: uitree_<format>_<timestamp>.<ext>]
+  -s, --stats                      Print tree statistics instead of writing the tree
+  -t, --timeout <SECS>             Give up after SECS seconds [default: 120]
+  -h, --help                       Print this help
+";
+
+/// Exit code for invalid command-line arguments.
+const EXIT_USAGE: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WalkMode {
+    Serial,
+    Parallel,
 }
 
-impl FileWriter {
-    fn new(outfile_prefix: &str) -> Self {
-        let tmstmp = Utc::now().format("%Y%m%d%H%M%S").to_string();
-        let filename = if outfile_prefix.contains("xml") {
-            format!("uitree_{}_{}.xml", outfile_prefix, tmstmp)
-        } else {
-            format!("uitree_{}_{}.txt", outfile_prefix, tmstmp)
-        };
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Xml,
+    Text,
+    Snapshot,
+    Json,
+    Dot,
+    Html,
+}
+
+impl OutputFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Xml | OutputFormat::Snapshot => "xml",
+            OutputFormat::Text => "txt",
+            OutputFormat::Json => "json",
+            OutputFormat::Dot => "dot",
+            OutputFormat::Html => "html",
+        }
+    }
 
-        let err_msg = format!("Unable to create file: {}", filename);
+    fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Xml => "xml",
+            OutputFormat::Text => "text",
+            OutputFormat::Snapshot => "snapshot",
+            OutputFormat::Json => "json",
+            OutputFormat::Dot => "dot",
+            OutputFormat::Html => "html",
+        }
+    }
+}
 
-        let f = OpenOptions::new()
-            .append(true)
-            .create(true)
-            .open(&filename)
-            .expect(&err_msg);
-        let outfile_writer = BufWriter::new(f);
+#[derive(Debug, Clone, PartialEq)]
+struct CliArgs {
+    mode: WalkMode,
+    max_depth: Option<usize>,
+    window: Option<String>,
+    view: TreeView,
+    format: OutputFormat,
+    output: Option<String>,
+    stats: bool,
+    timeout: Duration,
+}
 
-        FileWriter { outfile_writer }
+impl Default for CliArgs {
+    fn default() -> Self {
+        CliArgs {
+            mode: WalkMode::Parallel,
+            max_depth: None,
+            window: None,
+            view: TreeView::default(),
+            format: OutputFormat::Xml,
+            output: None,
+            stats: false,
+            timeout: Duration::from_secs(120),
+        }
     }
+}
 
-    fn write(&mut self, content: &str) {
-        self.outfile_writer
-            .write_all(content.as_bytes())
-            .expect("Unable to write to file");
+/// What the command line asks for: a dump, or just the help text.
+#[derive(Debug, PartialEq)]
+enum Command {
+    Run(CliArgs),
+    Help,
+}
+
+fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut cli = CliArgs::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        // accept both "--opt value" and "--opt=value"
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => {
+                (flag.to_string(), Some(value.to_string()))
+            }
+            _ => (arg, None),
+        };
+        let mut value = |name: &str| {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("missing value for {}", name))
+        };
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-s" | "--stats" => cli.stats = true,
+            "-m" | "--mode" => {
+                cli.mode = match value(&flag)?.as_str() {
+                    "serial" => WalkMode::Serial,
+                    "parallel" => WalkMode::Parallel,
+                    other => {
+                        return Err(format!(
+                            "unknown mode '{}', expected serial or parallel",
+                            other
+                        ));
+                    }
+                }
+            }
+            "-d" | "--max-depth" => {
+                let depth = value(&flag)?;
+                cli.max_depth = Some(
+                    depth
+                        .parse()
+                        .map_err(|_| format!("invalid max depth '{}'", depth))?,
+                );
+            }
+            "-w" | "--window" => cli.window = Some(value(&flag)?),
+            "-v" | "--view" => {
+                let view = value(&flag)?;
+                cli.view = TreeView::from_name(&view).ok_or_else(|| {
+                    format!("unknown view '{}', expected raw, control or content", view)
+                })?;
+            }
+            "-f" | "--format" => {
+                cli.format = match value(&flag)?.as_str() {
+                    "xml" => OutputFormat::Xml,
+                    "text" => OutputFormat::Text,
+                    "snapshot" => OutputFormat::Snapshot,
+                    "json" => OutputFormat::Json,
+                    "dot" => OutputFormat::Dot,
+                    "html" => OutputFormat::Html,
+                    other => {
+                        return Err(format!(
+                            "unknown format '{}', expected xml, text, snapshot, json, dot or html",
+                            other
+                        ));
+                    }
+                }
+            }
+            "-o" | "--output" => cli.output = Some(value(&flag)?),
+            "-t" | "--timeout" => {
+                let secs = value(&flag)?;
+                cli.timeout = Duration::from_secs(
+                    secs.parse()
+                        .map_err(|_| format!("invalid timeout '{}'", secs))?,
+                );
+            }
+            other => return Err(format!("unknown argument '{}'", other)),
+        }
     }
+    Ok(Command::Run(cli))
 }
 
-fn main() {
-    // create file writers
-    // let mut file_writer_recursive = FileWriter::new("recursive_uitree");
-    let mut file_writer_xml = FileWriter::new("xml_uitree");
-    let mut file_writer_par_xml = FileWriter::new("xml_parallel_uitree");
-
-    // XML Dom tree
-    let (tx_xml, rx_xml): (Sender<_>, Receiver<Result<UITreeXML, UITreeError>>) = channel();
-    println!("Spawning separate thread to get ui tree in XML format");
-    let start_xml = Instant::now();
-    thread::spawn(move || {
-        get_all_elements_xml(tx_xml, None, None, None, None, None);
+#[cfg(windows)]
+fn walk(cli: &CliArgs) -> Result<UITreeXML, UITreeError> {
+    let options = WalkOptions::default()
+        .with_max_depth(cli.max_depth)
+        .with_target_window_caption(cli.window.clone())
+        .with_tree_view(cli.view);
+    let (tx, rx): (Sender<_>, Receiver<Result<UITreeXML, UITreeError>>) = channel();
+    let mode = cli.mode;
+    thread::spawn(move || match mode {
+        WalkMode::Serial => get_all_elements_xml_with_options(tx, None, options, None),
+        WalkMode::Parallel => get_all_elements_par_xml_with_options(tx, options, None),
     });
-    println!("Spawned separate thread to get ui tree in XML format");
-    let ui_tree_xml: UITreeXML = rx_xml
-        .recv_timeout(Duration::from_secs(120))
-        .expect("UI tree XML refresh timed out or failed")
-        .expect("UI tree XML build failed");
-    let elapsed_xml = start_xml.elapsed();
-    println!(
-        "Time taken to get ui tree in XML format: {:#?}",
-        elapsed_xml
-    );
-    println!(
-        "No of elemetns in UI Tree XML: {:#}",
-        ui_tree_xml.get_elements().len()
+    rx.recv_timeout(cli.timeout)
+        .map_err(|_| UITreeError::Timeout(format!("no UI tree after {:?}", cli.timeout)))?
+}
+
+/// UI Automation is only available on Windows.
+#[cfg(not(windows))]
+fn walk(_cli: &CliArgs) -> Result<UITreeXML, UITreeError> {
+    Err(UITreeError::NoUIAutomation)
+}
+
+fn render(ui_tree: &UITreeXML, format: OutputFormat) -> Result<String, UITreeError> {
+    match format {
+        OutputFormat::Xml => Ok(ui_tree.get_xml_dom_tree().to_string()),
+        OutputFormat::Text => Ok(ui_tree.to_pretty_string()),
+        OutputFormat::Snapshot => ui_tree.to_snapshot_string(),
+        OutputFormat::Json => Ok(ui_tree.to_json()),
+        OutputFormat::Dot => Ok(ui_tree.to_dot()),
+        OutputFormat::Html => Ok(ui_tree.to_html_report()),
+    }
+}
+
+fn stats(ui_tree: &UITreeXML, elapsed: Duration) -> String {
+    let elements = ui_tree.get_elements();
+    let mut control_types: BTreeMap<String, usize> = BTreeMap::new();
+    let mut max_level = 0;
+    let mut windows = 0;
+    for element in elements {
+        let props = element.get_element_props();
+        *control_types
+            .entry(props.get_control_type().to_string())
+            .or_default() += 1;
+        max_level = max_level.max(props.get_level());
+        if props.get_level() == 1 {
+            windows += 1;
+        }
+    }
+
+    let mut out = format!(
+        "Elements: {}\nTop-level windows: {}\nMax depth: {}\nWalk time: {:?}\n",
+        elements.len(),
+        windows,
+        max_level,
+        elapsed
     );
-    file_writer_xml.write(ui_tree_xml.get_xml_dom_tree());
-    // dbg!(ui_tree_xml);
-    // println!("XML DOM tree: {}", xml_dom_tree);
-
-    //*****
-    // XML Dom tree - parallel
-    //*****
-    let (tx_par_xml, rx_par_xml): (Sender<_>, Receiver<Result<UITreeXML, UITreeError>>) = channel();
-    println!("Spawning separate thread to get ui tree in XML format");
-    let start_par_xml = Instant::now();
-    thread::spawn(move || {
-        get_all_elements_par_xml(tx_par_xml, None, None, None, None);
+    out.push_str("Control types:\n");
+    for (control_type, count) in &control_types {
+        out.push_str(&format!("  {}: {}\n", control_type, count));
+    }
+    for window in ui_tree.get_incomplete_windows() {
+        out.push_str(&format!(
+            "Incomplete window '{}' ({}): {:?}\n",
+            window.get_name(),
+            window.get_runtime_id(),
+            window.get_reason()
+        ));
+    }
+    out
+}
+
+fn write_output(path: &str, content: &str) -> Result<(), UITreeError> {
+    if path == "-" {
+        let mut stdout = std::io::stdout().lock();
+        return stdout
+            .write_all(content.as_bytes())
+            .map_err(|e| UITreeError::Io(e.to_string()));
+    }
+    let file = File::create(path).map_err(|e| UITreeError::Io(format!("{}: {}", path, e)))?;
+    let mut writer = BufWriter::new(file);
+    writer
+        .write_all(content.as_bytes())
+        .and_then(|_| writer.flush())
+        .map_err(|e| UITreeError::Io(format!("{}: {}", path, e)))
+}
+
+fn run(cli: &CliArgs) -> Result<(), UITreeError> {
+    let start = Instant::now();
+    let ui_tree = walk(cli)?;
+    let elapsed = start.elapsed();
+
+    if cli.stats {
+        print!("{}", stats(&ui_tree, elapsed));
+        return Ok(());
+    }
+
+    let path = cli.output.clone().unwrap_or_else(|| {
+        format!(
+            "uitree_{}_{}.{}",
+            cli.format.name(),
+            Utc::now().format("%Y%m%d%H%M%S"),
+            cli.format.extension()
+        )
     });
-    println!("Spawned separate thread to parallel get ui tree in XML format");
-    let ui_tree_par_xml: UITreeXML = rx_par_xml
-        .recv_timeout(Duration::from_secs(120))
-        .expect("Parallel UI tree XML refresh timed out or failed")
-        .expect("Parallel UI tree XML build failed");
-    let elapsed_par_xml = start_par_xml.elapsed();
-    println!(
-        "Time taken to get ui tree in parallel in XML format: {:#?}",
-        elapsed_par_xml
-    );
-    println!(
-        "No of elemetns in UI Tree XML: {:#}",
-        ui_tree_par_xml.get_elements().len()
-    );
-    file_writer_par_xml.write(ui_tree_par_xml.get_xml_dom_tree());
+    write_output(&path, &render(&ui_tree, cli.format)?)?;
+    if path != "-" {
+        eprintln!(
+            "Wrote {} elements to {} in {:?}",
+            ui_tree.get_elements().len(),
+            path,
+            elapsed
+        );
+    }
+    for window in ui_tree.get_incomplete_windows() {
+        eprintln!(
+            "Incomplete window '{}' ({}): {:?}",
+            window.get_name(),
+            window.get_runtime_id(),
+            window.get_reason()
+        );
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let cli = match parse_args(std::env::args().skip(1)) {
+        Ok(Command::Run(cli)) => cli,
+        Ok(Command::Help) => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    match run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        parse_args(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn test_defaults() {
+        assert_eq!(parse(&[]), Ok(Command::Run(CliArgs::default())));
+    }
+
+    #[test]
+    fn test_parses_all_options() {
+        let Ok(Command::Run(cli)) = parse(&[
+            "--mode",
+            "serial",
+            "-d",
+            "5",
+            "--window=Notepad",
+            "-v",
+            "raw",
+            "-f",
+            "snapshot",
+            "-o",
+            "out.xml",
+            "--stats",
+            "-t",
+            "30",
+        ]) else {
+            panic!("expected a run command");
+        };
+        assert_eq!(cli.mode, WalkMode::Serial);
+        assert_eq!(cli.max_depth, Some(5));
+        assert_eq!(cli.window.as_deref(), Some("Notepad"));
+        assert_eq!(cli.view, TreeView::Raw);
+        assert_eq!(cli.format, OutputFormat::Snapshot);
+        assert_eq!(cli.output.as_deref(), Some("out.xml"));
+        assert!(cli.stats);
+        assert_eq!(cli.timeout, Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_parses_export_formats() {
+        for (name, format) in [
+            ("json", OutputFormat::Json),
+            ("dot", OutputFormat::Dot),
+            ("html", OutputFormat::Html),
+        ] {
+            let Ok(Command::Run(cli)) = parse(&["-f", name]) else {
+                panic!("expected a run command");
+            };
+            assert_eq!(cli.format, format);
+            assert_eq!(cli.format.extension(), name);
+        }
+    }
+
+    #[test]
+    fn test_help() {
+        assert_eq!(parse(&["-s", "--help"]), Ok(Command::Help));
+    }
+
+    #[test]
+    fn test_rejects_invalid_arguments() {
+        assert!(parse(&["--mode", "fast"]).is_err());
+        assert!(parse(&["--max-depth", "deep"]).is_err());
+        assert!(parse(&["--view", "logical"]).is_err());
+        assert!(parse(&["--format"]).is_err());
+        assert!(parse(&["--format", "yaml"]).is_err());
+        assert!(parse(&["--verbose"]).is_err());
+    }
 }
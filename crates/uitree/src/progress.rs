@@ -0,0 +1,121 @@
+//! Progress reporting for running tree walks.
+
+use crate::uiexplore_xml::UITree;
+use crate::walk_options::WalkOptions;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+/// State of a running walk, sent every `progress_interval` elements.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgressUpdate {
+    /// Elements processed so far, summed over all worker threads.
+    pub elements_processed: usize,
+    /// Name of the top-level window currently being walked.
+    pub current_window: String,
+    /// Depth of the element just processed (the root is level 0).
+    pub depth: usize,
+    /// Time since the walk started.
+    pub elapsed: Duration,
+}
+
+/// Event sent over the progress channel set with [`WalkOptions::with_progress`].
+#[derive(Debug)]
+pub enum WalkProgress {
+    Update(ProgressUpdate),
+    /// Subtree of a top-level window that has been walked completely. Only
+    /// sent by the parallel walk, and only if partial trees are enabled.
+    WindowCompleted(Box<UITree>),
+}
+
+/// Counts processed elements and emits progress events; shared by all
+/// threads of one walk.
+#[derive(Debug)]
+pub(crate) struct ProgressTracker {
+    tx: Option<Sender<WalkProgress>>,
+    interval: usize,
+    partial_trees: bool,
+    started: Instant,
+    processed: AtomicUsize,
+}
+
+impl ProgressTracker {
+    pub(crate) fn new(options: &WalkOptions) -> Self {
+        ProgressTracker {
+            tx: options.get_progress_sender().cloned(),
+            interval: options.get_progress_interval(),
+            partial_trees: options.get_partial_trees(),
+            started: Instant::now(),
+            processed: AtomicUsize::new(0),
+        }
+    }
+
+    /// Counts one processed element and returns the new total.
+    pub(crate) fn element_processed(&self, current_window: &str, depth: usize) -> usize {
+        let processed = self.processed.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some(tx) = &self.tx
+            && processed.is_multiple_of(self.interval)
+        {
+            // A dropped receiver only means nobody is listening anymore
+            let _ = tx.send(WalkProgress::Update(ProgressUpdate {
+                elements_processed: processed,
+                current_window: current_window.to_string(),
+                depth,
+                elapsed: self.started.elapsed(),
+            }));
+        }
+        processed
+    }
+
+    pub(crate) fn window_completed(&self, subtree: &UITree) {
+        if let Some(tx) = &self.tx
+            && self.partial_trees
+        {
+            let _ = tx.send(WalkProgress::WindowCompleted(Box::new(subtree.clone())));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn test_tracker_sends_every_interval() {
+        let (tx, rx) = channel();
+        let tracker = ProgressTracker::new(
+            &WalkOptions::default()
+                .with_progress(tx)
+                .with_progress_interval(2),
+        );
+        for depth in 0..5 {
+            tracker.element_processed("App", depth);
+        }
+        drop(tracker);
+        let updates: Vec<_> = rx
+            .iter()
+            .map(|p| match p {
+                WalkProgress::Update(u) => (u.elements_processed, u.depth),
+                WalkProgress::WindowCompleted(_) => panic!("unexpected partial tree"),
+            })
+            .collect();
+        assert_eq!(updates, vec![(2, 1), (4, 3)]);
+    }
+
+    #[test]
+    fn test_tracker_partial_trees_opt_in() {
+        let (tx, rx) = channel();
+        let tracker = ProgressTracker::new(&WalkOptions::default().with_progress(tx.clone()));
+        tracker.window_completed(&UITree::empty());
+        let with_partial = ProgressTracker::new(
+            &WalkOptions::default()
+                .with_progress(tx)
+                .with_partial_trees(true),
+        );
+        with_partial.window_completed(&UITree::empty());
+        drop((tracker, with_partial));
+        assert_eq!(rx.iter().count(), 1);
+    }
+}
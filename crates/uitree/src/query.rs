@@ -0,0 +1,374 @@
+//! Typed queries over a `UITree`.
+//!
+//! A [`Query`] is evaluated directly against the tree map and the stored
+//! element properties, and can be rendered as the equivalent XPath for the
+//! XML DOM via [`Query::to_xpath`].
+
+use regex::Regex;
+use std::collections::HashSet;
+use xmlutil::xpath_string_literal;
+
+use crate::common_types::UIElementInTree;
+use crate::save_ui_element::SaveUIElement;
+use crate::uiexplore_xml::UITree;
+
+/// Tag the walker uses for elements without a control type.
+const UNKNOWN_CONTROL_TYPE: &str = "Unknown";
+
+#[derive(Debug, Clone)]
+enum Predicate {
+    Name(String),
+    NameContains(String),
+    NameMatches(Regex),
+    AutomationId(String),
+    ClassName(String),
+    FrameworkId(String),
+    RuntimeId(String),
+}
+
+impl Predicate {
+    fn matches(&self, props: &SaveUIElement, runtime_id: &str) -> bool {
+        match self {
+            Predicate::Name(name) => props.get_name() == name,
+            Predicate::NameContains(part) => props.get_name().contains(part.as_str()),
+            Predicate::NameMatches(regex) => regex.is_match(props.get_name()),
+            Predicate::AutomationId(id) => props.get_automation_id() == id,
+            Predicate::ClassName(class_name) => props.get_classname() == class_name,
+            Predicate::FrameworkId(framework_id) => props.get_framework_id() == framework_id,
+            Predicate::RuntimeId(rt_id) => runtime_id == rt_id,
+        }
+    }
+
+    fn to_xpath(&self) -> String {
+        match self {
+            Predicate::Name(name) => format!("@Name={}", xpath_string_literal(name)),
+            Predicate::NameContains(part) => {
+                format!("contains(@Name,{})", xpath_string_literal(part))
+            }
+            Predicate::NameMatches(regex) => {
+                format!("matches(@Name,{})", xpath_string_literal(regex.as_str()))
+            }
+            Predicate::AutomationId(id) => format!("@AutomationId={}", xpath_string_literal(id)),
+            Predicate::ClassName(class_name) => {
+                format!("@ClassName={}", xpath_string_literal(class_name))
+            }
+            Predicate::FrameworkId(framework_id) => {
+                format!("@FrameworkId={}", xpath_string_literal(framework_id))
+            }
+            Predicate::RuntimeId(rt_id) => format!("@RtID={}", xpath_string_literal(rt_id)),
+        }
+    }
+}
+
+/// A query for UI elements, built fluently:
+///
+/// `Query::control_type("Button").name_matches(regex).within(Query::control_type("Window")).nth(2)`
+///
+/// All conditions must hold. Results are returned in document order.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    control_type: Option<String>,
+    predicates: Vec<Predicate>,
+    within: Option<Box<Query>>,
+    nth: Option<usize>,
+}
+
+impl Query {
+    /// Matches every element.
+    pub fn any() -> Self {
+        Query::default()
+    }
+
+    /// Matches elements of the given control type, e.g. `"Button"`.
+    pub fn control_type(control_type: impl Into<String>) -> Self {
+        Query {
+            control_type: Some(control_type.into()),
+            ..Query::default()
+        }
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.predicates.push(Predicate::Name(name.into()));
+        self
+    }
+
+    pub fn name_contains(mut self, part: impl Into<String>) -> Self {
+        self.predicates.push(Predicate::NameContains(part.into()));
+        self
+    }
+
+    /// Elements whose name matches `regex` anywhere (unanchored, like XPath `matches`).
+    ///
+    /// [`Query::find_all`] uses the Rust `regex` syntax, while [`Query::to_xpath`]
+    /// passes the pattern unchanged to XPath `matches()`, whose syntax differs
+    /// (e.g. no inline flags such as `(?i)`, no `\b`). Keep to literals, `.`,
+    /// character classes, anchors and quantifiers if both forms are used.
+    pub fn name_matches(mut self, regex: Regex) -> Self {
+        self.predicates.push(Predicate::NameMatches(regex));
+        self
+    }
+
+    pub fn automation_id(mut self, automation_id: impl Into<String>) -> Self {
+        self.predicates
+            .push(Predicate::AutomationId(automation_id.into()));
+        self
+    }
+
+    pub fn class_name(mut self, class_name: impl Into<String>) -> Self {
+        self.predicates
+            .push(Predicate::ClassName(class_name.into()));
+        self
+    }
+
+    pub fn framework_id(mut self, framework_id: impl Into<String>) -> Self {
+        self.predicates
+            .push(Predicate::FrameworkId(framework_id.into()));
+        self
+    }
+
+    /// Matches the element with the given runtime ID in its `RtID` form, e.g. `"42-1-7"`.
+    pub fn runtime_id(mut self, runtime_id: impl Into<String>) -> Self {
+        self.predicates
+            .push(Predicate::RuntimeId(runtime_id.into()));
+        self
+    }
+
+    /// Only elements that are descendants of an element matched by `ancestor`.
+    pub fn within(mut self, ancestor: Query) -> Self {
+        self.within = Some(Box::new(ancestor));
+        self
+    }
+
+    /// Keeps only the `n`-th match (zero-based) in document order.
+    pub fn nth(mut self, n: usize) -> Self {
+        self.nth = Some(n);
+        self
+    }
+
+    /// All elements of `tree` matched by this query, in document order.
+    pub fn find_all<'a>(&self, tree: &'a UITree) -> Vec<&'a UIElementInTree> {
+        self.matching_nodes(tree)
+            .into_iter()
+            .map(|index| tree.element_for_node(index))
+            .collect()
+    }
+
+    /// The first element of `tree` matched by this query.
+    pub fn find_first<'a>(&self, tree: &'a UITree) -> Option<&'a UIElementInTree> {
+        self.find_all(tree).into_iter().next()
+    }
+
+    /// The equivalent XPath expression for the tree's XML DOM. Attribute
+    /// predicates only match if the walk emitted the attribute (see
+    /// [`crate::XmlAttribute`]).
+    pub fn to_xpath(&self) -> String {
+        let tag = match self.control_type.as_deref() {
+            Some("") => UNKNOWN_CONTROL_TYPE,
+            Some(control_type) => control_type,
+            None => "*",
+        };
+        let mut xpath = match &self.within {
+            Some(ancestor) => format!("{}//{}", ancestor.to_xpath(), tag),
+            None => format!("//{}", tag),
+        };
+        for predicate in &self.predicates {
+            xpath.push_str(&format!("[{}]", predicate.to_xpath()));
+        }
+        match self.nth {
+            Some(n) => format!("({})[{}]", xpath, n + 1),
+            None => xpath,
+        }
+    }
+
+    fn matches_element(&self, props: &SaveUIElement, runtime_id: &str) -> bool {
+        let control_type_matches = self.control_type.as_deref().is_none_or(|control_type| {
+            let actual = props.get_control_type();
+            actual == control_type || (actual.is_empty() && control_type == UNKNOWN_CONTROL_TYPE)
+        });
+        control_type_matches && self.predicates.iter().all(|p| p.matches(props, runtime_id))
+    }
+
+    /// Tree map indices of the matching nodes, in document (pre-)order.
+    fn matching_nodes(&self, tree: &UITree) -> Vec<usize> {
+        let map = tree.get_tree();
+        if map.node_count() == 0 || !map.has_node(map.root()) {
+            return Vec::new();
+        }
+        let ancestors: Option<HashSet<usize>> = self
+            .within
+            .as_ref()
+            .map(|q| q.matching_nodes(tree).into_iter().collect());
+
+        let mut found = Vec::new();
+        // (node, number of matched ancestors above it, whether it is the
+        // walker's copy of its parent, which is not part of the XML DOM)
+        let mut stack = vec![(map.root(), 0usize, false)];
+        while let Some((index, inside, is_copy)) = stack.pop() {
+            let node = map.node(index);
+            if !node.is_alive {
+                continue;
+            }
+            let (_, props) = tree.node(index);
+            let in_scope = ancestors.is_none() || inside > 0;
+            if !is_copy && in_scope && self.matches_element(props, &node.runtime_id) {
+                found.push(index);
+            }
+            let is_ancestor = ancestors.as_ref().is_some_and(|a| a.contains(&index));
+            let child_inside = inside + usize::from(is_ancestor);
+            stack.extend(node.children.iter().rev().map(|&c| {
+                let child_is_copy = map.node(c).runtime_id == node.runtime_id;
+                (c, child_inside, child_is_copy)
+            }));
+        }
+
+        match self.nth {
+            Some(n) => found.into_iter().nth(n).into_iter().collect(),
+            None => found,
+        }
+    }
+}
+
+impl UITree {
+    /// Shorthand for [`Query::find_all`].
+    pub fn query(&self, query: &Query) -> Vec<&UIElementInTree> {
+        query.find_all(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FakeBackend;
+    use crate::walk_options::WalkOptions;
+    use std::sync::mpsc::channel;
+
+    fn elem(name: &str, control_type: &str, rtid: i32) -> SaveUIElement {
+        SaveUIElement::default()
+            .with_name(name)
+            .with_control_type(control_type)
+            .with_runtime_id(vec![42, rtid])
+    }
+
+    /// Desktop → `Bob's "App"` (Toolbar with Save / Save as, Close) and
+    /// `Other` (Save, an element without control type).
+    fn desktop() -> UITree {
+        let mut fake = FakeBackend::new(elem("Desktop", "Pane", 1));
+        let app = fake.add_child(0, elem("Bob's \"App\"", "Window", 2));
+        let toolbar = fake.add_child(app, elem("Toolbar", "ToolBar", 3));
+        fake.add_child(toolbar, elem("Save", "Button", 4));
+        fake.add_child(
+            toolbar,
+            elem("Save as", "Button", 5).with_automation_id("saveAs"),
+        );
+        fake.add_child(app, elem("Close", "Button", 6));
+        let other = fake.add_child(0, elem("Other", "Window", 7));
+        fake.add_child(other, elem("Save", "Button", 8));
+        fake.add_child(other, elem("Blank", "", 9));
+
+        let (tx, rx) = channel();
+        crate::get_all_elements_xml_with_backend(tx, &fake, None, &WalkOptions::default(), None);
+        rx.recv().unwrap().unwrap()
+    }
+
+    fn names(elements: Vec<&UIElementInTree>) -> Vec<&str> {
+        elements
+            .into_iter()
+            .map(|e| e.get_element_props().get_name())
+            .collect()
+    }
+
+    /// Evaluating the query and its XPath must select the same elements.
+    fn assert_same_as_xpath(tree: &UITree, query: &Query) {
+        let direct: Vec<&SaveUIElement> = query
+            .find_all(tree)
+            .into_iter()
+            .map(|e| e.get_element_props())
+            .collect();
+        let via_xpath = tree
+            .get_elements_by_xpath(&query.to_xpath())
+            .unwrap_or_default();
+        assert_eq!(
+            direct.iter().map(|p| p.get_name()).collect::<Vec<_>>(),
+            via_xpath.iter().map(|p| p.get_name()).collect::<Vec<_>>(),
+            "xpath: {}",
+            query.to_xpath()
+        );
+    }
+
+    #[test]
+    fn test_control_type_and_name() {
+        let tree = desktop();
+        let query = Query::control_type("Button").name("Save");
+        assert_eq!(query.to_xpath(), "//Button[@Name='Save']");
+        assert_eq!(names(tree.query(&query)), ["Save", "Save"]);
+        assert_same_as_xpath(&tree, &query);
+    }
+
+    #[test]
+    fn test_name_matches_regex() {
+        let tree = desktop();
+        let query = Query::control_type("Button").name_matches(Regex::new("^Save").unwrap());
+        assert_eq!(query.to_xpath(), "//Button[matches(@Name,'^Save')]");
+        assert_eq!(names(tree.query(&query)), ["Save", "Save as", "Save"]);
+        assert_same_as_xpath(&tree, &query);
+    }
+
+    #[test]
+    fn test_within_and_nth() {
+        let tree = desktop();
+        let window = Query::control_type("Window").name_contains("App");
+        let query = Query::control_type("Button").within(window.clone()).nth(1);
+        assert_eq!(
+            query.to_xpath(),
+            "(//Window[contains(@Name,'App')]//Button)[2]"
+        );
+        assert_eq!(names(tree.query(&query)), ["Save as"]);
+        assert_same_as_xpath(&tree, &query);
+
+        let all = Query::control_type("Button").within(window);
+        assert_eq!(names(tree.query(&all)), ["Save", "Save as", "Close"]);
+        assert!(
+            Query::control_type("Button")
+                .nth(9)
+                .find_first(&tree)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_root_is_matched_once() {
+        let tree = desktop();
+        for query in [Query::any(), Query::control_type("Pane")] {
+            let found = names(tree.query(&query));
+            assert_eq!(found.iter().filter(|n| **n == "Desktop").count(), 1);
+            assert_same_as_xpath(&tree, &query);
+        }
+        let inside = Query::control_type("Button").within(Query::control_type("Pane"));
+        assert_eq!(names(tree.query(&inside)).len(), 4);
+        assert_same_as_xpath(&tree, &inside);
+    }
+
+    #[test]
+    fn test_names_with_quotes_are_escaped() {
+        let tree = desktop();
+        let query = Query::any().name("Bob's \"App\"");
+        assert_eq!(
+            query.to_xpath(),
+            r#"//*[@Name=concat('Bob',"'",'s "App"')]"#
+        );
+        assert_eq!(names(tree.query(&query)), ["Bob's \"App\""]);
+        assert_same_as_xpath(&tree, &query);
+    }
+
+    #[test]
+    fn test_attribute_predicates_and_unknown_control_type() {
+        let tree = desktop();
+        let query = Query::any().automation_id("saveAs");
+        assert_eq!(names(tree.query(&query)), ["Save as"]);
+        assert_same_as_xpath(&tree, &query);
+
+        let unknown = Query::control_type("Unknown");
+        assert_eq!(names(tree.query(&unknown)), ["Blank"]);
+        assert_same_as_xpath(&tree, &unknown);
+    }
+}
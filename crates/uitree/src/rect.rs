@@ -0,0 +1,64 @@
+//! Bounding rectangle of a `SaveUIElement`.
+//!
+//! On Windows this is UI Automation's own `Rect`. Elsewhere it is a plain
+//! stand-in with the same accessors, so trees can be built, saved and queried
+//! without the Windows dependencies, e.g. when running the tests on Linux.
+
+#[cfg(windows)]
+pub use uiautomation::types::Rect;
+
+#[cfg(not(windows))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rect {
+    left: i32,
+    top: i32,
+    right: i32,
+    bottom: i32,
+}
+
+#[cfg(not(windows))]
+impl Rect {
+    pub fn new(left: i32, top: i32, right: i32, bottom: i32) -> Self {
+        Rect {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    pub fn get_left(&self) -> i32 {
+        self.left
+    }
+
+    pub fn get_top(&self) -> i32 {
+        self.top
+    }
+
+    pub fn get_right(&self) -> i32 {
+        self.right
+    }
+
+    pub fn get_bottom(&self) -> i32 {
+        self.bottom
+    }
+
+    pub fn get_width(&self) -> i32 {
+        self.right - self.left
+    }
+
+    pub fn get_height(&self) -> i32 {
+        self.bottom - self.top
+    }
+}
+
+#[cfg(not(windows))]
+impl std::fmt::Display for Rect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[({}, {}), ({}, {})]",
+            self.left, self.top, self.right, self.bottom
+        )
+    }
+}
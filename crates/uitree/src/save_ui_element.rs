@@ -1,29 +1,96 @@
+#[cfg(windows)]
 use log::{debug, error, info};
+#[cfg(windows)]
 use uiautomation::UIElement;
+#[cfg(windows)]
+use uiautomation::patterns::{
+    UIExpandCollapsePattern, UIGridItemPattern, UIGridPattern, UIInvokePattern, UIPattern,
+    UIRangeValuePattern, UIScrollItemPattern, UIScrollPattern, UISelectionItemPattern,
+    UISelectionPattern, UITableItemPattern, UITablePattern, UITextPattern, UITogglePattern,
+    UITransformPattern, UIValuePattern, UIWindowPattern,
+};
+#[cfg(windows)]
 use uiautomation::types::Handle;
+#[cfg(windows)]
+use windows::core::IUnknown;
 
+#[cfg(windows)]
 use bromium_common::{RuntimeIdFilter, get_ui_automation_instance};
+use std::sync::Arc;
+
+use crate::interner::StringInterner;
+use crate::rect::Rect;
 
 #[derive(Debug, Clone)]
 pub struct SaveUIElement {
     name: String,
-    classname: String,
-    control_type: String,
-    localized_control_type: String,
-    framework_id: String,
+    // Shared between elements, see `SaveUIElement::intern`
+    classname: Arc<str>,
+    control_type: Arc<str>,
+    localized_control_type: Arc<str>,
+    framework_id: Arc<str>,
     runtime_id: Vec<i32>,
     automation_id: String,
     handle: isize,
-    bounding_rect: uiautomation::types::Rect,
+    bounding_rect: Rect,
     bounding_rect_size: i32,
     level: usize,
     z_order: usize,
     xpath: Option<String>,
+    is_enabled: bool,
+    is_offscreen: bool,
+    has_keyboard_focus: bool,
+    is_keyboard_focusable: bool,
+    help_text: String,
+    accelerator_key: String,
+    access_key: String,
+    process_id: u32,
+    value: Option<String>,
+    toggle_state: Option<String>,
+    supported_patterns: Vec<String>,
+}
+
+#[cfg(windows)]
+fn supports<T>(element: &UIElement) -> bool
+where
+    T: UIPattern + TryFrom<IUnknown, Error = uiautomation::Error>,
+{
+    element.get_pattern::<T>().is_ok()
 }
 
+#[cfg(windows)]
+type PatternProbe = fn(&UIElement) -> bool;
+
+/// Control patterns probed by `SaveUIElement::read_state`. Value and
+/// Toggle are probed separately since their current state is captured as well.
+#[cfg(windows)]
+const PATTERN_PROBES: [(&str, PatternProbe); 14] = [
+    ("Invoke", supports::<UIInvokePattern>),
+    ("SelectionItem", supports::<UISelectionItemPattern>),
+    ("Selection", supports::<UISelectionPattern>),
+    ("ExpandCollapse", supports::<UIExpandCollapsePattern>),
+    ("Scroll", supports::<UIScrollPattern>),
+    ("ScrollItem", supports::<UIScrollItemPattern>),
+    ("Window", supports::<UIWindowPattern>),
+    ("Text", supports::<UITextPattern>),
+    ("RangeValue", supports::<UIRangeValuePattern>),
+    ("Grid", supports::<UIGridPattern>),
+    ("GridItem", supports::<UIGridItemPattern>),
+    ("Table", supports::<UITablePattern>),
+    ("TableItem", supports::<UITableItemPattern>),
+    ("Transform", supports::<UITransformPattern>),
+];
+
 impl SaveUIElement {
-    /// Construct a `SaveUIElement` by extracting all properties from a `UIElement`
-    /// reference. The `UIElement` is borrowed — no COM `AddRef`/`Release` is needed.
+    /// Construct a `SaveUIElement` by extracting the identity and geometry of a
+    /// `UIElement` reference. The `UIElement` is borrowed — no COM
+    /// `AddRef`/`Release` is needed.
+    ///
+    /// The element state (focus, value, toggle state, supported patterns, ...)
+    /// costs a COM round trip per property and pattern, so it is only read by
+    /// [`SaveUIElement::read_state`]. The process id and offscreen flag are
+    /// always read since pruning and window scopes depend on them.
+    #[cfg(windows)]
     pub fn new(element: &UIElement, level: usize, z_order: usize) -> Self {
         let name = element.get_name().unwrap_or_default();
         let classname = element.get_classname().unwrap_or_default();
@@ -41,16 +108,18 @@ impl SaveUIElement {
             .into();
         let bounding_rect = element
             .get_bounding_rectangle()
-            .unwrap_or(uiautomation::types::Rect::new(0, 0, 0, 0));
+            .unwrap_or(Rect::new(0, 0, 0, 0));
         let bounding_rect_size = (bounding_rect.get_right() - bounding_rect.get_left())
             * (bounding_rect.get_bottom() - bounding_rect.get_top());
+        let is_offscreen = element.is_offscreen().unwrap_or_default();
+        let process_id = element.get_process_id().unwrap_or_default();
 
         SaveUIElement {
             name,
-            classname,
-            control_type,
-            localized_control_type,
-            framework_id,
+            classname: classname.into(),
+            control_type: control_type.into(),
+            localized_control_type: localized_control_type.into(),
+            framework_id: framework_id.into(),
             runtime_id,
             automation_id,
             handle,
@@ -58,8 +127,48 @@ impl SaveUIElement {
             bounding_rect_size,
             level,
             z_order,
-            xpath: None,
+            is_offscreen,
+            process_id,
+            ..Default::default()
+        }
+    }
+
+    /// Reads the element state (enabled, focus, help text, keys, value, toggle
+    /// state and supported patterns) from `element`, see
+    /// [`crate::WalkOptions::with_element_state`].
+    #[cfg(windows)]
+    pub fn read_state(&mut self, element: &UIElement) {
+        self.is_enabled = element.is_enabled().unwrap_or_default();
+        self.has_keyboard_focus = element.has_keyboard_focus().unwrap_or_default();
+        self.is_keyboard_focusable = element.is_keyboard_focusable().unwrap_or_default();
+        self.help_text = element.get_help_text().unwrap_or_default();
+        self.accelerator_key = element.get_accelerator_key().unwrap_or_default();
+        self.access_key = element.get_access_key().unwrap_or_default();
+
+        let mut supported_patterns = Vec::new();
+        self.value = match element.get_pattern::<UIValuePattern>() {
+            Ok(pattern) => {
+                supported_patterns.push("Value".to_string());
+                pattern.get_value().ok()
+            }
+            Err(_) => None,
+        };
+        self.toggle_state = match element.get_pattern::<UITogglePattern>() {
+            Ok(pattern) => {
+                supported_patterns.push("Toggle".to_string());
+                pattern
+                    .get_toggle_state()
+                    .ok()
+                    .map(|state| format!("{:?}", state))
+            }
+            Err(_) => None,
+        };
+        for (pattern_name, probe) in PATTERN_PROBES {
+            if probe(element) {
+                supported_patterns.push(pattern_name.to_string());
+            }
         }
+        self.supported_patterns = supported_patterns;
     }
 
     pub fn get_name(&self) -> &str {
@@ -89,7 +198,7 @@ impl SaveUIElement {
     pub fn get_bounding_rect_size(&self) -> i32 {
         self.bounding_rect_size
     }
-    pub fn get_bounding_rectangle(&self) -> &uiautomation::types::Rect {
+    pub fn get_bounding_rectangle(&self) -> &Rect {
         &self.bounding_rect
     }
     pub fn get_level(&self) -> usize {
@@ -101,7 +210,45 @@ impl SaveUIElement {
     pub fn get_xpath(&self) -> Option<&str> {
         self.xpath.as_deref()
     }
+    pub fn is_enabled(&self) -> bool {
+        self.is_enabled
+    }
+    pub fn is_offscreen(&self) -> bool {
+        self.is_offscreen
+    }
+    pub fn has_keyboard_focus(&self) -> bool {
+        self.has_keyboard_focus
+    }
+    pub fn is_keyboard_focusable(&self) -> bool {
+        self.is_keyboard_focusable
+    }
+    pub fn get_help_text(&self) -> &str {
+        &self.help_text
+    }
+    pub fn get_accelerator_key(&self) -> &str {
+        &self.accelerator_key
+    }
+    pub fn get_access_key(&self) -> &str {
+        &self.access_key
+    }
+    pub fn get_process_id(&self) -> u32 {
+        self.process_id
+    }
+    /// Current value from the Value pattern, if the element supports it.
+    pub fn get_value(&self) -> Option<&str> {
+        self.value.as_deref()
+    }
+    /// Current toggle state (`On`, `Off` or `Indeterminate`), if the element
+    /// supports the Toggle pattern.
+    pub fn get_toggle_state(&self) -> Option<&str> {
+        self.toggle_state.as_deref()
+    }
+    /// Names of the control patterns the element supports (e.g. `Invoke`, `Value`).
+    pub fn get_supported_patterns(&self) -> &[String] {
+        &self.supported_patterns
+    }
 
+    #[cfg(windows)]
     pub fn set_focus(&self) -> uiautomation::Result<()> {
         debug!(
             "Setting focus to element with runtime id: {:?}",
@@ -121,6 +268,128 @@ impl SaveUIElement {
         self.xpath = Some(xpath)
     }
 
+    /// Places the element at the given tree level and z-order.
+    pub(crate) fn set_position(&mut self, level: usize, z_order: usize) {
+        self.level = level;
+        self.z_order = z_order;
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn with_classname(mut self, classname: impl Into<String>) -> Self {
+        self.classname = classname.into().into();
+        self
+    }
+
+    pub fn with_control_type(mut self, control_type: impl Into<String>) -> Self {
+        self.control_type = control_type.into().into();
+        self
+    }
+
+    pub fn with_localized_control_type(
+        mut self,
+        localized_control_type: impl Into<String>,
+    ) -> Self {
+        self.localized_control_type = localized_control_type.into().into();
+        self
+    }
+
+    pub fn with_framework_id(mut self, framework_id: impl Into<String>) -> Self {
+        self.framework_id = framework_id.into().into();
+        self
+    }
+
+    /// Replaces the properties that repeat across elements with the shared
+    /// copies from `interner`.
+    pub(crate) fn intern(&mut self, interner: &mut StringInterner) {
+        self.classname = interner.intern(&self.classname);
+        self.control_type = interner.intern(&self.control_type);
+        self.localized_control_type = interner.intern(&self.localized_control_type);
+        self.framework_id = interner.intern(&self.framework_id);
+    }
+
+    pub fn with_runtime_id(mut self, runtime_id: Vec<i32>) -> Self {
+        self.runtime_id = runtime_id;
+        self
+    }
+
+    pub fn with_automation_id(mut self, automation_id: impl Into<String>) -> Self {
+        self.automation_id = automation_id.into();
+        self
+    }
+
+    pub fn with_handle(mut self, handle: isize) -> Self {
+        self.handle = handle;
+        self
+    }
+
+    /// Sets the bounding rectangle and recomputes the cached rectangle area.
+    pub fn with_bounding_rectangle(mut self, rect: Rect) -> Self {
+        self.bounding_rect_size =
+            (rect.get_right() - rect.get_left()) * (rect.get_bottom() - rect.get_top());
+        self.bounding_rect = rect;
+        self
+    }
+
+    pub fn with_enabled(mut self, is_enabled: bool) -> Self {
+        self.is_enabled = is_enabled;
+        self
+    }
+
+    pub fn with_offscreen(mut self, is_offscreen: bool) -> Self {
+        self.is_offscreen = is_offscreen;
+        self
+    }
+
+    pub fn with_keyboard_focus(mut self, has_keyboard_focus: bool) -> Self {
+        self.has_keyboard_focus = has_keyboard_focus;
+        self
+    }
+
+    pub fn with_keyboard_focusable(mut self, is_keyboard_focusable: bool) -> Self {
+        self.is_keyboard_focusable = is_keyboard_focusable;
+        self
+    }
+
+    pub fn with_help_text(mut self, help_text: impl Into<String>) -> Self {
+        self.help_text = help_text.into();
+        self
+    }
+
+    pub fn with_accelerator_key(mut self, accelerator_key: impl Into<String>) -> Self {
+        self.accelerator_key = accelerator_key.into();
+        self
+    }
+
+    pub fn with_access_key(mut self, access_key: impl Into<String>) -> Self {
+        self.access_key = access_key.into();
+        self
+    }
+
+    pub fn with_process_id(mut self, process_id: u32) -> Self {
+        self.process_id = process_id;
+        self
+    }
+
+    pub fn with_value(mut self, value: Option<String>) -> Self {
+        self.value = value;
+        self
+    }
+
+    pub fn with_toggle_state(mut self, toggle_state: Option<String>) -> Self {
+        self.toggle_state = toggle_state;
+        self
+    }
+
+    pub fn with_supported_patterns(mut self, supported_patterns: Vec<String>) -> Self {
+        self.supported_patterns = supported_patterns;
+        self
+    }
+
+    #[cfg(windows)]
     pub fn get_ui_automation_ui_element(&self) -> Option<UIElement> {
         debug!(
             "Getting ui element from SaveUIElement with runtime id: {:?}",
@@ -177,7 +446,7 @@ impl std::fmt::Display for SaveUIElement {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "SaveUIElement {{ name: {}, classname: {}, control_type: {}, localized_control_type: {}, framework_id: {}, runtime_id: {:?}, automation_id: {}, handle: {}, bounding_rect: {:?}, bounding_rect_size: {}, level: {}, z_order: {}, xpath: {:?} }}",
+            "SaveUIElement {{ name: {}, classname: {}, control_type: {}, localized_control_type: {}, framework_id: {}, runtime_id: {:?}, automation_id: {}, handle: {}, bounding_rect: {:?}, bounding_rect_size: {}, level: {}, z_order: {}, xpath: {:?}, is_enabled: {}, is_offscreen: {}, has_keyboard_focus: {}, is_keyboard_focusable: {}, help_text: {}, accelerator_key: {}, access_key: {}, process_id: {}, value: {:?}, toggle_state: {:?}, supported_patterns: {:?} }}",
             self.name,
             self.classname,
             self.control_type,
@@ -191,6 +460,17 @@ impl std::fmt::Display for SaveUIElement {
             self.level,
             self.z_order,
             self.xpath,
+            self.is_enabled,
+            self.is_offscreen,
+            self.has_keyboard_focus,
+            self.is_keyboard_focusable,
+            self.help_text,
+            self.accelerator_key,
+            self.access_key,
+            self.process_id,
+            self.value,
+            self.toggle_state,
+            self.supported_patterns,
         )
     }
 }
@@ -199,22 +479,34 @@ impl Default for SaveUIElement {
     fn default() -> Self {
         SaveUIElement {
             name: String::new(),
-            classname: String::new(),
-            control_type: String::new(),
-            localized_control_type: String::new(),
-            framework_id: String::new(),
+            classname: Arc::from(""),
+            control_type: Arc::from(""),
+            localized_control_type: Arc::from(""),
+            framework_id: Arc::from(""),
             runtime_id: Vec::new(),
             automation_id: String::new(),
             handle: 0,
-            bounding_rect: uiautomation::types::Rect::new(0, 0, 0, 0),
+            bounding_rect: Rect::new(0, 0, 0, 0),
             bounding_rect_size: 0,
             level: 0,
             z_order: 0,
             xpath: None,
+            is_enabled: false,
+            is_offscreen: false,
+            has_keyboard_focus: false,
+            is_keyboard_focusable: false,
+            help_text: String::new(),
+            accelerator_key: String::new(),
+            access_key: String::new(),
+            process_id: 0,
+            value: None,
+            toggle_state: None,
+            supported_patterns: Vec::new(),
         }
     }
 }
 
+#[cfg(windows)]
 impl TryFrom<&SaveUIElement> for UIElement {
     type Error = crate::error::UITreeError;
 
@@ -0,0 +1,896 @@
+//! Versioned on-disk snapshots of a `UITree`.
+//!
+//! A snapshot is an XML document holding the tree map (including tombstones,
+//! so arena indices survive the roundtrip), every `SaveUIElement` property
+//! and the windows that were not walked completely. The XML DOM is rendered
+//! from the tree map again after loading:
+//!
+//! ```xml
+//! <UITreeSnapshot Version="1" TreeView="control" XmlAttributes="AutomationId ClassName">
+//!   <TreeMap>
+//!     <Node Index="0" Parent="0" Alive="true" Name="..." RtID="..." Children="1 2"/>
+//!   </TreeMap>
+//!   <Elements>
+//!     <Element TreeIndex="0" RtID="42-1" Name="..." ControlType="..." RuntimeId="42,1" .../>
+//!   </Elements>
+//!   <IncompleteWindows>
+//!     <IncompleteWindow Name="..." RtID="42-7" Reason="TimedOut"/>
+//!   </IncompleteWindows>
+//! </UITreeSnapshot>
+//! ```
+
+use crate::UIHashMap;
+use crate::common_types::{IncompleteReason, IncompleteWindow, UIElementInTree};
+use crate::error::UITreeError;
+use crate::interner::StringInterner;
+use crate::rect::Rect;
+use crate::save_ui_element::SaveUIElement;
+use crate::tree_map::{UITreeMap, UITreeNode};
+use crate::uiexplore_xml::UITree;
+use crate::walk_options::{TreeView, XmlAttribute};
+
+use quick_xml::events::{BytesEnd, BytesStart, Event};
+use quick_xml::{Reader, Writer};
+use std::io::Cursor;
+use std::path::Path;
+use std::str::FromStr;
+
+use log::{debug, info};
+
+/// Current snapshot format version. Snapshots with another version are rejected.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+impl UITree {
+    /// Writes the complete tree to `path` as a versioned snapshot.
+    pub fn save_snapshot(&self, path: impl AsRef<Path>) -> Result<(), UITreeError> {
+        let path = path.as_ref();
+        let snapshot = self.to_snapshot_string()?;
+        std::fs::write(path, snapshot)
+            .map_err(|e| UITreeError::Io(format!("{}: {}", path.display(), e)))?;
+        info!(
+            "Saved UI tree snapshot with {} elements to {}",
+            self.get_elements().len(),
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// Reads a tree previously written with [`UITree::save_snapshot`].
+    pub fn load_snapshot(path: impl AsRef<Path>) -> Result<UITree, UITreeError> {
+        let path = path.as_ref();
+        let snapshot = std::fs::read_to_string(path)
+            .map_err(|e| UITreeError::Io(format!("{}: {}", path.display(), e)))?;
+        let tree = Self::from_snapshot_str(&snapshot)?;
+        info!(
+            "Loaded UI tree snapshot with {} elements from {}",
+            tree.get_elements().len(),
+            path.display()
+        );
+        Ok(tree)
+    }
+
+    /// Serializes the tree into the snapshot format.
+    pub fn to_snapshot_string(&self) -> Result<String, UITreeError> {
+        let mut writer = Writer::new_with_indent(Cursor::new(Vec::new()), b' ', 2);
+
+        let version = SNAPSHOT_VERSION.to_string();
+        let mut root = BytesStart::new("UITreeSnapshot");
+        root.push_attribute(("Version", version.as_str()));
+        root.push_attribute(("TreeView", self.get_tree_view().name()));
+        let xml_attributes: Vec<&str> = self
+            .get_xml_attributes()
+            .iter()
+            .map(|attribute| attribute.name())
+            .collect();
+        root.push_attribute(("XmlAttributes", xml_attributes.join(" ").as_str()));
+        write_event(&mut writer, Event::Start(root))?;
+
+        write_event(&mut writer, Event::Start(BytesStart::new("TreeMap")))?;
+        for node in self.get_tree().nodes() {
+            write_event(&mut writer, Event::Empty(node_to_xml(node)))?;
+        }
+        write_event(&mut writer, Event::End(BytesEnd::new("TreeMap")))?;
+
+        write_event(&mut writer, Event::Start(BytesStart::new("Elements")))?;
+        for elem in self.get_elements() {
+            write_event(&mut writer, Event::Empty(element_to_xml(elem)))?;
+        }
+        write_event(&mut writer, Event::End(BytesEnd::new("Elements")))?;
+
+        write_event(
+            &mut writer,
+            Event::Start(BytesStart::new("IncompleteWindows")),
+        )?;
+        for window in self.get_incomplete_windows() {
+            write_event(&mut writer, Event::Empty(incomplete_window_to_xml(window)))?;
+        }
+        write_event(&mut writer, Event::End(BytesEnd::new("IncompleteWindows")))?;
+
+        write_event(&mut writer, Event::End(BytesEnd::new("UITreeSnapshot")))?;
+
+        String::from_utf8(writer.into_inner().into_inner())
+            .map_err(|e| UITreeError::XmlError(e.to_string()))
+    }
+
+    /// Parses a tree from the snapshot format.
+    pub fn from_snapshot_str(snapshot: &str) -> Result<UITree, UITreeError> {
+        let mut reader = Reader::from_str(snapshot);
+        let mut has_root = false;
+        let mut nodes: Vec<UITreeNode<()>> = Vec::new();
+        let mut elements: Vec<UIElementInTree> = Vec::new();
+        let mut incomplete_windows: Vec<IncompleteWindow> = Vec::new();
+        let mut strings = StringInterner::new();
+        let mut tree_view = TreeView::default();
+        let mut xml_attributes = Vec::new();
+
+        loop {
+            let event = reader
+                .read_event()
+                .map_err(|e| UITreeError::Snapshot(e.to_string()))?;
+            match event {
+                Event::Start(ref e) | Event::Empty(ref e) => match e.name().as_ref() {
+                    b"UITreeSnapshot" => {
+                        let attrs = read_attributes(e)?;
+                        let v: u32 = parse_attr(&attrs, "Version")?;
+                        if v != SNAPSHOT_VERSION {
+                            return Err(UITreeError::Snapshot(format!(
+                                "unsupported snapshot version {} (expected {})",
+                                v, SNAPSHOT_VERSION
+                            )));
+                        }
+                        has_root = true;
+                        let name = required_attr(&attrs, "TreeView")?;
+                        tree_view = TreeView::from_name(name).ok_or_else(|| {
+                            UITreeError::Snapshot(format!("invalid TreeView value '{}'", name))
+                        })?;
+                        xml_attributes = required_attr(&attrs, "XmlAttributes")?
+                            .split_whitespace()
+                            .map(|name| {
+                                XmlAttribute::from_name(name).ok_or_else(|| {
+                                    UITreeError::Snapshot(format!(
+                                        "invalid XmlAttributes value '{}'",
+                                        name
+                                    ))
+                                })
+                            })
+                            .collect::<Result<_, _>>()?;
+                    }
+                    b"Node" => nodes.push(node_from_xml(&read_attributes(e)?)?),
+                    b"Element" => {
+                        elements.push(element_from_xml(&read_attributes(e)?, &mut strings)?)
+                    }
+                    b"IncompleteWindow" => {
+                        incomplete_windows.push(incomplete_window_from_xml(&read_attributes(e)?)?)
+                    }
+                    _ => {}
+                },
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        if !has_root {
+            return Err(UITreeError::Snapshot(
+                "missing UITreeSnapshot root element".to_string(),
+            ));
+        }
+        validate(&nodes, &elements)?;
+        debug!(
+            "Snapshot contains {} tree nodes and {} elements",
+            nodes.len(),
+            elements.len()
+        );
+
+        let mut tree = UITree::from_tree_map(UITreeMap::from_nodes(nodes), elements);
+        tree.set_tree_view(tree_view);
+        tree.set_incomplete_windows(incomplete_windows);
+        tree.set_xml_attributes(xml_attributes);
+        Ok(tree)
+    }
+}
+
+fn write_event(writer: &mut Writer<Cursor<Vec<u8>>>, event: Event<'_>) -> Result<(), UITreeError> {
+    writer
+        .write_event(event)
+        .map_err(|e| UITreeError::XmlError(e.to_string()))
+}
+
+fn join_numbers<T: ToString>(values: &[T], sep: &str) -> String {
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(sep)
+}
+
+fn split_numbers<T: FromStr>(value: &str, sep: char, attr: &str) -> Result<Vec<T>, UITreeError> {
+    value
+        .split(sep)
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.trim()
+                .parse()
+                .map_err(|_| UITreeError::Snapshot(format!("invalid {} value '{}'", attr, value)))
+        })
+        .collect()
+}
+
+fn node_to_xml(node: &UITreeNode<()>) -> BytesStart<'static> {
+    let mut start = BytesStart::new("Node");
+    start.push_attribute(("Index", node.index.to_string().as_str()));
+    start.push_attribute(("Parent", node.parent.to_string().as_str()));
+    start.push_attribute(("Alive", node.is_alive.to_string().as_str()));
+    start.push_attribute(("Name", &*node.name));
+    start.push_attribute(("RtID", &*node.runtime_id));
+    start.push_attribute(("Children", join_numbers(&node.children, " ").as_str()));
+    start
+}
+
+fn node_from_xml(attrs: &UIHashMap<String, String>) -> Result<UITreeNode<()>, UITreeError> {
+    Ok(UITreeNode {
+        name: required_attr(attrs, "Name")?.into(),
+        runtime_id: required_attr(attrs, "RtID")?.into(),
+        index: parse_attr(attrs, "Index")?,
+        parent: parse_attr(attrs, "Parent")?,
+        children: split_numbers(required_attr(attrs, "Children")?, ' ', "Children")?,
+        data: (),
+        is_alive: parse_attr(attrs, "Alive")?,
+    })
+}
+
+fn element_to_xml(elem: &UIElementInTree) -> BytesStart<'static> {
+    let props = elem.get_element_props();
+    let rect = props.get_bounding_rectangle();
+    let rect = [
+        rect.get_left(),
+        rect.get_top(),
+        rect.get_right(),
+        rect.get_bottom(),
+    ];
+
+    let mut start = BytesStart::new("Element");
+    start.push_attribute(("TreeIndex", elem.get_tree_index().to_string().as_str()));
+    start.push_attribute(("RtID", elem.get_rt_id()));
+    start.push_attribute(("Name", props.get_name()));
+    start.push_attribute(("ClassName", props.get_classname()));
+    start.push_attribute(("ControlType", props.get_control_type()));
+    start.push_attribute(("LocalizedControlType", props.get_localized_control_type()));
+    start.push_attribute(("FrameworkId", props.get_framework_id()));
+    start.push_attribute((
+        "RuntimeId",
+        join_numbers(props.get_runtime_id(), ",").as_str(),
+    ));
+    start.push_attribute(("AutomationId", props.get_automation_id()));
+    start.push_attribute(("Handle", props.get_handle().to_string().as_str()));
+    start.push_attribute(("BoundingRectangle", join_numbers(&rect, ",").as_str()));
+    start.push_attribute(("Level", props.get_level().to_string().as_str()));
+    start.push_attribute(("ZOrder", props.get_z_order().to_string().as_str()));
+    if let Some(xpath) = props.get_xpath() {
+        start.push_attribute(("XPath", xpath));
+    }
+    start.push_attribute(("IsEnabled", props.is_enabled().to_string().as_str()));
+    start.push_attribute(("IsOffscreen", props.is_offscreen().to_string().as_str()));
+    start.push_attribute((
+        "HasKeyboardFocus",
+        props.has_keyboard_focus().to_string().as_str(),
+    ));
+    start.push_attribute((
+        "IsKeyboardFocusable",
+        props.is_keyboard_focusable().to_string().as_str(),
+    ));
+    start.push_attribute(("HelpText", props.get_help_text()));
+    start.push_attribute(("AcceleratorKey", props.get_accelerator_key()));
+    start.push_attribute(("AccessKey", props.get_access_key()));
+    start.push_attribute(("ProcessId", props.get_process_id().to_string().as_str()));
+    if let Some(value) = props.get_value() {
+        start.push_attribute(("Value", value));
+    }
+    if let Some(toggle_state) = props.get_toggle_state() {
+        start.push_attribute(("ToggleState", toggle_state));
+    }
+    start.push_attribute((
+        "SupportedPatterns",
+        props.get_supported_patterns().join(" ").as_str(),
+    ));
+    start
+}
+
+fn element_from_xml(
+    attrs: &UIHashMap<String, String>,
+    strings: &mut StringInterner,
+) -> Result<UIElementInTree, UITreeError> {
+    let rect: Vec<i32> = split_numbers(
+        required_attr(attrs, "BoundingRectangle")?,
+        ',',
+        "BoundingRectangle",
+    )?;
+    let [left, top, right, bottom] = rect[..] else {
+        return Err(UITreeError::Snapshot(format!(
+            "BoundingRectangle needs 4 values, found {}",
+            rect.len()
+        )));
+    };
+
+    let mut props = SaveUIElement::default()
+        .with_name(required_attr(attrs, "Name")?)
+        .with_classname(required_attr(attrs, "ClassName")?)
+        .with_control_type(required_attr(attrs, "ControlType")?)
+        .with_localized_control_type(required_attr(attrs, "LocalizedControlType")?)
+        .with_framework_id(required_attr(attrs, "FrameworkId")?)
+        .with_runtime_id(split_numbers(
+            required_attr(attrs, "RuntimeId")?,
+            ',',
+            "RuntimeId",
+        )?)
+        .with_automation_id(required_attr(attrs, "AutomationId")?)
+        .with_handle(parse_attr(attrs, "Handle")?)
+        .with_bounding_rectangle(Rect::new(left, top, right, bottom))
+        .with_enabled(parse_attr(attrs, "IsEnabled")?)
+        .with_offscreen(parse_attr(attrs, "IsOffscreen")?)
+        .with_keyboard_focus(parse_attr(attrs, "HasKeyboardFocus")?)
+        .with_keyboard_focusable(parse_attr(attrs, "IsKeyboardFocusable")?)
+        .with_help_text(required_attr(attrs, "HelpText")?)
+        .with_accelerator_key(required_attr(attrs, "AcceleratorKey")?)
+        .with_access_key(required_attr(attrs, "AccessKey")?)
+        .with_process_id(parse_attr(attrs, "ProcessId")?)
+        .with_value(attrs.get("Value").cloned())
+        .with_toggle_state(attrs.get("ToggleState").cloned())
+        .with_supported_patterns(
+            required_attr(attrs, "SupportedPatterns")?
+                .split_whitespace()
+                .map(str::to_string)
+                .collect(),
+        );
+    props.set_position(parse_attr(attrs, "Level")?, parse_attr(attrs, "ZOrder")?);
+    if let Some(xpath) = attrs.get("XPath") {
+        props.set_xpath(xpath.clone());
+    }
+    props.intern(strings);
+
+    Ok(UIElementInTree::new(props, parse_attr(attrs, "TreeIndex")?)
+        .with_rt_id(required_attr(attrs, "RtID")?.to_string()))
+}
+
+fn incomplete_window_to_xml(window: &IncompleteWindow) -> BytesStart<'static> {
+    let mut start = BytesStart::new("IncompleteWindow");
+    start.push_attribute(("Name", window.get_name()));
+    start.push_attribute(("RtID", window.get_runtime_id()));
+    match window.get_reason() {
+        IncompleteReason::TimedOut => start.push_attribute(("Reason", "TimedOut")),
+        IncompleteReason::Unresponsive => start.push_attribute(("Reason", "Unresponsive")),
+        IncompleteReason::Failed(error) => {
+            start.push_attribute(("Reason", "Failed"));
+            start.push_attribute(("Error", error.as_str()));
+        }
+    }
+    start
+}
+
+fn incomplete_window_from_xml(
+    attrs: &UIHashMap<String, String>,
+) -> Result<IncompleteWindow, UITreeError> {
+    let reason = match required_attr(attrs, "Reason")? {
+        "TimedOut" => IncompleteReason::TimedOut,
+        "Unresponsive" => IncompleteReason::Unresponsive,
+        "Failed" => IncompleteReason::Failed(required_attr(attrs, "Error")?.to_string()),
+        other => {
+            return Err(UITreeError::Snapshot(format!(
+                "invalid Reason value '{}'",
+                other
+            )));
+        }
+    };
+    Ok(IncompleteWindow::new(
+        required_attr(attrs, "Name")?.to_string(),
+        required_attr(attrs, "RtID")?.to_string(),
+        reason,
+    ))
+}
+
+fn read_attributes(start: &BytesStart<'_>) -> Result<UIHashMap<String, String>, UITreeError> {
+    let mut attrs = UIHashMap::default();
+    for attr in start.attributes() {
+        let attr = attr.map_err(|e| UITreeError::Snapshot(e.to_string()))?;
+        let key = String::from_utf8_lossy(attr.key.as_ref()).into_owned();
+        let value = attr
+            .unescape_value()
+            .map_err(|e| UITreeError::Snapshot(e.to_string()))?
+            .into_owned();
+        attrs.insert(key, value);
+    }
+    Ok(attrs)
+}
+
+fn required_attr<'a>(
+    attrs: &'a UIHashMap<String, String>,
+    name: &str,
+) -> Result<&'a str, UITreeError> {
+    attrs
+        .get(name)
+        .map(String::as_str)
+        .ok_or_else(|| UITreeError::Snapshot(format!("missing attribute '{}'", name)))
+}
+
+fn parse_attr<T: FromStr>(attrs: &UIHashMap<String, String>, name: &str) -> Result<T, UITreeError> {
+    let value = required_attr(attrs, name)?;
+    value
+        .parse()
+        .map_err(|_| UITreeError::Snapshot(format!("invalid {} value '{}'", name, value)))
+}
+
+/// Checks that all cross references in the snapshot are consistent, so a
+/// hand-edited or truncated file cannot cause panics or endless recursion later:
+/// references stay inside the arena, every alive node has exactly one element,
+/// parent and child links agree and the tree below the root has no cycles.
+fn validate(nodes: &[UITreeNode<()>], elements: &[UIElementInTree]) -> Result<(), UITreeError> {
+    let Some(root) = nodes.first() else {
+        return Err(UITreeError::Snapshot("tree map has no nodes".to_string()));
+    };
+    if !root.is_alive {
+        return Err(UITreeError::Snapshot("root node is not alive".to_string()));
+    }
+    for (pos, node) in nodes.iter().enumerate() {
+        if !node.is_alive {
+            continue;
+        }
+        if node.index != pos {
+            return Err(UITreeError::Snapshot(format!(
+                "node at position {} has index {}",
+                pos, node.index
+            )));
+        }
+        if node.parent >= nodes.len() || node.children.iter().any(|&c| c >= nodes.len()) {
+            return Err(UITreeError::Snapshot(format!(
+                "node {} references a node outside the tree",
+                pos
+            )));
+        }
+        if pos != 0 && !nodes[node.parent].children.contains(&pos) {
+            return Err(UITreeError::Snapshot(format!(
+                "node {} is not a child of its parent {}",
+                pos, node.parent
+            )));
+        }
+        if let Some(&child) = node
+            .children
+            .iter()
+            .find(|&&c| nodes[c].is_alive && nodes[c].parent != pos)
+        {
+            return Err(UITreeError::Snapshot(format!(
+                "child {} of node {} has parent {}",
+                child, pos, nodes[child].parent
+            )));
+        }
+    }
+
+    let mut element_for_node = vec![false; nodes.len()];
+    for elem in elements {
+        let index = elem.get_tree_index();
+        let Some(node) = nodes.get(index).filter(|n| n.is_alive) else {
+            return Err(UITreeError::Snapshot(format!(
+                "element tree index {} is not an alive node",
+                index
+            )));
+        };
+        if *node.runtime_id != *elem.get_rt_id() {
+            return Err(UITreeError::Snapshot(format!(
+                "element RtID '{}' does not match node {} ('{}')",
+                elem.get_rt_id(),
+                index,
+                node.runtime_id
+            )));
+        }
+        if std::mem::replace(&mut element_for_node[index], true) {
+            return Err(UITreeError::Snapshot(format!(
+                "node {} has more than one element",
+                index
+            )));
+        }
+    }
+    if let Some(node) = nodes
+        .iter()
+        .find(|n| n.is_alive && !element_for_node[n.index])
+    {
+        return Err(UITreeError::Snapshot(format!(
+            "node {} has no element",
+            node.index
+        )));
+    }
+
+    // Iterative, so a deep or cyclic tree cannot overflow the stack here
+    let mut visited = vec![false; nodes.len()];
+    let mut stack = vec![0];
+    while let Some(index) = stack.pop() {
+        if std::mem::replace(&mut visited[index], true) {
+            return Err(UITreeError::Snapshot(format!(
+                "node {} is reached twice from the root",
+                index
+            )));
+        }
+        stack.extend(
+            nodes[index]
+                .children
+                .iter()
+                .copied()
+                .filter(|&c| nodes[c].is_alive),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FakeBackend;
+    use crate::uiexplore_xml::{
+        get_all_elements_par_xml_with_backend, get_all_elements_xml_with_backend,
+    };
+    use crate::walk_options::WalkOptions;
+    use std::sync::mpsc::channel;
+
+    fn build_fake_desktop() -> FakeBackend {
+        let elem = |name: &str, ct: &str, rtid: i32| {
+            SaveUIElement::default()
+                .with_name(name)
+                .with_control_type(ct)
+                .with_localized_control_type(ct.to_lowercase())
+                .with_classname(format!("{}Class", ct))
+                .with_framework_id("Win32")
+                .with_automation_id(format!("auto-{}", rtid))
+                .with_runtime_id(vec![42, rtid])
+                .with_bounding_rectangle(Rect::new(rtid, rtid * 2, rtid * 10, rtid * 20))
+                .with_enabled(true)
+                .with_process_id(1000 + rtid as u32)
+        };
+        let mut backend = FakeBackend::new(elem("Desktop", "Pane", 1));
+        let app = backend.add_child(0, elem("App & <Co>", "Window", 2).with_handle(4242));
+        backend.add_child(
+            app,
+            elem("\"OK\"", "Button", 3)
+                .with_keyboard_focusable(true)
+                .with_access_key("Alt+O")
+                .with_supported_patterns(vec!["Invoke".to_string()]),
+        );
+        backend.add_child(
+            app,
+            elem("Username", "Edit", 4)
+                .with_keyboard_focus(true)
+                .with_help_text("Your <login> name")
+                .with_value(Some("alice".to_string()))
+                .with_supported_patterns(vec!["Value".to_string(), "Text".to_string()]),
+        );
+        let other = backend.add_child(0, elem("Other", "Window", 5));
+        backend.add_child(other, elem("Close", "Button", 6));
+        backend
+    }
+
+    fn walk(backend: &FakeBackend) -> UITree {
+        let (tx, rx) = channel();
+        get_all_elements_xml_with_backend(tx, backend, None, &WalkOptions::default(), None);
+        rx.recv().unwrap().unwrap()
+    }
+
+    fn assert_same_tree(a: &UITree, b: &UITree) {
+        assert_eq!(a.get_tree_view(), b.get_tree_view());
+        assert_eq!(a.get_xml_attributes(), b.get_xml_attributes());
+        let incomplete = |t: &UITree| {
+            t.get_incomplete_windows()
+                .iter()
+                .map(|w| {
+                    (
+                        w.get_name().to_string(),
+                        w.get_runtime_id().to_string(),
+                        w.get_reason().clone(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(incomplete(a), incomplete(b));
+        assert_eq!(a.get_xml_dom_tree(), b.get_xml_dom_tree());
+        assert_eq!(a.get_tree().node_count(), b.get_tree().node_count());
+        for (na, nb) in a.get_tree().nodes().iter().zip(b.get_tree().nodes()) {
+            assert_eq!(na.name, nb.name);
+            assert_eq!(na.runtime_id, nb.runtime_id);
+            assert_eq!(na.parent, nb.parent);
+            assert_eq!(na.children, nb.children);
+            assert_eq!(na.is_alive, nb.is_alive);
+        }
+        assert_eq!(a.get_elements().len(), b.get_elements().len());
+        for (ea, eb) in a.get_elements().iter().zip(b.get_elements()) {
+            assert_eq!(ea.get_tree_index(), eb.get_tree_index());
+            assert_eq!(ea.get_rt_id(), eb.get_rt_id());
+            // Display covers every SaveUIElement field
+            assert_eq!(
+                ea.get_element_props().to_string(),
+                eb.get_element_props().to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip_preserves_everything() {
+        let tree = walk(&build_fake_desktop());
+        let snapshot = tree.to_snapshot_string().unwrap();
+        let loaded = UITree::from_snapshot_str(&snapshot).unwrap();
+        assert_same_tree(&tree, &loaded);
+    }
+
+    #[test]
+    fn test_snapshot_queries_work_after_load() {
+        let tree = walk(&build_fake_desktop());
+        let loaded = UITree::from_snapshot_str(&tree.to_snapshot_string().unwrap()).unwrap();
+
+        let edit = loaded.get_element_by_xpath("//Edit").unwrap();
+        assert_eq!(edit.get_value(), Some("alice"));
+        assert!(edit.has_keyboard_focus());
+        assert_eq!(edit.get_automation_id(), "auto-4");
+        assert_eq!(edit.get_bounding_rectangle().get_right(), 40);
+        assert_eq!(edit.get_classname(), "EditClass");
+
+        for idx in 1..tree.get_tree().node_count() {
+            assert_eq!(
+                tree.get_xpath_for_element(idx, false).unwrap(),
+                loaded.get_xpath_for_element(idx, false).unwrap()
+            );
+        }
+
+        let mut visited = 0;
+        loaded.for_each(|_, _| visited += 1);
+        let mut expected = 0;
+        tree.for_each(|_, _| expected += 1);
+        assert_eq!(visited, expected);
+    }
+
+    #[test]
+    fn test_snapshot_keeps_tombstones() {
+        let backend = build_fake_desktop();
+        let (tx, rx) = channel();
+        get_all_elements_par_xml_with_backend(
+            tx,
+            move || Ok(backend.clone()),
+            WalkOptions::default(),
+            None,
+        );
+        let tree = rx.recv().unwrap().unwrap();
+        assert!(tree.get_tree().nodes().iter().any(|n| !n.is_alive));
+
+        let loaded = UITree::from_snapshot_str(&tree.to_snapshot_string().unwrap()).unwrap();
+        assert_same_tree(&tree, &loaded);
+        assert!(
+            loaded
+                .get_element_by_xpath("//Button[@Name='Close']")
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_snapshot_file_roundtrip() {
+        let tree = walk(&build_fake_desktop());
+        let path =
+            std::env::temp_dir().join(format!("uitree_snapshot_test_{}.xml", std::process::id()));
+        tree.save_snapshot(&path).unwrap();
+        let loaded = UITree::load_snapshot(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_same_tree(&tree, &loaded.unwrap());
+    }
+
+    #[test]
+    fn test_snapshot_missing_file() {
+        let result = UITree::load_snapshot("/definitely/not/here/snapshot.xml");
+        assert!(matches!(result, Err(UITreeError::Io(_))));
+    }
+
+    #[test]
+    fn test_snapshot_rejects_newer_version() {
+        let tree = walk(&build_fake_desktop());
+        let snapshot =
+            tree.to_snapshot_string()
+                .unwrap()
+                .replacen("Version=\"1\"", "Version=\"999\"", 1);
+        let result = UITree::from_snapshot_str(&snapshot);
+        assert!(matches!(result, Err(UITreeError::Snapshot(_))));
+    }
+
+    #[test]
+    fn test_snapshot_keeps_tree_view() {
+        let (tx, rx) = channel();
+        get_all_elements_xml_with_backend(
+            tx,
+            &build_fake_desktop(),
+            None,
+            &WalkOptions::default().with_tree_view(TreeView::Raw),
+            None,
+        );
+        let tree = rx.recv().unwrap().unwrap();
+        assert_eq!(tree.get_tree_view(), TreeView::Raw);
+        let snapshot = tree.to_snapshot_string().unwrap();
+        assert_same_tree(&tree, &UITree::from_snapshot_str(&snapshot).unwrap());
+    }
+
+    #[test]
+    fn test_snapshot_keeps_incomplete_windows() {
+        let (tx, rx) = channel();
+        get_all_elements_xml_with_backend(
+            tx,
+            &build_fake_desktop(),
+            None,
+            &WalkOptions::default().with_window_budget(Some(std::time::Duration::ZERO)),
+            None,
+        );
+        let mut tree = rx.recv().unwrap().unwrap();
+        let mut windows = tree.get_incomplete_windows().to_vec();
+        windows.push(IncompleteWindow::new(
+            "Broken \"<app>\"".to_string(),
+            "42-9".to_string(),
+            IncompleteReason::Failed("no & access".to_string()),
+        ));
+        windows.push(IncompleteWindow::new(
+            "Hung".to_string(),
+            "42-10".to_string(),
+            IncompleteReason::Unresponsive,
+        ));
+        tree.set_incomplete_windows(windows);
+
+        let snapshot = tree.to_snapshot_string().unwrap();
+        let loaded = UITree::from_snapshot_str(&snapshot).unwrap();
+        assert!(!loaded.is_complete());
+        assert_eq!(loaded.get_incomplete_windows().len(), 4);
+        assert_same_tree(&tree, &loaded);
+    }
+
+    #[test]
+    fn test_snapshot_keeps_xml_attributes() {
+        let (tx, rx) = channel();
+        get_all_elements_xml_with_backend(
+            tx,
+            &build_fake_desktop(),
+            None,
+            &WalkOptions::default().with_xml_attributes(&[XmlAttribute::FrameworkId]),
+            None,
+        );
+        let tree = rx.recv().unwrap().unwrap();
+        let snapshot = tree.to_snapshot_string().unwrap();
+        let loaded = UITree::from_snapshot_str(&snapshot).unwrap();
+        assert_same_tree(&tree, &loaded);
+        // the XPath document has the same attributes as the walked tree
+        assert!(
+            loaded
+                .get_element_by_xpath("//Edit[@FrameworkId]")
+                .is_some()
+        );
+        assert!(
+            loaded
+                .get_element_by_xpath("//Edit[@AutomationId]")
+                .is_none()
+        );
+        for idx in 1..tree.get_tree().node_count() {
+            assert_eq!(
+                tree.get_xpath_for_element(idx, false).ok(),
+                loaded.get_xpath_for_element(idx, false).ok()
+            );
+        }
+
+        let invalid = snapshot.replacen(
+            "XmlAttributes=\"FrameworkId\"",
+            "XmlAttributes=\"Colour\"",
+            1,
+        );
+        assert!(matches!(
+            UITree::from_snapshot_str(&invalid),
+            Err(UITreeError::Snapshot(_))
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_renders_xml_dom_on_load() {
+        let tree = walk(&build_fake_desktop());
+        let snapshot = tree.to_snapshot_string().unwrap();
+        assert!(!snapshot.contains("XmlDom"));
+        let loaded = UITree::from_snapshot_str(&snapshot).unwrap();
+        assert_eq!(loaded.get_xml_dom_tree(), tree.get_xml_dom_tree());
+    }
+
+    #[test]
+    fn test_snapshot_keeps_synthetic_ids() {
+        let mut backend = build_fake_desktop();
+        backend.add_child(0, SaveUIElement::default().with_name("No ID 1"));
+        backend.add_child(0, SaveUIElement::default().with_name("No ID 2"));
+        let tree = walk(&backend);
+        let loaded = UITree::from_snapshot_str(&tree.to_snapshot_string().unwrap()).unwrap();
+        assert_same_tree(&tree, &loaded);
+
+        let second = loaded.get_element_by_xpath("//*[@Name='No ID 2']").unwrap();
+        assert_eq!(second.get_name(), "No ID 2");
+    }
+
+    #[test]
+    fn test_snapshot_rejects_malformed_input() {
+        assert!(UITree::from_snapshot_str("").is_err());
+        assert!(UITree::from_snapshot_str("<Window Name=\"x\"/>").is_err());
+        let dangling = r#"<UITreeSnapshot Version="1" TreeView="control" XmlAttributes=""><TreeMap>
+            <Node Index="0" Parent="0" Alive="true" Name="r" RtID="1" Children="7"/>
+            </TreeMap><Elements/></UITreeSnapshot>"#;
+        assert_rejected(dangling, "outside the tree");
+        let no_view = dangling.replacen(" TreeView=\"control\"", "", 1);
+        assert_rejected(&no_view, "missing attribute 'TreeView'");
+    }
+
+    fn assert_rejected(snapshot: &str, reason: &str) {
+        match UITree::from_snapshot_str(snapshot) {
+            Err(UITreeError::Snapshot(msg)) => assert!(msg.contains(reason), "{}", msg),
+            other => panic!("expected a snapshot error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_snapshot_rejects_nodes_without_elements() {
+        let tree = walk(&build_fake_desktop());
+        let snapshot = tree.to_snapshot_string().unwrap();
+        let start = snapshot.find("<Elements>").unwrap();
+        let end = snapshot.find("</Elements>").unwrap() + "</Elements>".len();
+        let empty = format!("{}<Elements/>{}", &snapshot[..start], &snapshot[end..]);
+        assert_rejected(&empty, "has no element");
+
+        // the same element listed twice
+        let edit = snapshot
+            .lines()
+            .find(|line| line.contains("<Element ") && line.contains("RtID=\"42-4\""))
+            .unwrap();
+        let doubled = snapshot.replacen("</Elements>", &format!("{}</Elements>", edit), 1);
+        assert_rejected(&doubled, "more than one element");
+    }
+
+    #[test]
+    fn test_snapshot_rejects_inconsistent_links() {
+        let wrong_parent = r#"<UITreeSnapshot Version="1" TreeView="control" XmlAttributes=""><TreeMap>
+            <Node Index="0" Parent="0" Alive="true" Name="r" RtID="1" Children=""/>
+            <Node Index="1" Parent="0" Alive="true" Name="a" RtID="2" Children=""/>
+            </TreeMap><Elements/></UITreeSnapshot>"#;
+        assert_rejected(wrong_parent, "not a child of its parent");
+        let wrong_child = wrong_parent.replacen("Children=\"\"", "Children=\"1\"", 1);
+        let wrong_child = wrong_child.replacen(
+            "Parent=\"0\" Alive=\"true\" Name=\"a\"",
+            "Parent=\"1\" Alive=\"true\" Name=\"a\"",
+            1,
+        );
+        assert_rejected(&wrong_child, "has parent 1");
+    }
+
+    #[test]
+    fn test_snapshot_rejects_children_cycle() {
+        let element = |index: usize, rtid: &str| {
+            format!(
+                r#"<Element TreeIndex="{}" RtID="{}" Name="" ClassName="" ControlType="Pane"
+                LocalizedControlType="" FrameworkId="" RuntimeId="" AutomationId="" Handle="0"
+                BoundingRectangle="0,0,0,0" Level="0" ZOrder="0" IsEnabled="true"
+                IsOffscreen="false" HasKeyboardFocus="false" IsKeyboardFocusable="false"
+                HelpText="" AcceleratorKey="" AccessKey="" ProcessId="0"
+                SupportedPatterns=""/>"#,
+                index, rtid
+            )
+        };
+        let cycle = format!(
+            r#"<UITreeSnapshot Version="1" TreeView="control" XmlAttributes=""><TreeMap>
+            <Node Index="0" Parent="0" Alive="true" Name="r" RtID="1" Children="1"/>
+            <Node Index="1" Parent="2" Alive="true" Name="a" RtID="2" Children="2"/>
+            <Node Index="2" Parent="1" Alive="true" Name="b" RtID="3" Children="1"/>
+            </TreeMap><Elements>{}{}{}</Elements></UITreeSnapshot>"#,
+            element(0, "1"),
+            element(1, "2"),
+            element(2, "3")
+        );
+        assert_rejected(&cycle, "has parent 2");
+
+        let self_loop = format!(
+            r#"<UITreeSnapshot Version="1" TreeView="control" XmlAttributes=""><TreeMap>
+            <Node Index="0" Parent="0" Alive="true" Name="r" RtID="1" Children="1 0"/>
+            <Node Index="1" Parent="0" Alive="true" Name="a" RtID="2" Children="2"/>
+            <Node Index="2" Parent="1" Alive="true" Name="b" RtID="3" Children=""/>
+            </TreeMap><Elements>{}{}{}</Elements></UITreeSnapshot>"#,
+            element(0, "1"),
+            element(1, "2"),
+            element(2, "3")
+        );
+        assert_rejected(&self_loop, "reached twice");
+    }
+}
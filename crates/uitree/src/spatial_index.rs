@@ -0,0 +1,532 @@
+//! Spatial index over the bounding rectangles of a `UITree`'s elements.
+//!
+//! The index is a static R-tree bulk-loaded with the Sort-Tile-Recursive
+//! algorithm. It is built on the first spatial query and rebuilt whenever the
+//! elements of the tree change.
+
+use crate::common_types::UIElementInTree;
+use crate::rect::Rect;
+use crate::uiexplore_xml::UITree;
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Maximum number of children of an R-tree node.
+const NODE_CAPACITY: usize = 16;
+
+/// Screen rectangle with inclusive edges, matching the hit-testing of
+/// `is_inside_rectangle`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl Bounds {
+    pub fn new(left: i32, top: i32, right: i32, bottom: i32) -> Self {
+        Bounds {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    fn from_rect(rect: &Rect) -> Self {
+        Bounds::new(
+            rect.get_left(),
+            rect.get_top(),
+            rect.get_right(),
+            rect.get_bottom(),
+        )
+    }
+
+    pub fn contains_point(&self, x: i32, y: i32) -> bool {
+        x >= self.left && x <= self.right && y >= self.top && y <= self.bottom
+    }
+
+    pub fn contains(&self, other: &Bounds) -> bool {
+        other.left >= self.left
+            && other.right <= self.right
+            && other.top >= self.top
+            && other.bottom <= self.bottom
+    }
+
+    pub fn intersects(&self, other: &Bounds) -> bool {
+        self.left <= other.right
+            && other.left <= self.right
+            && self.top <= other.bottom
+            && other.top <= self.bottom
+    }
+
+    pub fn area(&self) -> i64 {
+        (self.right as i64 - self.left as i64) * (self.bottom as i64 - self.top as i64)
+    }
+
+    fn union(&self, other: &Bounds) -> Bounds {
+        Bounds::new(
+            self.left.min(other.left),
+            self.top.min(other.top),
+            self.right.max(other.right),
+            self.bottom.max(other.bottom),
+        )
+    }
+
+    /// Squared distance from the point to the rectangle (0 when inside).
+    fn distance_sq(&self, x: i32, y: i32) -> i64 {
+        let dx = (self.left as i64 - x as i64)
+            .max(x as i64 - self.right as i64)
+            .max(0);
+        let dy = (self.top as i64 - y as i64)
+            .max(y as i64 - self.bottom as i64)
+            .max(0);
+        dx * dx + dy * dy
+    }
+
+    fn center(&self) -> (i64, i64) {
+        (
+            self.left as i64 + self.right as i64,
+            self.top as i64 + self.bottom as i64,
+        )
+    }
+}
+
+/// How [`UITree::elements_in_region`] matches element rectangles against the region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionMatch {
+    /// The element rectangle lies completely inside the region.
+    Inside,
+    /// The element rectangle overlaps the region.
+    Intersecting,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    bounds: Bounds,
+    /// Position of the element in `UITree::get_elements`.
+    position: usize,
+    z_order: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    bounds: Bounds,
+    /// Index of the first child in `entries` (leaf) or `nodes` (inner node).
+    first: usize,
+    len: usize,
+    leaf: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SpatialIndex {
+    entries: Vec<Entry>,
+    nodes: Vec<Node>,
+    root: Option<usize>,
+}
+
+impl SpatialIndex {
+    /// Indexes all elements except the desktop root (level 0), whose rectangle
+    /// covers the whole screen, and elements without a valid rectangle.
+    pub(crate) fn build(elements: &[UIElementInTree]) -> Self {
+        let mut entries: Vec<Entry> = elements
+            .iter()
+            .enumerate()
+            .filter_map(|(position, element)| {
+                let props = element.get_element_props();
+                let bounds = Bounds::from_rect(props.get_bounding_rectangle());
+                (props.get_level() != 0
+                    && bounds.left <= bounds.right
+                    && bounds.top <= bounds.bottom)
+                    .then_some(Entry {
+                        bounds,
+                        position,
+                        z_order: props.get_z_order(),
+                    })
+            })
+            .collect();
+
+        sort_tiles(&mut entries, |e| e.bounds);
+        let mut level: Vec<Node> = entries
+            .chunks(NODE_CAPACITY)
+            .enumerate()
+            .map(|(i, chunk)| Node {
+                bounds: union_all(chunk.iter().map(|e| e.bounds)),
+                first: i * NODE_CAPACITY,
+                len: chunk.len(),
+                leaf: true,
+            })
+            .collect();
+
+        let mut nodes = Vec::new();
+        while level.len() > 1 {
+            sort_tiles(&mut level, |n| n.bounds);
+            let base = nodes.len();
+            nodes.append(&mut level);
+            level = nodes[base..]
+                .chunks(NODE_CAPACITY)
+                .enumerate()
+                .map(|(i, chunk)| Node {
+                    bounds: union_all(chunk.iter().map(|n| n.bounds)),
+                    first: base + i * NODE_CAPACITY,
+                    len: chunk.len(),
+                    leaf: false,
+                })
+                .collect();
+        }
+        let root = level.pop().map(|node| {
+            nodes.push(node);
+            nodes.len() - 1
+        });
+
+        SpatialIndex {
+            entries,
+            nodes,
+            root,
+        }
+    }
+
+    /// Positions of all entries accepted by `keep`, descending only into nodes
+    /// accepted by `visit`.
+    fn search(
+        &self,
+        visit: impl Fn(&Bounds) -> bool,
+        keep: impl Fn(&Entry) -> bool,
+    ) -> Vec<&Entry> {
+        let mut found = Vec::new();
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !visit(&node.bounds) {
+                continue;
+            }
+            if node.leaf {
+                found.extend(
+                    self.entries[node.first..node.first + node.len]
+                        .iter()
+                        .filter(|e| keep(e)),
+                );
+            } else {
+                stack.extend(node.first..node.first + node.len);
+            }
+        }
+        found
+    }
+
+    /// Entries whose rectangle contains the point.
+    fn at_point(&self, x: i32, y: i32, z_order: Option<usize>) -> Vec<&Entry> {
+        self.search(
+            |b| b.contains_point(x, y),
+            |e| e.bounds.contains_point(x, y) && z_order.is_none_or(|z| e.z_order == z),
+        )
+    }
+
+    fn in_region(&self, region: &Bounds, mode: RegionMatch, z_order: Option<usize>) -> Vec<&Entry> {
+        self.search(
+            |b| b.intersects(region),
+            |e| {
+                z_order.is_none_or(|z| e.z_order == z)
+                    && match mode {
+                        RegionMatch::Inside => region.contains(&e.bounds),
+                        RegionMatch::Intersecting => region.intersects(&e.bounds),
+                    }
+            },
+        )
+    }
+
+    /// Best-first search for the entry closest to the point; ties are broken
+    /// by the smaller area, then by the element position.
+    fn nearest(&self, x: i32, y: i32, z_order: Option<usize>) -> Option<&Entry> {
+        // (distance, is_entry, area, position, index): nodes sort before entries
+        // at the same distance, so every equally close entry is seen in order
+        let mut heap = BinaryHeap::new();
+        if let Some(root) = self.root {
+            heap.push(Reverse((
+                self.nodes[root].bounds.distance_sq(x, y),
+                false,
+                0,
+                0,
+                root,
+            )));
+        }
+        while let Some(Reverse((_, is_entry, _, _, index))) = heap.pop() {
+            if is_entry {
+                return Some(&self.entries[index]);
+            }
+            let node = &self.nodes[index];
+            for child in node.first..node.first + node.len {
+                if node.leaf {
+                    let entry = &self.entries[child];
+                    if z_order.is_none_or(|z| entry.z_order == z) {
+                        heap.push(Reverse((
+                            entry.bounds.distance_sq(x, y),
+                            true,
+                            entry.bounds.area(),
+                            entry.position,
+                            child,
+                        )));
+                    }
+                } else {
+                    heap.push(Reverse((
+                        self.nodes[child].bounds.distance_sq(x, y),
+                        false,
+                        0,
+                        0,
+                        child,
+                    )));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Orders `items` into Sort-Tile-Recursive order: vertical slices sorted by
+/// x-center, each slice sorted by y-center.
+fn sort_tiles<T>(items: &mut [T], bounds: impl Fn(&T) -> Bounds) {
+    let node_count = items.len().div_ceil(NODE_CAPACITY);
+    let slice_count = (node_count as f64).sqrt().ceil().max(1.0) as usize;
+    let slice_len = slice_count * NODE_CAPACITY;
+    items.sort_by_key(|item| bounds(item).center().0);
+    for slice in items.chunks_mut(slice_len) {
+        slice.sort_by_key(|item| bounds(item).center().1);
+    }
+}
+
+fn union_all(mut bounds: impl Iterator<Item = Bounds>) -> Bounds {
+    let first = bounds.next().unwrap_or(Bounds::new(0, 0, 0, 0));
+    bounds.fold(first, |acc, b| acc.union(&b))
+}
+
+impl UITree {
+    /// The innermost element at the given screen point: the smallest rectangle
+    /// containing it. With `z_order` set, only elements of that top-level
+    /// window are considered. The desktop root is never returned.
+    pub fn element_at(&self, x: i32, y: i32, z_order: Option<usize>) -> Option<&UIElementInTree> {
+        self.spatial_index()
+            .at_point(x, y, z_order)
+            .into_iter()
+            .min_by_key(|e| (e.bounds.area(), e.position))
+            .map(|e| &self.get_elements()[e.position])
+    }
+
+    /// Like [`UITree::element_at`], restricted to the front-most top-level
+    /// window (lowest z-order) containing the point.
+    pub fn topmost_element_at(&self, x: i32, y: i32) -> Option<&UIElementInTree> {
+        self.spatial_index()
+            .at_point(x, y, None)
+            .into_iter()
+            .min_by_key(|e| (e.z_order, e.bounds.area(), e.position))
+            .map(|e| &self.get_elements()[e.position])
+    }
+
+    /// All elements inside or intersecting `region`, ordered like
+    /// [`UITree::get_elements`] (by z-order, then by area).
+    pub fn elements_in_region(
+        &self,
+        region: Bounds,
+        mode: RegionMatch,
+        z_order: Option<usize>,
+    ) -> Vec<&UIElementInTree> {
+        let mut positions: Vec<usize> = self
+            .spatial_index()
+            .in_region(&region, mode, z_order)
+            .into_iter()
+            .map(|e| e.position)
+            .collect();
+        positions.sort_unstable();
+        positions
+            .into_iter()
+            .map(|p| &self.get_elements()[p])
+            .collect()
+    }
+
+    /// The element whose rectangle is closest to the point (distance 0 when
+    /// the point is inside); among equally close elements the smallest wins.
+    pub fn nearest_element(
+        &self,
+        x: i32,
+        y: i32,
+        z_order: Option<usize>,
+    ) -> Option<&UIElementInTree> {
+        self.spatial_index()
+            .nearest(x, y, z_order)
+            .map(|e| &self.get_elements()[e.position])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save_ui_element::SaveUIElement;
+    use crate::tree_map::UITreeMap;
+
+    /// Deterministic pseudo-random element layout with nested rectangles.
+    fn random_tree(count: usize) -> UITree {
+        let mut seed: u64 = 42;
+        let mut next = |max: i32| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((seed >> 33) % max as u64) as i32
+        };
+        let mut tree = UITreeMap::new("root".to_string(), "0".to_string(), ());
+        let mut elements = vec![UIElementInTree::new(
+            SaveUIElement::default().with_bounding_rectangle(Rect::new(0, 0, 2000, 2000)),
+            0,
+        )];
+        for i in 1..count {
+            let (left, top) = (next(1900), next(1900));
+            let (width, height) = (next(300), next(300));
+            let mut props = SaveUIElement::default()
+                .with_runtime_id(vec![42, i as i32])
+                .with_bounding_rectangle(Rect::new(left, top, left + width, top + height));
+            props.set_position(1 + next(5) as usize, next(3) as usize);
+            let index = tree.add_child(0, "e", &format!("42-{}", i), ());
+            elements.push(UIElementInTree::new(props, index));
+        }
+        crate::walker_common::sort_elements(&mut elements);
+        UITree::new(tree, String::new(), elements)
+    }
+
+    /// The linear scan the index replaces.
+    fn brute_force_at(tree: &UITree, x: i32, y: i32, z: Option<usize>) -> Option<usize> {
+        let mut best = None;
+        let mut best_area = i64::MAX;
+        for (position, element) in tree.get_elements().iter().enumerate() {
+            let props = element.get_element_props();
+            let bounds = Bounds::from_rect(props.get_bounding_rectangle());
+            if props.get_level() == 0 || z.is_some_and(|z| props.get_z_order() != z) {
+                continue;
+            }
+            if bounds.contains_point(x, y) && bounds.area() < best_area {
+                best_area = bounds.area();
+                best = Some(position);
+            }
+        }
+        best
+    }
+
+    fn position(tree: &UITree, element: Option<&UIElementInTree>) -> Option<usize> {
+        element.map(|e| {
+            tree.get_elements()
+                .iter()
+                .position(|x| std::ptr::eq(x, e))
+                .unwrap()
+        })
+    }
+
+    #[test]
+    fn test_point_queries_match_linear_scan() {
+        let tree = random_tree(3000);
+        for (x, y) in [
+            (0, 0),
+            (10, 10),
+            (999, 500),
+            (1500, 1700),
+            (1999, 1999),
+            (2500, 10),
+        ] {
+            for z in [None, Some(0), Some(2)] {
+                assert_eq!(
+                    position(&tree, tree.element_at(x, y, z)),
+                    brute_force_at(&tree, x, y, z),
+                    "point ({}, {}) z {:?}",
+                    x,
+                    y,
+                    z
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_topmost_element_prefers_lowest_z_order() {
+        let tree = random_tree(3000);
+        let hit = tree.topmost_element_at(999, 500).unwrap();
+        let z = hit.get_element_props().get_z_order();
+        let any_lower = tree.get_elements().iter().any(|e| {
+            let p = e.get_element_props();
+            p.get_level() != 0
+                && p.get_z_order() < z
+                && Bounds::from_rect(p.get_bounding_rectangle()).contains_point(999, 500)
+        });
+        assert!(!any_lower);
+        assert_eq!(
+            position(&tree, Some(hit)),
+            brute_force_at(&tree, 999, 500, Some(z))
+        );
+    }
+
+    #[test]
+    fn test_region_queries_match_linear_scan() {
+        let tree = random_tree(2000);
+        let region = Bounds::new(300, 300, 900, 700);
+        for mode in [RegionMatch::Inside, RegionMatch::Intersecting] {
+            let expected: Vec<usize> = tree
+                .get_elements()
+                .iter()
+                .enumerate()
+                .filter(|(_, e)| {
+                    let props = e.get_element_props();
+                    let b = Bounds::from_rect(props.get_bounding_rectangle());
+                    props.get_level() != 0
+                        && match mode {
+                            RegionMatch::Inside => region.contains(&b),
+                            RegionMatch::Intersecting => region.intersects(&b),
+                        }
+                })
+                .map(|(p, _)| p)
+                .collect();
+            let found: Vec<usize> = tree
+                .elements_in_region(region, mode, None)
+                .into_iter()
+                .map(|e| position(&tree, Some(e)).unwrap())
+                .collect();
+            assert!(!expected.is_empty());
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn test_nearest_element_matches_linear_scan() {
+        let tree = random_tree(2000);
+        for (x, y) in [(2500, 2500), (-100, 40), (1000, 1000)] {
+            let expected = tree
+                .get_elements()
+                .iter()
+                .enumerate()
+                .filter(|(_, e)| e.get_element_props().get_level() != 0)
+                .min_by_key(|(p, e)| {
+                    let b = Bounds::from_rect(e.get_element_props().get_bounding_rectangle());
+                    (b.distance_sq(x, y), b.area(), *p)
+                })
+                .map(|(p, _)| p);
+            assert_eq!(position(&tree, tree.nearest_element(x, y, None)), expected);
+        }
+    }
+
+    #[test]
+    fn test_index_rebuilt_after_elements_change() {
+        let mut tree = random_tree(100);
+        assert!(tree.element_at(5000, 5000, None).is_none());
+        let mut props = SaveUIElement::default()
+            .with_runtime_id(vec![42, 999])
+            .with_bounding_rectangle(Rect::new(4990, 4990, 5010, 5010));
+        props.set_position(1, 0);
+        tree.get_elements_mut().push(UIElementInTree::new(props, 0));
+        assert!(tree.element_at(5000, 5000, None).is_some());
+    }
+
+    #[test]
+    fn test_empty_tree_has_no_hits() {
+        let tree = UITree::empty();
+        assert!(tree.element_at(0, 0, None).is_none());
+        assert!(tree.nearest_element(0, 0, None).is_none());
+        assert!(
+            tree.elements_in_region(Bounds::new(0, 0, 10, 10), RegionMatch::Intersecting, None)
+                .is_empty()
+        );
+    }
+}
@@ -1,6 +1,7 @@
 //! A generic tree structure with fast key-value lookup.
 
 use crate::UIHashMap;
+use std::sync::Arc;
 
 #[derive(Debug, thiserror::Error)]
 pub enum TreeMapError {
@@ -8,11 +9,12 @@ pub enum TreeMapError {
     InvalidIndex(usize, usize),
 }
 
-// A generic node in a UITreeMap
+// A generic node in a UITreeMap. Name and runtime ID are shared with the
+// lookup maps of the tree, see `get_name` and `get_runtime_id`.
 #[derive(Debug, Clone)]
 pub struct UITreeNode<T> {
-    pub name: String,
-    pub runtime_id: String,
+    pub(crate) name: Arc<str>,
+    pub(crate) runtime_id: Arc<str>,
     pub index: usize,
     pub parent: usize,
     pub children: Vec<usize>,
@@ -25,8 +27,8 @@ pub struct UITreeNode<T> {
 impl<T: Default> Default for UITreeNode<T> {
     fn default() -> Self {
         Self {
-            name: String::new(),
-            runtime_id: String::new(),
+            name: Arc::from(""),
+            runtime_id: Arc::from(""),
             index: 0,
             parent: 0,
             children: Vec::new(),
@@ -39,8 +41,8 @@ impl<T: Default> Default for UITreeNode<T> {
 impl<T: Default> UITreeNode<T> {
     pub fn new(data: T) -> Self {
         Self {
-            name: String::new(),
-            runtime_id: String::new(),
+            name: Arc::from(""),
+            runtime_id: Arc::from(""),
             index: 0,
             parent: 0,
             children: Vec::new(),
@@ -50,18 +52,32 @@ impl<T: Default> UITreeNode<T> {
     }
 }
 
+impl<T> UITreeNode<T> {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Key of the node in the tree (`RtID`), synthetic for elements without a
+    /// runtime ID.
+    pub fn get_runtime_id(&self) -> &str {
+        &self.runtime_id
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct UITreeMap<T> {
     nodes: Vec<UITreeNode<T>>,
-    name_to_index: UIHashMap<String, Vec<usize>>, // Name-to-indices map (names are not unique)
-    rtid_to_index: UIHashMap<String, usize>,
+    name_to_index: UIHashMap<Arc<str>, Vec<usize>>, // Name-to-indices map (names are not unique)
+    rtid_to_index: UIHashMap<Arc<str>, usize>,
 }
 
 impl<T> UITreeMap<T> {
     pub fn new(root_name: String, rt_id: String, root_data: T) -> Self {
+        let root_name: Arc<str> = root_name.into();
+        let rt_id: Arc<str> = rt_id.into();
         let root = UITreeNode {
-            name: root_name.clone(),
-            runtime_id: rt_id.clone(),
+            name: Arc::clone(&root_name),
+            runtime_id: Arc::clone(&rt_id),
             index: 0,
             parent: 0,
             children: Vec::new(),
@@ -69,7 +85,7 @@ impl<T> UITreeMap<T> {
             is_alive: true,
         };
 
-        let mut name_to_index: UIHashMap<String, Vec<usize>> = UIHashMap::default();
+        let mut name_to_index: UIHashMap<Arc<str>, Vec<usize>> = UIHashMap::default();
         let mut rtid_to_index = UIHashMap::default();
 
         name_to_index.entry(root_name).or_default().push(0);
@@ -82,6 +98,29 @@ impl<T> UITreeMap<T> {
         }
     }
 
+    /// Rebuilds a tree from a previously exported node arena (see [`UITreeMap::nodes`]),
+    /// including tombstones. The lookup maps are recreated from the alive nodes.
+    pub fn from_nodes(nodes: Vec<UITreeNode<T>>) -> Self {
+        let mut name_to_index: UIHashMap<Arc<str>, Vec<usize>> = UIHashMap::default();
+        let mut rtid_to_index = UIHashMap::default();
+        for (index, node) in nodes.iter().enumerate() {
+            if !node.is_alive {
+                continue;
+            }
+            name_to_index
+                .entry(Arc::clone(&node.name))
+                .or_default()
+                .push(index);
+            rtid_to_index.insert(Arc::clone(&node.runtime_id), index);
+        }
+
+        Self {
+            nodes,
+            name_to_index,
+            rtid_to_index,
+        }
+    }
+
     pub fn root(&self) -> usize {
         0 // Root is always index 0
     }
@@ -113,8 +152,8 @@ impl<T> UITreeMap<T> {
     pub fn add_child(&mut self, parent: usize, name: &str, rt_id: &str, data: T) -> usize {
         let index = self.nodes.len();
         let node = UITreeNode {
-            name: name.to_string(),
-            runtime_id: rt_id.to_string(),
+            name: Arc::from(name),
+            runtime_id: Arc::from(rt_id),
             index,
             parent,
             children: Vec::with_capacity(15),
@@ -123,10 +162,11 @@ impl<T> UITreeMap<T> {
         };
 
         self.name_to_index
-            .entry(name.to_string())
+            .entry(Arc::clone(&node.name))
             .or_default()
             .push(index);
-        self.rtid_to_index.insert(rt_id.to_string(), index);
+        self.rtid_to_index
+            .insert(Arc::clone(&node.runtime_id), index);
         self.nodes[parent].children.push(index);
         self.nodes.push(node);
         index
@@ -147,15 +187,15 @@ impl<T> UITreeMap<T> {
 
         // Remove from hash maps
         let name = &self.nodes[index].name;
-        if let Some(indices) = self.name_to_index.get_mut(name) {
+        if let Some(indices) = self.name_to_index.get_mut(&**name) {
             indices.retain(|&i| i != index);
             if indices.is_empty() {
-                self.name_to_index.remove(name);
+                self.name_to_index.remove(&**name);
             }
         }
         let _rtid_to_index_removal = self
             .rtid_to_index
-            .remove_entry(&self.nodes[index].runtime_id);
+            .remove_entry(&*self.nodes[index].runtime_id);
 
         // Remove from parent's children
         let parent_index = self.nodes[index].parent;
@@ -183,6 +223,36 @@ impl<T> UITreeMap<T> {
         Ok(())
     }
 
+    /// Replaces the subtree at `index` with a new, childless node at the same
+    /// position below the same parent and returns the index of the new node.
+    pub fn replace_node(
+        &mut self,
+        index: usize,
+        name: &str,
+        rt_id: &str,
+        data: T,
+    ) -> Result<usize, TreeMapError>
+    where
+        T: Default,
+    {
+        if !self.has_node(index) {
+            return Err(TreeMapError::InvalidIndex(index, self.nodes.len()));
+        }
+        let parent = self.nodes[index].parent;
+        let position = self.nodes[parent]
+            .children
+            .iter()
+            .position(|&x| x == index)
+            .unwrap_or(self.nodes[parent].children.len());
+        self.remove_node(index)?;
+
+        let new_index = self.add_child(parent, name, rt_id, data);
+        let children = &mut self.nodes[parent].children;
+        children.pop();
+        children.insert(position.min(children.len()), new_index);
+        Ok(new_index)
+    }
+
     pub fn get_path_to_element(&self, index: usize) -> Vec<usize> {
         let mut path = Vec::new();
         let mut current_index = index;
@@ -333,8 +403,8 @@ mod tests {
         let tree = UITreeMap::new("Root".into(), "rt-root".into(), "data".to_string());
         assert_eq!(tree.root(), 0);
         assert_eq!(tree.node_count(), 1);
-        assert_eq!(tree.node(0).name, "Root");
-        assert_eq!(tree.node(0).runtime_id, "rt-root");
+        assert_eq!(tree.node(0).get_name(), "Root");
+        assert_eq!(tree.node(0).get_runtime_id(), "rt-root");
     }
 
     #[test]
@@ -343,7 +413,7 @@ mod tests {
         let idx = tree.add_child(0, "Child", "rt-child", "child-data".into());
         assert_eq!(idx, 1);
         assert_eq!(tree.node_count(), 2);
-        assert_eq!(tree.node(idx).name, "Child");
+        assert_eq!(tree.node(idx).get_name(), "Child");
         assert_eq!(tree.node(idx).parent, 0);
         assert!(tree.children(0).contains(&idx));
     }
@@ -371,7 +441,7 @@ mod tests {
         let tree = sample_tree();
         let node = tree.get_element_by_runtime_id("rt-gc1").unwrap();
         assert_eq!(node.index, 3);
-        assert_eq!(node.name, "GrandChild1");
+        assert_eq!(&*node.name, "GrandChild1");
         assert!(tree.get_element_by_runtime_id("rt-missing").is_none());
     }
 
@@ -408,6 +478,37 @@ mod tests {
         assert!(tree.get_element_by_runtime_id("rt-gc1").is_none());
     }
 
+    #[test]
+    fn test_replace_node_keeps_position() {
+        let mut tree = sample_tree();
+        let new = tree
+            .replace_node(1, "Child1b", "rt-c1b", "c1b-data".into())
+            .unwrap();
+        assert_eq!(tree.children(0), &[new, 2]);
+        assert!(tree.children(new).is_empty());
+        assert!(tree.get_element_by_name("GrandChild1").is_none());
+        assert_eq!(tree.get_element_by_runtime_id("rt-c1b").unwrap().index, new);
+        assert!(
+            tree.replace_node(0, "Root2", "rt-root2", String::new())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_from_nodes_rebuilds_lookups() {
+        let mut tree = sample_tree();
+        tree.remove_node(1).unwrap();
+        let rebuilt = UITreeMap::from_nodes(tree.nodes().to_vec());
+        assert_eq!(rebuilt.node_count(), tree.node_count());
+        assert!(!rebuilt.has_node(1));
+        assert!(rebuilt.get_element_by_name("Child1").is_none());
+        assert_eq!(
+            rebuilt.get_element_by_runtime_id("rt-c2").map(|n| n.index),
+            tree.get_element_by_runtime_id("rt-c2").map(|n| n.index)
+        );
+        assert_eq!(rebuilt.children(0), tree.children(0));
+    }
+
     #[test]
     fn test_remove_root_fails() {
         let mut tree = sample_tree();
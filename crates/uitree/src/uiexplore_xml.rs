@@ -1,45 +1,69 @@
-use crate::common_types::UIElementInTree;
+#[cfg(windows)]
+use crate::backend::UIABackend;
+use crate::backend::UIBackend;
+use crate::common_types::{IncompleteReason, IncompleteWindow, UIElementInTree};
 use crate::error::UITreeError;
+use crate::interner::StringInterner;
+use crate::progress::ProgressTracker;
 
 use crate::save_ui_element::SaveUIElement;
-use crate::walker_common::{self, MAX_SIBLINGS};
-use bromium_common::{format_runtime_id, get_ui_automation_instance};
+use crate::spatial_index::SpatialIndex;
+#[cfg(windows)]
+use crate::walk_options::WindowScope;
+use crate::walk_options::{TreeView, WalkOptions, XmlAttribute};
+use crate::walker_common::{self, MAX_SIBLINGS, RtIdAllocator};
 
 use crate::UITreeMap;
-use xmlutil::XpathQueryResult;
-use xmlutil::xpath_eval::{XpathDocCache, eval_xpath, eval_xpath_on_cache};
-use xmlutil::xpath_gen::get_xpath_full_from_runtime_id;
+use xmlutil::xpath_dom::{XpathDom, XpathDomBuilder};
+use xmlutil::xpath_eval::XpathVariables;
+use xmlutil::xpath_gen::{
+    XpathCandidate, get_xpath_candidates_from_runtime_id, get_xpath_full_from_runtime_id,
+};
 
 use quick_xml::Writer;
 use quick_xml::events::{BytesEnd, BytesStart, Event};
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::collections::{HashSet, VecDeque};
 use std::io::Cursor;
 use std::sync::Arc;
-use std::sync::Mutex;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{Sender, channel};
-
-use uiautomation::{UIElement, UITreeWalker};
+use std::sync::mpsc::{RecvTimeoutError, Sender, channel};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 use log::{debug, error, info, trace, warn};
 
+/// The bulk data is shared between clones and copied on the first mutation,
+/// so cloning a tree is cheap.
+///
+/// XPath queries run against a document built from the tree map and the
+/// elements, not against the XML string; after a merge the XML string is
+/// rendered from the tree again on first use.
 #[derive(Debug)]
 pub struct UITree {
-    tree: UITreeMap<()>,
-    xml_dom_tree: String,
-    ui_elements: Vec<UIElementInTree>,
-    node_to_elem: Vec<usize>,
-    xpath_cache: Mutex<Option<XpathDocCache>>,
+    tree: Arc<UITreeMap<()>>,
+    xml_dom_tree: Arc<OnceLock<String>>,
+    ui_elements: Arc<Vec<UIElementInTree>>,
+    node_to_elem: Arc<Vec<usize>>,
+    incomplete_windows: Vec<IncompleteWindow>,
+    xml_attributes: Vec<XmlAttribute>,
+    tree_view: TreeView,
+    xpath_cache: Mutex<Option<XpathDom>>,
+    spatial_index: Arc<OnceLock<SpatialIndex>>,
 }
 
 impl Clone for UITree {
     fn clone(&self) -> Self {
         UITree {
-            tree: self.tree.clone(),
-            xml_dom_tree: self.xml_dom_tree.clone(),
-            ui_elements: self.ui_elements.clone(),
-            node_to_elem: self.node_to_elem.clone(),
+            tree: Arc::clone(&self.tree),
+            xml_dom_tree: Arc::clone(&self.xml_dom_tree),
+            ui_elements: Arc::clone(&self.ui_elements),
+            node_to_elem: Arc::clone(&self.node_to_elem),
+            incomplete_windows: self.incomplete_windows.clone(),
+            xml_attributes: self.xml_attributes.clone(),
+            tree_view: self.tree_view,
             xpath_cache: Mutex::new(None),
+            spatial_index: Arc::clone(&self.spatial_index),
         }
     }
 }
@@ -47,64 +71,92 @@ impl Clone for UITree {
 impl UITree {
     pub fn empty() -> Self {
         UITree {
-            tree: UITreeMap::new("Root".to_string(), String::new(), ()),
-            xml_dom_tree: String::new(),
-            ui_elements: Vec::new(),
-            node_to_elem: Vec::new(),
+            tree: Arc::new(UITreeMap::new("Root".to_string(), String::new(), ())),
+            xml_dom_tree: Arc::new(OnceLock::from(String::new())),
+            ui_elements: Arc::default(),
+            node_to_elem: Arc::default(),
+            incomplete_windows: Vec::new(),
+            xml_attributes: XmlAttribute::DEFAULT.to_vec(),
+            tree_view: TreeView::default(),
             xpath_cache: Mutex::new(None),
+            spatial_index: Arc::default(),
         }
     }
 
     pub fn new(
         tree: UITreeMap<()>,
         xml_dom_tree: String,
-        ui_elements: Vec<UIElementInTree>,
+        mut ui_elements: Vec<UIElementInTree>,
     ) -> Self {
-        let node_to_elem = Self::build_node_to_elem(&tree, &ui_elements);
+        let node_to_elem = Self::build_node_to_elem(&tree, &mut ui_elements);
         UITree {
-            tree,
-            xml_dom_tree,
-            ui_elements,
-            node_to_elem,
+            tree: Arc::new(tree),
+            xml_dom_tree: Arc::new(OnceLock::from(xml_dom_tree)),
+            ui_elements: Arc::new(ui_elements),
+            node_to_elem: Arc::new(node_to_elem),
+            incomplete_windows: Vec::new(),
+            xml_attributes: XmlAttribute::DEFAULT.to_vec(),
+            tree_view: TreeView::default(),
             xpath_cache: Mutex::new(None),
+            spatial_index: Arc::default(),
         }
     }
 
-    fn build_node_to_elem(tree: &UITreeMap<()>, elements: &[UIElementInTree]) -> Vec<usize> {
-        // Build a map from runtime-ID → element position.
-        // Skip empty runtime IDs to avoid collisions (CF-28): elements with
-        // empty IDs are resolved by tree-index match below.
-        let mut rtid_to_pos: crate::UIHashMap<String, usize> = crate::UIHashMap::default();
+    /// Like [`UITree::new`], with the XML string rendered from the tree map on
+    /// first use.
+    pub(crate) fn from_tree_map(tree: UITreeMap<()>, ui_elements: Vec<UIElementInTree>) -> Self {
+        let mut ui_tree = Self::new(tree, String::new(), ui_elements);
+        ui_tree.xml_dom_tree = Arc::default();
+        ui_tree
+    }
+
+    /// Maps every alive tree node to its element by `RtID` and points the
+    /// element's tree index at that node; indices of merged subtree elements
+    /// are stale until then.
+    fn build_node_to_elem(tree: &UITreeMap<()>, elements: &mut [UIElementInTree]) -> Vec<usize> {
+        let mut map = vec![0; tree.node_count()];
+        let mut mapped = vec![false; tree.node_count()];
+        let mut unclaimed: crate::UIHashMap<&str, Vec<usize>> = crate::UIHashMap::default();
+        // Elements whose tree index already points at a node with their key
         for (pos, elem) in elements.iter().enumerate() {
-            let rtid = format_runtime_id(elem.get_element_props().get_runtime_id());
-            if !rtid.is_empty() {
-                rtid_to_pos.insert(rtid, pos);
+            let index = elem.get_tree_index();
+            if tree.has_node(index)
+                && !mapped[index]
+                && tree.node(index).is_alive
+                && &*tree.node(index).runtime_id == elem.get_rt_id()
+            {
+                map[index] = pos;
+                mapped[index] = true;
+            } else {
+                unclaimed.entry(elem.get_rt_id()).or_default().push(pos);
             }
         }
-        // Build a secondary map: tree-index → element position for elements
-        // whose tree_index field matches. This handles empty-runtime-ID nodes.
-        let mut idx_to_pos: crate::UIHashMap<usize, usize> = crate::UIHashMap::default();
-        for (pos, elem) in elements.iter().enumerate() {
-            idx_to_pos.insert(elem.get_tree_index(), pos);
-        }
-
-        let mut map = vec![0; tree.node_count()];
+        // The remaining nodes take the remaining elements with the same key
         for (i, slot) in map.iter_mut().enumerate() {
-            // Skip dead (tombstone) nodes — leave their mapping at 0
-            if !tree.node(i).is_alive {
+            // Dead (tombstone) nodes keep their mapping at 0
+            if mapped[i] || !tree.node(i).is_alive {
                 continue;
             }
-            if let Some(&pos) = rtid_to_pos.get(&tree.node(i).runtime_id) {
-                *slot = pos;
-            } else if let Some(&pos) = idx_to_pos.get(&i) {
+            if let Some(pos) = unclaimed
+                .get_mut(&*tree.node(i).runtime_id)
+                .and_then(|positions| positions.pop())
+            {
                 *slot = pos;
+                mapped[i] = true;
+            }
+        }
+
+        for (i, &pos) in map.iter().enumerate() {
+            if mapped[i] {
+                elements[pos].set_tree_index(i);
             }
         }
         map
     }
 
     fn rebuild_node_to_elem(&mut self) {
-        self.node_to_elem = Self::build_node_to_elem(&self.tree, &self.ui_elements);
+        let elements = Arc::make_mut(&mut self.ui_elements);
+        self.node_to_elem = Arc::new(Self::build_node_to_elem(&self.tree, elements));
     }
 
     pub fn get_tree(&self) -> &UITreeMap<()> {
@@ -112,11 +164,48 @@ impl UITree {
     }
 
     pub fn get_tree_mut(&mut self) -> &mut UITreeMap<()> {
-        &mut self.tree
+        self.clear_xpath_cache();
+        Arc::make_mut(&mut self.tree)
+    }
+
+    /// Top-level windows whose subtree is missing or cut short because walking
+    /// them ran past the window budget or failed.
+    pub fn get_incomplete_windows(&self) -> &[IncompleteWindow] {
+        &self.incomplete_windows
+    }
+
+    /// UI Automation view the tree was walked in. Elements found through
+    /// XPaths generated from this tree are only guaranteed to exist in the same view.
+    pub fn get_tree_view(&self) -> TreeView {
+        self.tree_view
+    }
+
+    pub(crate) fn set_tree_view(&mut self, view: TreeView) {
+        self.tree_view = view;
+    }
+
+    /// Optional attributes written to the XML DOM of this tree.
+    pub fn get_xml_attributes(&self) -> &[XmlAttribute] {
+        &self.xml_attributes
+    }
+
+    pub(crate) fn set_xml_attributes(&mut self, attributes: Vec<XmlAttribute>) {
+        self.clear_xpath_cache();
+        self.xml_dom_tree = Arc::default();
+        self.xml_attributes = attributes;
+    }
+
+    pub(crate) fn set_incomplete_windows(&mut self, windows: Vec<IncompleteWindow>) {
+        self.incomplete_windows = windows;
+    }
+
+    /// Returns `true` when every top-level window was walked completely.
+    pub fn is_complete(&self) -> bool {
+        self.incomplete_windows.is_empty()
     }
 
     pub fn get_xml_dom_tree(&self) -> &str {
-        &self.xml_dom_tree
+        self.xml_dom_tree.get_or_init(|| self.render_xml())
     }
 
     pub fn get_elements(&self) -> &[UIElementInTree] {
@@ -124,7 +213,16 @@ impl UITree {
     }
 
     pub fn get_elements_mut(&mut self) -> &mut Vec<UIElementInTree> {
-        &mut self.ui_elements
+        // the caller may move or change elements
+        self.spatial_index = Arc::default();
+        self.clear_xpath_cache();
+        Arc::make_mut(&mut self.ui_elements)
+    }
+
+    /// Spatial index over the element rectangles, built on first use.
+    pub(crate) fn spatial_index(&self) -> &SpatialIndex {
+        self.spatial_index
+            .get_or_init(|| SpatialIndex::build(&self.ui_elements))
     }
 
     pub fn for_each<F>(&self, mut f: F)
@@ -151,17 +249,29 @@ impl UITree {
         (&node.name, self.ui_elements[elem_pos].get_element_props())
     }
 
+    /// The element stored for the tree map node at `index`.
+    pub(crate) fn element_for_node(&self, index: usize) -> &UIElementInTree {
+        &self.ui_elements[self.node_to_elem[index]]
+    }
+
     pub fn pretty_print_tree(&self) {
-        self.debug_tree(self.root(), 0, 0);
+        print!("{}", self.to_pretty_string());
+    }
+
+    /// Renders the tree as indented text, one element per line.
+    pub fn to_pretty_string(&self) -> String {
+        let mut out = String::new();
+        self.debug_tree(&mut out, self.root(), 0, 0);
+        out
     }
 
-    fn debug_tree(&self, index: usize, indent: usize, depth: usize) {
+    fn debug_tree(&self, out: &mut String, index: usize, indent: usize, depth: usize) {
         if depth > self.tree.node_count() {
-            println!(
-                "{}(Max depth exceeded at node {})",
+            out.push_str(&format!(
+                "{}(Max depth exceeded at node {})\n",
                 " ".repeat(indent),
                 index
-            );
+            ));
             return;
         }
 
@@ -173,10 +283,10 @@ impl UITree {
         let prefix = " ".repeat(indent);
         let elem_pos = self.node_to_elem[index];
         let elem = self.ui_elements[elem_pos].get_element_props();
-        println!("{}{}: {}", prefix, &node.name, elem);
+        out.push_str(&format!("{}{}: {}\n", prefix, &node.name, elem));
 
         for &child in &node.children {
-            self.debug_tree(child, indent + 2, depth + 1);
+            self.debug_tree(out, child, indent + 2, depth + 1);
         }
     }
 
@@ -189,109 +299,248 @@ impl UITree {
         get_xpath_full_from_runtime_id(&node.runtime_id, self.get_xml_dom_tree(), simple_path)
     }
 
-    /// Ensures the XPath expression ends with `/@RtID` so the query returns
-    /// runtime-ID attribute values. If the expression already ends with an
-    /// attribute selector (`/@SomeAttr`), it is replaced with `/@RtID`.
-    fn normalize_xpath_for_rtid(xpath: &str) -> String {
-        if xpath.ends_with("/@RtID") {
-            return xpath.to_string();
-        }
-        // Strip any existing trailing /@Attribute before appending /@RtID
-        let base = if let Some(pos) = xpath.rfind("/@") {
-            let after_slash = &xpath[pos..];
-            // Only strip if the trailing segment is a simple attribute name
-            // (no further path separators or predicates)
-            if !after_slash[2..].contains('/') && !after_slash[2..].contains('[') {
-                &xpath[..pos]
+    /// Locator candidates for the element at `index`, best first, each with
+    /// its match count in this tree's XML.
+    pub fn get_xpath_candidates_for_element(
+        &self,
+        index: usize,
+    ) -> Result<Vec<XpathCandidate>, xmlutil::xpath_gen::XpathGenError> {
+        let node = self.tree.node(index);
+        get_xpath_candidates_from_runtime_id(&node.runtime_id, self.get_xml_dom_tree())
+    }
+
+    fn clear_xpath_cache(&mut self) {
+        *self
+            .xpath_cache
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Walks the nodes that make up the XML DOM in document order, calling
+    /// `visit(index, true)` when a node opens and `visit(index, false)` when
+    /// it closes. The level-0 copy of a walk root shares its key with the
+    /// root and is not visited again.
+    fn visit_dom_nodes<E>(
+        &self,
+        index: usize,
+        visit: &mut impl FnMut(usize, bool) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let node = self.tree.node(index);
+        for &child in &node.children {
+            let child_node = self.tree.node(child);
+            if !child_node.is_alive {
+                continue;
+            }
+            if child_node.runtime_id == node.runtime_id {
+                self.visit_dom_nodes(child, visit)?;
             } else {
-                xpath
+                visit(child, true)?;
+                self.visit_dom_nodes(child, visit)?;
+                visit(child, false)?;
             }
-        } else {
-            xpath
+        }
+        Ok(())
+    }
+
+    fn dom_element(&self, index: usize) -> (&str, Vec<(&'static str, Cow<'_, str>)>) {
+        let elem = self.element_for_node(index).get_element_props();
+        let attributes = element_attributes(
+            elem,
+            &self.tree.node(index).runtime_id,
+            elem.get_z_order(),
+            &self.xml_attributes,
+        );
+        (control_type_tag(elem), attributes)
+    }
+
+    /// Builds the XPath document straight from the tree map; every element
+    /// is tagged with the index of its tree map node.
+    fn build_xpath_dom(&self) -> Result<XpathDom, String> {
+        let mut builder = XpathDomBuilder::new();
+        let mut visit = |index: usize, start: bool| {
+            if !start {
+                builder.end_element();
+                return Ok(());
+            }
+            let (tag, attributes) = self.dom_element(index);
+            builder.start_element(tag, attributes.iter().map(|(k, v)| (*k, v.as_ref())), index)
+        };
+        let root = self.root();
+        if !self.node_to_elem.is_empty() {
+            visit(root, true)?;
+            self.visit_dom_nodes(root, &mut visit)?;
+            visit(root, false)?;
+        }
+        builder.finish()
+    }
+
+    /// Renders the XML DOM from the tree map, in the same form the walker
+    /// writes it.
+    fn render_xml(&self) -> String {
+        // the walker writes nothing for a root without children
+        if self.node_to_elem.is_empty() || self.tree.children(self.root()).is_empty() {
+            return String::new();
+        }
+        let mut writer = Writer::new(Cursor::new(Vec::new()));
+        let mut visit = |index: usize, start: bool| {
+            if !start {
+                let elem = self.element_for_node(index).get_element_props();
+                return writer.write_event(Event::End(BytesEnd::new(control_type_tag(elem))));
+            }
+            let (tag, attributes) = self.dom_element(index);
+            let mut start = BytesStart::new(tag);
+            for (key, value) in &attributes {
+                start.push_attribute((*key, value.as_ref()));
+            }
+            writer.write_event(Event::Start(start))
         };
-        format!("{base}/@RtID")
+        let root = self.root();
+        let rendered = visit(root, true)
+            .and_then(|_| self.visit_dom_nodes(root, &mut visit))
+            .and_then(|_| visit(root, false));
+        if let Err(e) = rendered {
+            error!("Failed to render XML DOM: {}", e);
+            return String::new();
+        }
+        String::from_utf8(writer.into_inner().into_inner()).unwrap_or_default()
+    }
+
+    /// Brings the XPath document up to date after the subtree at `index`
+    /// replaced the node `replaced`, or was appended below `parent`.
+    fn patch_xpath_dom(
+        &self,
+        dom: &mut XpathDom,
+        replaced: Option<usize>,
+        parent: usize,
+        index: usize,
+    ) -> Result<(), String> {
+        let (tag, attributes) = self.dom_element(index);
+        let attributes = attributes.iter().map(|(k, v)| (*k, v.as_ref()));
+        match replaced {
+            Some(replaced) => dom.replace_element(replaced, tag, attributes, index)?,
+            None => dom.append_element(Some(self.dom_node(parent)), tag, attributes, index)?,
+        }
+        let mut open = vec![index];
+        self.visit_dom_nodes(index, &mut |child, start| {
+            if !start {
+                open.pop();
+                return Ok(());
+            }
+            let (tag, attributes) = self.dom_element(child);
+            dom.append_element(
+                open.last().copied(),
+                tag,
+                attributes.iter().map(|(k, v)| (*k, v.as_ref())),
+                child,
+            )?;
+            open.push(child);
+            Ok(())
+        })
     }
 
-    fn eval_xpath_cached(&self, xpath: &str) -> xmlutil::XpathResult {
+    /// Evaluates `xpath` and returns the positions of the selected elements.
+    fn eval_xpath_cached(&self, xpath: &str, variables: &XpathVariables) -> Vec<usize> {
         let mut cache_guard = self.xpath_cache.lock().unwrap_or_else(|e| e.into_inner());
         if cache_guard.is_none() {
-            *cache_guard = XpathDocCache::new(self.get_xml_dom_tree());
+            match self.build_xpath_dom() {
+                Ok(dom) => *cache_guard = Some(dom),
+                Err(e) => {
+                    error!("Failed to build XPath document: {}", e);
+                    return Vec::new();
+                }
+            }
         }
-        match cache_guard.as_mut() {
-            Some(cache) => eval_xpath_on_cache(xpath, cache),
-            None => eval_xpath(xpath, self.get_xml_dom_tree()),
+        let Some(dom) = cache_guard.as_mut() else {
+            return Vec::new();
+        };
+        match dom.eval_with_variables(xpath, variables) {
+            Ok(nodes) => nodes
+                .into_iter()
+                .map(|index| self.node_to_elem[index])
+                .collect(),
+            Err(e) => {
+                debug!("XPath '{}' could not be evaluated:\n{}", xpath, e);
+                Vec::new()
+            }
         }
     }
 
     pub fn get_element_by_xpath(&self, xpath: &str) -> Option<&SaveUIElement> {
-        let xpath = Self::normalize_xpath_for_rtid(xpath);
-
-        let xpath_result = self.eval_xpath_cached(&xpath);
-
-        match xpath_result.get_result_count() {
-            0 => None,
-            1 => {
-                let items = xpath_result.get_result_items();
-                let default_result = XpathQueryResult::default();
-                let itm = items.first().unwrap_or(&default_result);
-                let runtime_id = itm.get_item_value();
-                let node = self.get_tree().get_element_by_runtime_id(runtime_id)?;
-                let elem_pos = self.node_to_elem[node.index];
-                Some(self.ui_elements[elem_pos].get_element_props())
-            }
-            _ => {
-                warn!(
-                    "Warning: XPath expression returned {} results, expected only 1 result. Returning the first result.",
-                    xpath_result.get_result_count()
-                );
-                let items = xpath_result.get_result_items();
-                let default_result = XpathQueryResult::default();
-                let itm = items.first().unwrap_or(&default_result);
-                let runtime_id = itm.get_item_value();
-                let node = self.get_tree().get_element_by_runtime_id(runtime_id)?;
-                let elem_pos = self.node_to_elem[node.index];
-                Some(self.ui_elements[elem_pos].get_element_props())
-            }
+        self.get_element_by_xpath_with_variables(xpath, &XpathVariables::default())
+    }
+
+    /// Like [`UITree::get_element_by_xpath`], binding `variables` for `$name`
+    /// references so values never need quoting inside the expression.
+    pub fn get_element_by_xpath_with_variables(
+        &self,
+        xpath: &str,
+        variables: &XpathVariables,
+    ) -> Option<&SaveUIElement> {
+        self.get_element_in_tree_by_xpath_with_variables(xpath, variables)
+            .map(UIElementInTree::get_element_props)
+    }
+
+    /// Like [`UITree::get_element_by_xpath`], returning the element together
+    /// with its `RtID` key and tree index.
+    pub fn get_element_in_tree_by_xpath(&self, xpath: &str) -> Option<&UIElementInTree> {
+        self.get_element_in_tree_by_xpath_with_variables(xpath, &XpathVariables::default())
+    }
+
+    /// Like [`UITree::get_element_in_tree_by_xpath`], binding `variables` for
+    /// `$name` references.
+    pub fn get_element_in_tree_by_xpath_with_variables(
+        &self,
+        xpath: &str,
+        variables: &XpathVariables,
+    ) -> Option<&UIElementInTree> {
+        let positions = self.eval_xpath_cached(xpath, variables);
+        if positions.len() > 1 {
+            warn!(
+                "Warning: XPath expression returned {} results, expected only 1 result. Returning the first result.",
+                positions.len()
+            );
         }
+        let pos = *positions.first()?;
+        Some(&self.ui_elements[pos])
     }
 
     pub fn get_elements_by_xpath(&self, xpath: &str) -> Option<Vec<&SaveUIElement>> {
-        let xpath = Self::normalize_xpath_for_rtid(xpath);
-
-        let xpath_result = self.eval_xpath_cached(&xpath);
-        let mut results: Vec<&SaveUIElement> = Vec::new();
-        match xpath_result.get_result_count() {
-            0 => None,
-            1 => {
-                let items = xpath_result.get_result_items();
-                let default_result = &XpathQueryResult::default();
-                let itm = items.first().unwrap_or(default_result);
-                let runtime_id = itm.get_item_value();
-                let node = self.get_tree().get_element_by_runtime_id(runtime_id)?;
-                let elem_pos = self.node_to_elem[node.index];
-                results.push(self.ui_elements[elem_pos].get_element_props());
-                Some(results)
-            }
-            _ => {
-                let items = xpath_result.get_result_items();
-                for itm in items {
-                    let runtime_id = itm.get_item_value();
-                    if let Some(node) = self.get_tree().get_element_by_runtime_id(runtime_id) {
-                        let elem_pos = self.node_to_elem[node.index];
-                        results.push(self.ui_elements[elem_pos].get_element_props());
-                    } else {
-                        warn!(
-                            "Element with runtime_id '{}' not found in tree, skipping",
-                            runtime_id
-                        );
-                    }
-                }
-                if results.is_empty() {
-                    return None;
-                }
-                Some(results)
-            }
+        self.get_elements_by_xpath_with_variables(xpath, &XpathVariables::default())
+    }
+
+    /// Like [`UITree::get_elements_by_xpath`], binding `variables` for `$name`
+    /// references.
+    pub fn get_elements_by_xpath_with_variables(
+        &self,
+        xpath: &str,
+        variables: &XpathVariables,
+    ) -> Option<Vec<&SaveUIElement>> {
+        self.get_elements_in_tree_by_xpath_with_variables(xpath, variables)
+            .map(|elements| {
+                elements
+                    .into_iter()
+                    .map(UIElementInTree::get_element_props)
+                    .collect()
+            })
+    }
+
+    /// Like [`UITree::get_elements_by_xpath_with_variables`], returning the
+    /// elements together with their `RtID` keys and tree indices.
+    pub fn get_elements_in_tree_by_xpath_with_variables(
+        &self,
+        xpath: &str,
+        variables: &XpathVariables,
+    ) -> Option<Vec<&UIElementInTree>> {
+        let positions = self.eval_xpath_cached(xpath, variables);
+        if positions.is_empty() {
+            return None;
         }
+        Some(
+            positions
+                .into_iter()
+                .map(|pos| &self.ui_elements[pos])
+                .collect(),
+        )
     }
 }
 
@@ -299,18 +548,26 @@ impl UITree {
     pub fn append_or_replace_subtree(
         &mut self,
         parent_index: usize,
-        mut subtree: UITree,
+        subtree: UITree,
     ) -> Result<usize, String> {
-        trace!("Parent index to append subtree: {}", parent_index);
-        trace!(
-            "Appending or replacing subtree with root: {}",
-            subtree.get_tree().node(subtree.root()).name
-        );
-        let subtree_root = subtree.root();
-        let subtree_node = subtree.get_tree().node(subtree_root);
-        let subtree_runtime_id = subtree_node.runtime_id.clone();
-        let subtree_name = subtree_node.name.clone();
+        self.merge_subtrees(parent_index, vec![subtree])?
+            .pop()
+            .unwrap_or_else(|| Err("No subtree merged".to_string()))
+    }
 
+    /// Appends the given subtrees below `parent_index`, replacing any existing
+    /// node with the same runtime ID as a subtree root in place.
+    ///
+    /// All subtrees are merged in a single pass and the elements are sorted
+    /// once. An XPath document already built is updated in place; the XML DOM
+    /// is rendered from the merged tree when it is next needed. Returns, per
+    /// subtree, the index of its new root node or why it could not be merged;
+    /// subtrees that fail leave the tree untouched.
+    pub fn merge_subtrees(
+        &mut self,
+        parent_index: usize,
+        mut subtrees: Vec<UITree>,
+    ) -> Result<Vec<Result<usize, String>>, String> {
         if !self.get_tree().has_node(parent_index) {
             error!(
                 "Parent index {} does not exist in the current tree",
@@ -319,50 +576,93 @@ impl UITree {
             return Err("Parent index does not exist in the current tree".to_string());
         }
 
-        if let Some(existing_node) = self
-            .get_tree()
-            .get_element_by_runtime_id(&subtree_runtime_id)
-        {
-            let existing_node_index = existing_node.index;
-            debug!(
-                "Subtree root already exists in the current tree at index {}. Replacing existing subtree.",
-                existing_node_index
-            );
-            self.get_tree_mut()
-                .remove_node(existing_node_index)
-                .map_err(|e| e.to_string())?;
+        let xpath_dom = self
+            .xpath_cache
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        let mut dom_edits = Vec::new();
+        let mut results = Vec::with_capacity(subtrees.len());
+        let mut merged_elements = Vec::new();
+        for subtree in subtrees.iter_mut() {
+            let subtree_root = subtree.root();
+            let subtree_node = subtree.get_tree().node(subtree_root);
+            let subtree_runtime_id = subtree_node.runtime_id.clone();
+            let subtree_name = subtree_node.name.clone();
+            trace!("Merging subtree with root: {}", subtree_name);
+
+            let existing = self.outermost_node_with_key(&subtree_runtime_id);
+            let new_index = match existing {
+                Some(existing_node_index) => {
+                    debug!(
+                        "Subtree root already exists in the current tree at index {}. Replacing existing subtree.",
+                        existing_node_index
+                    );
+                    self.get_tree_mut()
+                        .replace_node(existing_node_index, &subtree_name, &subtree_runtime_id, ())
+                        .map_err(|e| e.to_string())
+                }
+                None => Ok(self.get_tree_mut().add_child(
+                    parent_index,
+                    &subtree_name,
+                    &subtree_runtime_id,
+                    (),
+                )),
+            };
+            if let Ok(new_index) = new_index {
+                debug!("Added subtree root to current tree at index {}", new_index);
+                self.append_children(new_index, subtree, subtree_root)?;
+                merged_elements.append(subtree.get_elements_mut());
+                dom_edits.push((existing, new_index));
+            }
+            results.push(new_index);
         }
 
-        let tree_mut = self.get_tree_mut();
-        let new_index = tree_mut.add_child(parent_index, &subtree_name, &subtree_runtime_id, ());
-        debug!("Added subtree root to current tree at index {}", new_index);
-
-        remove_in_place(self.get_elements_mut(), subtree.get_elements_mut());
-
-        self.get_elements_mut().append(subtree.get_elements_mut());
+        remove_in_place(self.get_elements_mut(), &merged_elements);
+        self.get_elements_mut().append(&mut merged_elements);
 
         info!("Sorting UI elements by z-order and size...");
         walker_common::sort_elements(self.get_elements_mut());
 
-        self.append_children(new_index, &mut subtree, subtree_root)?;
+        self.xml_dom_tree = Arc::default();
+        self.rebuild_node_to_elem();
 
-        let current_xml_dom_tree = self.get_xml_dom_tree();
-        let subtree_xml_dom_tree = subtree.get_xml_dom_tree();
-        info!(
-            "Merging XML DOM trees... adding new subtree: {}",
-            subtree_xml_dom_tree
-        );
-        let new_xml_dom_tree = append_or_replace_node_by_rt_id(
-            current_xml_dom_tree,
-            subtree_xml_dom_tree,
-            &subtree_runtime_id,
-        )?;
-        self.xml_dom_tree = new_xml_dom_tree;
-        *self.xpath_cache.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        if let Some(mut dom) = xpath_dom {
+            let patched = dom_edits.into_iter().try_for_each(|(replaced, index)| {
+                self.patch_xpath_dom(&mut dom, replaced, parent_index, index)
+            });
+            match patched {
+                Ok(()) => {
+                    *self
+                        .xpath_cache
+                        .get_mut()
+                        .unwrap_or_else(|e| e.into_inner()) = Some(dom)
+                }
+                Err(e) => debug!("XPath document is rebuilt on next use: {}", e),
+            }
+        }
 
-        self.rebuild_node_to_elem();
+        Ok(results)
+    }
+
+    /// The outermost node with the runtime ID `key`: a walk root and its
+    /// level-0 copy share their key, and are replaced together.
+    fn outermost_node_with_key(&self, key: &str) -> Option<usize> {
+        let index = self.get_tree().get_element_by_runtime_id(key)?.index;
+        Some(self.dom_node(index))
+    }
 
-        Ok(new_index)
+    /// The node standing for `index` in the XML DOM: itself, or the node it
+    /// is the level-0 copy of.
+    fn dom_node(&self, mut index: usize) -> usize {
+        while index != self.root() {
+            let parent = self.get_tree().node(index).parent;
+            if self.get_tree().node(parent).runtime_id != self.get_tree().node(index).runtime_id {
+                break;
+            }
+            index = parent;
+        }
+        index
     }
 
     fn append_children(
@@ -393,128 +693,153 @@ impl UITree {
 }
 
 fn remove_in_place(orig: &mut Vec<UIElementInTree>, check: &[UIElementInTree]) {
-    let ids: HashSet<Vec<i32>> = check
-        .iter()
-        .map(|e| e.get_element_props().get_runtime_id().to_vec())
-        .collect();
-    orig.retain(|x| !ids.contains(x.get_element_props().get_runtime_id()));
+    let ids: HashSet<&str> = check.iter().map(|e| e.get_rt_id()).collect();
+    orig.retain(|x| !ids.contains(x.get_rt_id()));
 }
 
-fn append_or_replace_node_by_rt_id(
-    current_xml_dom_tree: &str,
-    xml_dom_subtree: &str,
-    target_node_rt_id: &str,
-) -> Result<String, String> {
-    let target = target_node_rt_id.to_string();
-
-    let mut xot = xot::Xot::new();
-    let root = xot
-        .parse(current_xml_dom_tree)
-        .map_err(|e| format!("Failed to parse current XML: {}", e))?;
-    let doc = xot
-        .document_element(root)
-        .map_err(|e| format!("Failed to get document element: {}", e))?;
-
-    if let Some(existing_node) = find_node_by_rt_id(&mut xot, doc, &target) {
-        let new_subtree = xot
-            .parse(xml_dom_subtree)
-            .map_err(|e| format!("Failed to parse subtree XML: {}", e))?;
-        let new_subtree_doc = xot
-            .document_element(new_subtree)
-            .map_err(|e| format!("Failed to get subtree document element: {}", e))?;
-        xot.replace(existing_node, new_subtree_doc)
-            .map_err(|e| format!("Failed to replace node: {}", e))?;
-
-        xot.serialize_xml_string(Default::default(), root)
-            .map_err(|e| format!("Failed to serialize XML: {}", e))
-    } else {
-        let new_node = xot
-            .parse(xml_dom_subtree)
-            .map_err(|e| format!("Failed to parse subtree XML: {}", e))?;
-        let new_node_doc = xot
-            .document_element(new_node)
-            .map_err(|e| format!("Failed to get subtree document element: {}", e))?;
-        xot.append(doc, new_node_doc)
-            .map_err(|e| format!("Failed to append node: {}", e))?;
-
-        xot.serialize_xml_string(Default::default(), root)
-            .map_err(|e| format!("Failed to serialize XML: {}", e))
-    }
+/// Walks the UI Automation tree on the calling thread and sends the resulting
+/// `UITree` over `tx`.
+///
+/// The window budget and `cancel` are only checked between elements, so a UI
+/// Automation call that never returns (e.g. into a hung application) blocks
+/// the walk. [`get_all_elements_par_xml`] gives up on such windows instead.
+#[cfg(windows)]
+pub fn get_all_elements_xml(
+    tx: Sender<Result<UITree, UITreeError>>,
+    root_element: Option<SaveUIElement>,
+    max_depth: Option<usize>,
+    calling_window_caption: Option<String>,
+    target_window_caption: Option<String>,
+    cancel: Option<Arc<AtomicBool>>,
+) {
+    let options = WalkOptions::default()
+        .with_max_depth(max_depth)
+        .with_calling_window_caption(calling_window_caption)
+        .with_target_window_caption(target_window_caption);
+    get_all_elements_xml_with_options(tx, root_element, options, cancel);
+}
+
+/// Walks only the top-level windows in `scope` (see [`WindowScope`]).
+#[cfg(windows)]
+pub fn get_all_elements_xml_scoped(
+    tx: Sender<Result<UITree, UITreeError>>,
+    scope: WindowScope,
+    max_depth: Option<usize>,
+    cancel: Option<Arc<AtomicBool>>,
+) {
+    let options = WalkOptions::default()
+        .with_max_depth(max_depth)
+        .with_window_scope(Some(scope));
+    get_all_elements_xml_with_options(tx, None, options, cancel);
 }
 
-fn find_node_by_rt_id(xot: &mut xot::Xot, doc: xot::Node, target: &str) -> Option<xot::Node> {
-    let rt_id_a = xot.add_name("RtID");
-    let descendants = xot.descendants(doc);
-    let rt_id_default = "n/a".to_string();
-    for desc in descendants {
-        let desc_attrs = xot.attributes(desc);
-        let rt_id = desc_attrs.get(rt_id_a).unwrap_or(&rt_id_default);
-        if rt_id == target {
-            return Some(desc);
+/// Like [`get_all_elements_xml`], with all walk settings taken from `options`.
+#[cfg(windows)]
+pub fn get_all_elements_xml_with_options(
+    tx: Sender<Result<UITree, UITreeError>>,
+    root_element: Option<SaveUIElement>,
+    options: WalkOptions,
+    cancel: Option<Arc<AtomicBool>>,
+) {
+    let backend = match UIABackend::with_view(options.get_tree_view()) {
+        Ok(b) => b.with_element_state(options.get_element_state()),
+        Err(e) => {
+            let _ = tx.send(Err(e));
+            return;
         }
-    }
-    None
+    };
+    get_all_elements_xml_with_backend(tx, &backend, root_element, &options, cancel);
 }
 
-pub fn get_all_elements_xml(
+/// Walks the tree exposed by `backend` and sends the resulting `UITree` over `tx`.
+///
+/// When `root_element` is given the walk starts at that element, otherwise at
+/// the backend's root. Elements whose name equals the calling window caption
+/// are skipped together with their subtree; if a target window caption is
+/// set, only elements whose tree path contains it are kept.
+///
+/// The walk runs on the calling thread and checks the window budget and
+/// `cancel` between elements only, so a backend call that never returns
+/// blocks it; see [`get_all_elements_par_xml_with_backend`].
+pub fn get_all_elements_xml_with_backend<B: UIBackend>(
     tx: Sender<Result<UITree, UITreeError>>,
+    backend: &B,
     root_element: Option<SaveUIElement>,
-    max_depth: Option<usize>,
-    calling_window_caption: Option<String>,
-    target_window_caption: Option<String>,
+    options: &WalkOptions,
     cancel: Option<Arc<AtomicBool>>,
 ) {
     info!(
         "Starting UI element retrieval with max depth: {:?} and window title filters: calling_window_caption='{}', target_window_caption='{}'",
-        max_depth,
-        calling_window_caption.as_deref().unwrap_or("none"),
-        target_window_caption.as_deref().unwrap_or("none")
+        options.get_max_depth(),
+        options.get_calling_window_caption().unwrap_or("none"),
+        options.get_target_window_caption().unwrap_or("none")
     );
-    let automation = match get_ui_automation_instance() {
-        Ok(a) => a,
+
+    let progress = ProgressTracker::new(options);
+    let ui_tree = match walk_tree(
+        backend,
+        root_element,
+        None,
+        options,
+        cancel.as_ref(),
+        None,
+        &progress,
+    ) {
+        Ok(tree) => tree,
         Err(e) => {
-            error!("Failed to create UIAutomation instance: {}", e);
-            let _ = tx.send(Err(UITreeError::NoUIAutomation));
+            let _ = tx.send(Err(e));
             return;
         }
     };
-    let walker = match automation.get_control_view_walker() {
-        Ok(w) => w,
+
+    info!(
+        "Sending UI tree with {} elements to the main thread...",
+        ui_tree.get_elements().len()
+    );
+    match tx.send(Ok(ui_tree)) {
+        Ok(_) => {
+            info!("UI tree sent successfully.");
+        }
         Err(e) => {
-            error!("Failed to get control view walker: {}", e);
-            let _ = tx.send(Err(UITreeError::UIAutomation(e.to_string())));
-            return;
+            error!("Error sending UI tree: {:?}", e);
         }
     };
+}
 
-    let mut ui_elements: Vec<UIElementInTree> = Vec::with_capacity(10000);
-
-    let mut xml_writer = Writer::new(Cursor::new(Vec::new()));
+/// Walks the tree below `root_element` (or the backend's root). The walk stops
+/// early once `deadline` has passed; the part walked so far is returned with
+/// the root listed in [`UITree::get_incomplete_windows`].
+///
+/// `root_rt_id` is the key the root already has in another tree, for subtrees
+/// that are merged into that tree later; it also namespaces synthetic IDs.
+fn walk_tree<B: UIBackend>(
+    backend: &B,
+    root_element: Option<SaveUIElement>,
+    root_rt_id: Option<String>,
+    options: &WalkOptions,
+    cancel: Option<&Arc<AtomicBool>>,
+    deadline: Option<Instant>,
+    progress: &ProgressTracker,
+) -> Result<UITree, UITreeError> {
+    let from_desktop = root_element.is_none();
+    let root = match root_element {
+        Some(elem) => backend.resolve(&elem)?,
+        None => backend.root()?,
+    };
 
-    let root = if let Some(elem) = root_element {
-        match elem.get_ui_automation_ui_element() {
-            Some(e) => e,
-            None => {
-                error!("Failed to resolve root UIElement from SaveUIElement");
-                let _ = tx.send(Err(UITreeError::UIAutomation(
-                    "Failed to resolve root UIElement from SaveUIElement".to_string(),
-                )));
-                return;
-            }
+    let mut strings = StringInterner::new();
+    let mut ui_elem_props = backend.properties(&root, 0, 999);
+    ui_elem_props.intern(&mut strings);
+    let (runtime_id, rt_ids) = match root_rt_id {
+        Some(rt_id) => {
+            let rt_ids = RtIdAllocator::new(format!("{}:", rt_id));
+            (rt_id, rt_ids)
         }
-    } else {
-        match automation.get_root_element() {
-            Ok(e) => e,
-            Err(e) => {
-                error!("Failed to get root element: {}", e);
-                let _ = tx.send(Err(UITreeError::UIAutomation(e.to_string())));
-                return;
-            }
+        None => {
+            let mut rt_ids = RtIdAllocator::new("");
+            (rt_ids.rt_id(ui_elem_props.get_runtime_id()), rt_ids)
         }
     };
-
-    let ui_elem_props = SaveUIElement::new(&root, 0, 999);
-    let runtime_id = format_runtime_id(ui_elem_props.get_runtime_id());
     let item = format!(
         "'{}' {} ({} | {} | {})",
         ui_elem_props.get_name(),
@@ -523,59 +848,60 @@ pub fn get_all_elements_xml(
         ui_elem_props.get_framework_id(),
         runtime_id
     );
-    let mut tree = UITreeMap::new(item, runtime_id.clone(), ());
-
-    let mut tree_path = ui_elem_props.get_name().to_string();
-
-    let ui_elem_in_tree = UIElementInTree::new(ui_elem_props, 0);
-    ui_elements.push(ui_elem_in_tree);
+    let tree = UITreeMap::new(item, runtime_id.clone(), ());
+    let tree_path = ui_elem_props.get_name().to_string();
+    let root_name = tree_path.clone();
+
+    let mut ctx = WalkContext {
+        backend,
+        tree,
+        ui_elements: Vec::with_capacity(10000),
+        xml_writer: Writer::new(Cursor::new(Vec::new())),
+        current_window: tree_path.clone(),
+        tree_path,
+        from_desktop,
+        frameworks: Vec::new(),
+        root_rt_id: runtime_id.clone(),
+        rt_ids,
+        strings,
+        options,
+        cancel,
+        deadline,
+        timed_out: false,
+        window_deadline: None,
+        window_timed_out: false,
+        incomplete_windows: Vec::new(),
+        progress,
+    };
+    ctx.ui_elements
+        .push(UIElementInTree::new(ui_elem_props, 0).with_rt_id(runtime_id));
 
-    if let Ok(_first_child) = walker.get_first_child(&root) {
-        get_element(
-            &mut tree,
-            &mut ui_elements,
-            0,
-            &walker,
-            &root,
-            &mut xml_writer,
-            0,
-            0,
-            max_depth,
-            calling_window_caption.as_deref(),
-            target_window_caption.as_deref(),
-            &mut tree_path,
-            cancel.as_ref(),
-        );
+    if backend.first_child(&root).is_some() {
+        get_element(&mut ctx, 0, &root, 0, 0);
     }
 
     // Check cancellation before sending results
-    if cancel.as_ref().is_some_and(|c| c.load(Ordering::Relaxed)) {
+    if ctx.is_cancelled() {
         info!("Tree construction cancelled, discarding partial results");
-        let _ = tx.send(Err(UITreeError::Cancelled));
-        return;
+        return Err(UITreeError::Cancelled);
+    }
+    if ctx.timed_out {
+        warn!(
+            "Walking '{}' exceeded its time budget, keeping what was walked",
+            root_name
+        );
+        let root_rt_id = ctx.root_rt_id.clone();
+        ctx.incomplete_windows.push(IncompleteWindow::new(
+            root_name,
+            root_rt_id,
+            IncompleteReason::TimedOut,
+        ));
     }
 
-    let xml_dom_tree = String::from_utf8(xml_writer.into_inner().into_inner()).unwrap_or_default();
-
-    info!("Sorting UI elements by z-order and size...");
-    walker_common::sort_elements(&mut ui_elements);
-
-    let ui_tree = UITree::new(tree, xml_dom_tree, ui_elements);
-
-    info!(
-        "Sending UI tree with {} elements to the main thread...",
-        ui_tree.get_elements().len()
-    );
-    match tx.send(Ok(ui_tree)) {
-        Ok(_) => {
-            info!("UI tree sent successfully.");
-        }
-        Err(e) => {
-            error!("Error sending UI tree: {:?}", e);
-        }
-    };
+    Ok(ctx.into_ui_tree())
 }
 
+#[cfg(windows)]
 pub fn get_all_elements_par_xml(
     tx: Sender<Result<UITree, UITreeError>>,
     max_depth: Option<usize>,
@@ -583,82 +909,93 @@ pub fn get_all_elements_par_xml(
     target_window_caption: Option<String>,
     cancel: Option<Arc<AtomicBool>>,
 ) {
+    let options = WalkOptions::default()
+        .with_max_depth(max_depth)
+        .with_calling_window_caption(calling_window_caption)
+        .with_target_window_caption(target_window_caption);
+    get_all_elements_par_xml_with_options(tx, options, cancel);
+}
+
+/// Parallel variant of [`get_all_elements_xml_scoped`].
+#[cfg(windows)]
+pub fn get_all_elements_par_xml_scoped(
+    tx: Sender<Result<UITree, UITreeError>>,
+    scope: WindowScope,
+    max_depth: Option<usize>,
+    cancel: Option<Arc<AtomicBool>>,
+) {
+    let options = WalkOptions::default()
+        .with_max_depth(max_depth)
+        .with_window_scope(Some(scope));
+    get_all_elements_par_xml_with_options(tx, options, cancel);
+}
+
+/// Like [`get_all_elements_par_xml`], with all walk settings taken from `options`.
+#[cfg(windows)]
+pub fn get_all_elements_par_xml_with_options(
+    tx: Sender<Result<UITree, UITreeError>>,
+    options: WalkOptions,
+    cancel: Option<Arc<AtomicBool>>,
+) {
+    let view = options.get_tree_view();
+    let element_state = options.get_element_state();
+    let backend = move || UIABackend::with_view(view).map(|b| b.with_element_state(element_state));
+    get_all_elements_par_xml_with_backend(tx, backend, options, cancel);
+}
+
+/// Parallel variant of [`get_all_elements_xml_with_backend`]: the top-level
+/// windows are walked by a pool of worker threads and merged into one `UITree`.
+///
+/// `make_backend` is called once on the coordinating thread and once on every
+/// worker thread, since UI Automation instances must not cross threads. The
+/// number of workers and the time budget per window are taken from `options`.
+/// Windows that time out or fail keep their top-level element in the tree and
+/// are listed in [`UITree::get_incomplete_windows`].
+pub fn get_all_elements_par_xml_with_backend<B, F>(
+    tx: Sender<Result<UITree, UITreeError>>,
+    make_backend: F,
+    options: WalkOptions,
+    cancel: Option<Arc<AtomicBool>>,
+) where
+    B: UIBackend,
+    F: Fn() -> Result<B, UITreeError> + Send + Sync + 'static,
+{
     info!(
         "Starting parallel UI element retrieval with max depth: {:?} and window title filters: calling_window_caption='{}', target_window_caption='{}'",
-        max_depth,
-        calling_window_caption.as_deref().unwrap_or("none"),
-        target_window_caption.as_deref().unwrap_or("none")
+        options.get_max_depth(),
+        options.get_calling_window_caption().unwrap_or("none"),
+        options.get_target_window_caption().unwrap_or("none")
     );
-    let automation = match get_ui_automation_instance() {
-        Ok(a) => a,
-        Err(e) => {
-            error!("Failed to create UIAutomation instance: {}", e);
-            let _ = tx.send(Err(UITreeError::NoUIAutomation));
-            return;
-        }
-    };
-    let walker = match automation.get_control_view_walker() {
-        Ok(w) => w,
+    let backend = match make_backend() {
+        Ok(b) => b,
         Err(e) => {
-            error!("Failed to get control view walker: {}", e);
-            let _ = tx.send(Err(UITreeError::UIAutomation(e.to_string())));
+            let _ = tx.send(Err(e));
             return;
         }
     };
 
-    let mut ui_elements: Vec<UIElementInTree> = Vec::with_capacity(10000);
-
-    let mut xml_writer = Writer::new(Cursor::new(Vec::new()));
-
-    let root = match automation.get_root_element() {
-        Ok(e) => e,
+    // The coordinator only collects the top-level windows; the budget applies
+    // to the workers
+    let top_level_options = options
+        .clone()
+        .with_max_depth(Some(1_usize))
+        .with_window_budget(None);
+    let progress = Arc::new(ProgressTracker::new(&options));
+    let mut ui_tree = match walk_tree(
+        &backend,
+        None,
+        None,
+        &top_level_options,
+        cancel.as_ref(),
+        None,
+        &progress,
+    ) {
+        Ok(tree) => tree,
         Err(e) => {
-            error!("Failed to get root element: {}", e);
-            let _ = tx.send(Err(UITreeError::UIAutomation(e.to_string())));
+            let _ = tx.send(Err(e));
             return;
         }
     };
-    let ui_elem_props = SaveUIElement::new(&root, 0, 999);
-    let runtime_id = format_runtime_id(ui_elem_props.get_runtime_id());
-    let item = format!(
-        "'{}' {} ({} | {} | {})",
-        ui_elem_props.get_name(),
-        ui_elem_props.get_localized_control_type(),
-        ui_elem_props.get_classname(),
-        ui_elem_props.get_framework_id(),
-        runtime_id
-    );
-    let mut tree = UITreeMap::new(item, runtime_id.clone(), ());
-
-    let ui_elem_in_tree = UIElementInTree::new(ui_elem_props, 0);
-    ui_elements.push(ui_elem_in_tree);
-
-    let mut tree_path = String::new();
-
-    if let Ok(_first_child) = walker.get_first_child(&root) {
-        get_element(
-            &mut tree,
-            &mut ui_elements,
-            0,
-            &walker,
-            &root,
-            &mut xml_writer,
-            0,
-            0,
-            Some(1_usize),
-            calling_window_caption.as_deref(),
-            target_window_caption.as_deref(),
-            &mut tree_path,
-            cancel.as_ref(),
-        );
-    }
-
-    let xml_dom_tree = String::from_utf8(xml_writer.into_inner().into_inner()).unwrap_or_default();
-
-    info!("Sorting UI elements by z-order and size...");
-    walker_common::sort_elements(&mut ui_elements);
-
-    let mut ui_tree = UITree::new(tree, xml_dom_tree, ui_elements);
     debug!(
         "This is the top level tree we are processing:\n{}",
         ui_tree.get_xml_dom_tree()
@@ -682,79 +1019,199 @@ pub fn get_all_elements_par_xml(
     };
 
     let child_indices = ui_tree.get_tree().children(root_first_child_idx);
-    let mut child_elements = Vec::new();
+    let mut child_elements = VecDeque::new();
     trace!("children to process in parallel: {}", child_indices.len());
-    for &child_index in child_indices {
+    for (position, &child_index) in child_indices.iter().enumerate() {
         let elem_pos = ui_tree.node_to_elem[child_index];
-        let child_save_ui_elem = ui_tree.ui_elements[elem_pos].get_element_props();
-        child_elements.push(child_save_ui_elem.clone());
+        let child_elem = &ui_tree.ui_elements[elem_pos];
+        child_elements.push_back((
+            position,
+            child_elem.get_element_props().clone(),
+            child_elem.get_rt_id().to_string(),
+        ));
     }
 
     let child_count = child_elements.len();
-    let (tx_par, rx_par) = channel::<Result<UITree, UITreeError>>();
-    let mut handles: Vec<std::thread::JoinHandle<()>> = Vec::new();
-    for element in child_elements {
-        let tx_par_clone = tx_par.clone();
-        let calling_window_caption_n = calling_window_caption.clone();
-        let target_window_caption_n = target_window_caption.clone();
-        let cancel_clone = cancel.clone();
-        debug!(
-            "Spawning thread to process element: '{}'",
-            element.get_name()
-        );
-        let handle = std::thread::spawn(move || {
-            get_all_elements_xml(
-                tx_par_clone,
-                Some(element),
-                max_depth,
-                calling_window_caption_n,
-                target_window_caption_n,
-                cancel_clone,
-            );
-        });
-        handles.push(handle);
-    }
-    drop(tx_par);
-
-    debug!("Collecting subtrees from {} threads...", child_count);
-    let mut subtrees = Vec::new();
-    for _ in 0..child_count {
-        match rx_par.recv() {
-            Ok(Ok(subtree)) => subtrees.push(subtree),
-            Ok(Err(e)) => {
-                error!("Subtree build failed: {}", e);
-                let _ = tx.send(Err(e));
-                return;
+    let worker_count = options.get_max_workers().min(child_count).max(1);
+    // A worker stuck in a call into an unresponsive application is given up
+    // on once its window is well past the budget
+    let abandon_after = options
+        .get_window_budget()
+        .map(|budget| budget + UNRESPONSIVE_GRACE);
+    let pool = WorkerPool {
+        queue: Arc::new(Mutex::new(child_elements)),
+        make_backend: Arc::new(make_backend),
+        options: Arc::new(options),
+        progress: Arc::clone(&progress),
+        cancel: cancel.clone(),
+    };
+    let (tx_par, rx_par) = channel::<WorkerMessage>();
+    debug!(
+        "Spawning {} worker threads for {} windows",
+        worker_count, child_count
+    );
+    let mut handles: Vec<Option<std::thread::JoinHandle<()>>> = (0..worker_count)
+        .map(|worker| Some(pool.spawn(worker, tx_par.clone())))
+        .collect();
+
+    debug!("Collecting subtrees from {} workers...", worker_count);
+    let mut results: Vec<Option<Result<UITree, UITreeError>>> =
+        (0..child_count).map(|_| None).collect();
+    // Start time and worker of the windows currently being walked
+    let mut in_flight: Vec<Option<(Instant, usize)>> = vec![None; child_count];
+    let mut abandoned = vec![false; child_count];
+    let mut pending = child_count;
+    while pending > 0 {
+        let wait = in_flight
+            .iter()
+            .flatten()
+            .filter_map(|(started, _)| abandon_after.map(|limit| *started + limit))
+            .min()
+            .map_or(WORKER_POLL_INTERVAL, |expiry| {
+                expiry
+                    .saturating_duration_since(Instant::now())
+                    .min(WORKER_POLL_INTERVAL)
+            });
+        match rx_par.recv_timeout(wait) {
+            Ok(WorkerMessage::Started { position, worker }) => {
+                in_flight[position] = Some((Instant::now(), worker));
             }
-            Err(e) => {
-                error!("Failed to receive subtree from thread: {}", e);
-                let _ = tx.send(Err(UITreeError::ChannelRecv(e.to_string())));
-                return;
+            Ok(WorkerMessage::Finished { position, result }) => {
+                // The result of an abandoned window comes too late to be used
+                if abandoned[position] {
+                    continue;
+                }
+                let result = *result;
+                in_flight[position] = None;
+                if let Ok(subtree) = &result {
+                    progress.window_completed(subtree);
+                }
+                results[position] = Some(result);
+                pending -= 1;
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                let now = Instant::now();
+                for position in 0..child_count {
+                    let Some((started, worker)) = in_flight[position] else {
+                        continue;
+                    };
+                    if abandon_after.is_none_or(|limit| now < started + limit) {
+                        continue;
+                    }
+                    warn!(
+                        "Worker {} stopped responding while walking window {}, abandoning it",
+                        worker, position
+                    );
+                    in_flight[position] = None;
+                    abandoned[position] = true;
+                    pending -= 1;
+                    // The stuck thread is left behind; a new worker takes
+                    // over the windows still queued
+                    handles[worker] = None;
+                    handles.push(Some(pool.spawn(handles.len(), tx_par.clone())));
+                }
+                // Workers that could not get a backend exit without walking
+                // their share, so nothing more will arrive
+                if handles.iter().flatten().all(|h| h.is_finished()) {
+                    for message in rx_par.try_iter() {
+                        if let WorkerMessage::Finished { position, result } = message
+                            && !abandoned[position]
+                        {
+                            let result = *result;
+                            if let Ok(subtree) = &result {
+                                progress.window_completed(subtree);
+                            }
+                            results[position] = Some(result);
+                        }
+                    }
+                    break;
+                }
             }
+            Err(RecvTimeoutError::Disconnected) => break,
         }
     }
 
     trace!("Waiting for all threads to complete...");
-    for handle in handles {
+    for handle in handles.into_iter().flatten() {
         if let Err(e) = handle.join() {
             error!("Thread panicked: {:?}", e);
         }
     }
 
-    debug!("Appending {} subtrees to the main tree...", subtrees.len());
-    for subtree in subtrees {
-        match ui_tree.append_or_replace_subtree(ui_tree.get_tree().root(), subtree) {
-            Ok(_) => {}
-            Err(e) => {
-                error!("Error appending subtree: {}", e);
+    if cancel.as_ref().is_some_and(|c| c.load(Ordering::Relaxed)) {
+        info!("Tree construction cancelled, discarding partial results");
+        let _ = tx.send(Err(UITreeError::Cancelled));
+        return;
+    }
+
+    let child_indices = ui_tree.get_tree().children(root_first_child_idx).to_vec();
+    let mut subtrees = Vec::new();
+    let mut incomplete_windows = Vec::new();
+    for ((child_index, result), abandoned) in child_indices.into_iter().zip(results).zip(abandoned)
+    {
+        let reason = match result {
+            Some(Ok(subtree)) => {
+                // A window over budget keeps the part of its subtree walked in time
+                let timed_out = !subtree.is_complete();
+                // A window without children has nothing to add to the tree
+                if !subtree.get_xml_dom_tree().is_empty() {
+                    subtrees.push(subtree);
+                }
+                if !timed_out {
+                    continue;
+                }
+                IncompleteReason::TimedOut
             }
+            Some(Err(e)) => IncompleteReason::Failed(e.to_string()),
+            None if abandoned => IncompleteReason::Unresponsive,
+            None => IncompleteReason::Failed("window was not walked".to_string()),
+        };
+        let (_, props) = ui_tree.node(child_index);
+        warn!("Window '{}' is incomplete: {:?}", props.get_name(), reason);
+        incomplete_windows.push(IncompleteWindow::new(
+            props.get_name().to_string(),
+            ui_tree.get_tree().node(child_index).runtime_id.to_string(),
+            reason,
+        ));
+    }
+
+    debug!("Merging {} subtrees into the main tree...", subtrees.len());
+    let subtree_names: Vec<(String, String)> = subtrees
+        .iter()
+        .map(|t| {
+            let (_, props) = t.node(t.root());
+            (
+                props.get_name().to_string(),
+                t.get_tree().node(t.root()).runtime_id.to_string(),
+            )
+        })
+        .collect();
+    let root = ui_tree.get_tree().root();
+    match ui_tree.merge_subtrees(root, subtrees) {
+        Ok(merged) => {
+            for ((name, runtime_id), result) in subtree_names.into_iter().zip(merged) {
+                if let Err(e) = result {
+                    error!("Error appending subtree: {}", e);
+                    incomplete_windows.push(IncompleteWindow::new(
+                        name,
+                        runtime_id,
+                        IncompleteReason::Failed(e),
+                    ));
+                }
+            }
+        }
+        Err(e) => {
+            error!("Error merging subtrees: {}", e);
+            let _ = tx.send(Err(UITreeError::XmlError(e)));
+            return;
         }
-        debug!("UI tree has now {} elements", ui_tree.get_elements().len());
     }
+    ui_tree.incomplete_windows = incomplete_windows;
 
     info!(
-        "Sending UI tree with {} elements to the main thread...",
-        ui_tree.get_elements().len()
+        "Sending UI tree with {} elements ({} incomplete windows) to the main thread...",
+        ui_tree.get_elements().len(),
+        ui_tree.get_incomplete_windows().len()
     );
     match tx.send(Ok(ui_tree)) {
         Ok(_) => {
@@ -766,47 +1223,206 @@ pub fn get_all_elements_par_xml(
     };
 }
 
-#[allow(clippy::too_many_arguments)]
-fn get_element(
-    tree: &mut UITreeMap<()>,
-    ui_elements: &mut Vec<UIElementInTree>,
+/// Time a parallel worker gets past the window budget before its window is
+/// abandoned, so windows that stop at their deadline are not cut off.
+const UNRESPONSIVE_GRACE: Duration = Duration::from_secs(1);
+
+/// How often the coordinator checks on its workers while waiting for results.
+const WORKER_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Messages from the workers of a parallel walk to the coordinator.
+enum WorkerMessage {
+    Started {
+        position: usize,
+        worker: usize,
+    },
+    Finished {
+        position: usize,
+        result: Box<Result<UITree, UITreeError>>,
+    },
+}
+
+/// Everything a worker of a parallel walk needs, shared by all workers.
+struct WorkerPool<F> {
+    queue: Arc<Mutex<VecDeque<(usize, SaveUIElement, String)>>>,
+    make_backend: Arc<F>,
+    options: Arc<WalkOptions>,
+    progress: Arc<ProgressTracker>,
+    cancel: Option<Arc<AtomicBool>>,
+}
+
+impl<F> WorkerPool<F> {
+    /// Starts a worker that walks queued windows until the queue is empty.
+    fn spawn<B>(&self, worker: usize, tx: Sender<WorkerMessage>) -> std::thread::JoinHandle<()>
+    where
+        B: UIBackend,
+        F: Fn() -> Result<B, UITreeError> + Send + Sync + 'static,
+    {
+        let queue = Arc::clone(&self.queue);
+        let make_backend = Arc::clone(&self.make_backend);
+        let options = Arc::clone(&self.options);
+        let progress = Arc::clone(&self.progress);
+        let cancel = self.cancel.clone();
+        std::thread::spawn(move || {
+            let next_window = || queue.lock().unwrap_or_else(|e| e.into_inner()).pop_front();
+            let backend = match make_backend() {
+                Ok(b) => b,
+                Err(e) => {
+                    // Report the error once; the remaining windows are left to other workers
+                    if let Some((position, _, _)) = next_window() {
+                        let _ = tx.send(WorkerMessage::Finished {
+                            position,
+                            result: Box::new(Err(e)),
+                        });
+                    }
+                    return;
+                }
+            };
+            while let Some((position, element, rt_id)) = next_window() {
+                debug!("Processing window: '{}'", element.get_name());
+                if tx
+                    .send(WorkerMessage::Started { position, worker })
+                    .is_err()
+                {
+                    return;
+                }
+                let deadline = options.get_window_budget().map(|b| Instant::now() + b);
+                let result = walk_tree(
+                    &backend,
+                    Some(element),
+                    Some(rt_id),
+                    &options,
+                    cancel.as_ref(),
+                    deadline,
+                    &progress,
+                );
+                if tx
+                    .send(WorkerMessage::Finished {
+                        position,
+                        result: Box::new(result),
+                    })
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        })
+    }
+}
+
+/// State shared by all recursion levels of a single tree walk.
+struct WalkContext<'a, B: UIBackend> {
+    backend: &'a B,
+    tree: UITreeMap<()>,
+    ui_elements: Vec<UIElementInTree>,
+    xml_writer: Writer<Cursor<Vec<u8>>>,
+    tree_path: String,
+    /// Whether the walk starts at the desktop, so level 1 holds the top-level
+    /// windows the window scope applies to.
+    from_desktop: bool,
+    /// Name of the top-level window being walked, for progress reporting.
+    current_window: String,
+    /// Framework id and framework depth of the elements on the current path.
+    frameworks: Vec<(String, usize)>,
+    /// Key of the walk root, reused for its level-0 copy.
+    root_rt_id: String,
+    rt_ids: RtIdAllocator,
+    /// Shares the class, control type and framework strings across elements.
+    strings: StringInterner,
+    options: &'a WalkOptions,
+    cancel: Option<&'a Arc<AtomicBool>>,
+    deadline: Option<Instant>,
+    timed_out: bool,
+    /// Deadline of the top-level window being walked, from the window budget.
+    window_deadline: Option<Instant>,
+    window_timed_out: bool,
+    incomplete_windows: Vec<IncompleteWindow>,
+    progress: &'a ProgressTracker,
+}
+
+impl<B: UIBackend> WalkContext<'_, B> {
+    fn is_cancelled(&self) -> bool {
+        self.cancel.is_some_and(|c| c.load(Ordering::Relaxed))
+    }
+
+    /// Returns `true` when the walk was cancelled or ran past its deadline.
+    fn should_stop(&mut self) -> bool {
+        if self.is_cancelled() {
+            return true;
+        }
+        if let Some(deadline) = self.deadline
+            && Instant::now() >= deadline
+        {
+            self.timed_out = true;
+        }
+        self.timed_out
+    }
+
+    /// Returns `true` when the current top-level window ran past its budget.
+    /// The rest of the window is skipped, the walk goes on with the next one.
+    fn window_over_budget(&mut self) -> bool {
+        if let Some(deadline) = self.window_deadline
+            && Instant::now() >= deadline
+        {
+            self.window_timed_out = true;
+        }
+        self.window_timed_out
+    }
+
+    fn into_ui_tree(self) -> UITree {
+        let xml_dom_tree =
+            String::from_utf8(self.xml_writer.into_inner().into_inner()).unwrap_or_default();
+
+        let mut ui_elements = self.ui_elements;
+        info!("Sorting UI elements by z-order and size...");
+        walker_common::sort_elements(&mut ui_elements);
+
+        let mut tree = UITree::new(self.tree, xml_dom_tree, ui_elements);
+        tree.xml_attributes = self.options.get_xml_attributes().to_vec();
+        tree.tree_view = self.options.get_tree_view();
+        tree.incomplete_windows = self.incomplete_windows;
+        tree
+    }
+}
+
+fn get_element<B: UIBackend>(
+    ctx: &mut WalkContext<'_, B>,
     parent: usize,
-    walker: &UITreeWalker,
-    element: &UIElement,
-    xml_writer: &mut Writer<Cursor<Vec<u8>>>,
+    element: &B::Element,
     level: usize,
     mut z_order: usize,
-    max_depth: Option<usize>,
-    calling_window_caption: Option<&str>,
-    target_window_caption: Option<&str>,
-    tree_path: &mut String,
-    cancel: Option<&Arc<AtomicBool>>,
 ) {
-    // Check cancellation flag before processing each element
-    if cancel.is_some_and(|c| c.load(Ordering::Relaxed)) {
+    // Check cancellation flag and deadline before processing each element
+    if ctx.should_stop() {
         return;
     }
 
-    if let Some(limit) = max_depth
+    if let Some(limit) = ctx.options.get_max_depth()
         && level > limit
     {
         return;
     }
+    if level > 1 && ctx.window_over_budget() {
+        return;
+    }
+
+    let element_name = ctx.backend.name(element);
+    if level == 1 {
+        ctx.current_window.clone_from(&element_name);
+    }
 
-    let element_count = ui_elements.len();
+    let element_count = ctx.progress.element_processed(&ctx.current_window, level);
     if element_count.is_multiple_of(100) {
         info!("Processed {} UI elements so far...", element_count);
     }
 
-    let element_name = element.get_name().unwrap_or_default();
-
-    if let Some(caption) = calling_window_caption
+    if let Some(caption) = ctx.options.get_calling_window_caption()
         && element_name == caption
     {
         trace!("Skipping element with caption: {}", caption);
         return;
     }
-    let prev_tree_path_len = tree_path.len();
+    let prev_tree_path_len = ctx.tree_path.len();
 
     if level > 0 {
         let name = if element_name.is_empty() {
@@ -815,48 +1431,61 @@ fn get_element(
             &element_name
         };
 
-        if tree_path.is_empty() {
-            tree_path.push_str(name);
+        if ctx.tree_path.is_empty() {
+            ctx.tree_path.push_str(name);
         } else {
-            tree_path.push('\\');
-            tree_path.push_str(name);
+            ctx.tree_path.push('\\');
+            ctx.tree_path.push_str(name);
         }
-        trace!("Current tree path: {}", tree_path);
-        if let Some(target_caption) = target_window_caption
-            && !tree_path.contains(target_caption)
+        trace!("Current tree path: {}", ctx.tree_path);
+        if let Some(target_caption) = ctx.options.get_target_window_caption()
+            && !ctx.tree_path.contains(target_caption)
         {
             trace!(
                 "Skipping element with caption: {} in tree path {}, looking for target caption: {}",
-                name, tree_path, target_caption
+                name, ctx.tree_path, target_caption
             );
-            tree_path.truncate(prev_tree_path_len);
+            ctx.tree_path.truncate(prev_tree_path_len);
             return;
         }
     }
 
     let effective_z_order = if level == 0 { 999 } else { z_order };
-    let ui_elem_props = SaveUIElement::new(element, level, effective_z_order);
-    let runtime_id = format_runtime_id(ui_elem_props.get_runtime_id());
-    let item = walker_common::format_node_item(&ui_elem_props, &runtime_id);
-
-    let parent = tree.add_child(parent, item.as_str(), runtime_id.as_str(), ());
+    let mut ui_elem_props = ctx.backend.properties(element, level, effective_z_order);
 
-    let control_type_tag = if ui_elem_props.get_control_type().is_empty() {
-        "Unknown".to_string()
+    if level == 1
+        && ctx.from_desktop
+        && let Some(scope) = ctx.options.get_window_scope()
+        && !scope.matches(&ui_elem_props, |pid| ctx.backend.executable_name(pid))
+    {
+        trace!(
+            "Skipping top-level window outside the window scope: {}",
+            element_name
+        );
+        ctx.tree_path.truncate(prev_tree_path_len);
+        return;
+    }
+    ui_elem_props.intern(&mut ctx.strings);
+    let runtime_id = if level == 0 {
+        ctx.root_rt_id.clone()
     } else {
-        ui_elem_props.get_control_type().to_string()
+        ctx.rt_ids.rt_id(ui_elem_props.get_runtime_id())
     };
-    let mut start = BytesStart::new(&control_type_tag);
-    start.push_attribute(("RtID", runtime_id.as_str()));
-    let z_order_str = effective_z_order.to_string();
-    start.push_attribute(("z-order", z_order_str.as_str()));
-    start.push_attribute(("Name", ui_elem_props.get_name()));
-    if ui_elem_props.get_control_type().is_empty() {
-        start.push_attribute(("ControlType", "No control type defined"));
-    } else {
-        start.push_attribute(("ControlType", ui_elem_props.get_control_type()));
-    }
-    if let Err(e) = xml_writer.write_event(Event::Start(start)) {
+    let item = walker_common::format_node_item(&ui_elem_props, &runtime_id);
+
+    let parent = ctx
+        .tree
+        .add_child(parent, item.as_str(), runtime_id.as_str(), ());
+
+    let control_type_tag = control_type_tag(&ui_elem_props).to_string();
+    let start = element_start_tag(
+        &control_type_tag,
+        &ui_elem_props,
+        &runtime_id,
+        effective_z_order,
+        ctx.options,
+    );
+    if let Err(e) = ctx.xml_writer.write_event(Event::Start(start)) {
         error!(
             "Failed to write XML start event for '{}': {}",
             control_type_tag, e
@@ -864,32 +1493,46 @@ fn get_element(
         return;
     }
 
-    let ui_elem_in_tree = UIElementInTree::new(ui_elem_props, parent);
-    ui_elements.push(ui_elem_in_tree);
+    let framework_depth = match ctx.frameworks.last() {
+        Some((framework_id, depth)) if framework_id == ui_elem_props.get_framework_id() => {
+            depth + 1
+        }
+        _ => 0,
+    };
+    let descend = ctx
+        .options
+        .get_pruning()
+        .should_descend(&ui_elem_props, framework_depth);
+    if !descend {
+        trace!("Pruning subtree below element: {}", element_name);
+    }
+    ctx.frameworks.push((
+        ui_elem_props.get_framework_id().to_string(),
+        framework_depth,
+    ));
+
+    let elem_index = ctx.ui_elements.len();
+    let ui_elem_in_tree = UIElementInTree::new(ui_elem_props, parent).with_rt_id(runtime_id);
+    ctx.ui_elements.push(ui_elem_in_tree);
+    if level == 1 && ctx.from_desktop {
+        ctx.window_deadline = ctx.options.get_window_budget().map(|b| Instant::now() + b);
+    }
 
-    if let Ok(child) = walker.get_first_child(element) {
-        trace!(
-            "Found child element: {}",
-            child.get_name().unwrap_or("Unknown".to_string())
-        );
-        get_element(
-            tree,
-            ui_elements,
-            parent,
-            walker,
-            &child,
-            xml_writer,
-            level + 1,
-            z_order,
-            max_depth,
-            calling_window_caption,
-            target_window_caption,
-            tree_path,
-            cancel,
-        );
+    // Children past the depth limit would be dropped anyway, so the backend
+    // is not even asked for them
+    let at_max_depth = ctx
+        .options
+        .get_max_depth()
+        .is_some_and(|limit| level >= limit);
+    if descend
+        && !at_max_depth
+        && let Some(child) = ctx.backend.first_child(element)
+    {
+        trace!("Found child element: {}", ctx.backend.name(&child));
+        get_element(ctx, parent, &child, level + 1, z_order);
         let mut next = child;
         let mut sibling_count: usize = 0;
-        while let Ok(sibling) = walker.get_next_sibling(&next) {
+        while let Some(sibling) = ctx.backend.next_sibling(&next) {
             sibling_count += 1;
             if sibling_count > MAX_SIBLINGS {
                 warn!(
@@ -898,48 +1541,150 @@ fn get_element(
                 );
                 break;
             }
-            // Check cancellation in sibling loop
-            if cancel.is_some_and(|c| c.load(Ordering::Relaxed)) {
-                return;
+            // Check cancellation and deadline in sibling loop; the element is
+            // still closed so a timed-out walk leaves well-formed XML behind
+            if ctx.should_stop() {
+                break;
+            }
+            if level >= 1 && ctx.window_over_budget() {
+                break;
             }
             if level + 1 == 1 {
                 z_order += 1;
             }
-            trace!(
-                "Found sibling element: {}",
-                sibling.get_name().unwrap_or("Unknown".to_string())
-            );
-            get_element(
-                tree,
-                ui_elements,
-                parent,
-                walker,
-                &sibling,
-                xml_writer,
-                level + 1,
-                z_order,
-                max_depth,
-                calling_window_caption,
-                target_window_caption,
-                tree_path,
-                cancel,
-            );
+            trace!("Found sibling element: {}", ctx.backend.name(&sibling));
+            get_element(ctx, parent, &sibling, level + 1, z_order);
             next = sibling;
         }
     }
 
-    if let Err(e) = xml_writer.write_event(Event::End(BytesEnd::new(&control_type_tag))) {
+    if let Err(e) = ctx
+        .xml_writer
+        .write_event(Event::End(BytesEnd::new(&control_type_tag)))
+    {
         error!(
             "Failed to write XML end event for '{}': {}",
             control_type_tag, e
         );
     }
-    tree_path.truncate(prev_tree_path_len);
+    ctx.frameworks.pop();
+    ctx.tree_path.truncate(prev_tree_path_len);
+
+    if level == 1 && ctx.from_desktop {
+        if ctx.window_timed_out {
+            let window = &ctx.ui_elements[elem_index];
+            warn!(
+                "Window '{}' exceeded its time budget, keeping what was walked",
+                window.get_element_props().get_name()
+            );
+            ctx.incomplete_windows.push(IncompleteWindow::new(
+                window.get_element_props().get_name().to_string(),
+                window.get_rt_id().to_string(),
+                IncompleteReason::TimedOut,
+            ));
+        }
+        ctx.window_deadline = None;
+        ctx.window_timed_out = false;
+    }
+}
+
+/// Tag of an element in the XML DOM: its control type, or `Unknown`.
+fn control_type_tag(props: &SaveUIElement) -> &str {
+    if props.get_control_type().is_empty() {
+        "Unknown"
+    } else {
+        props.get_control_type()
+    }
+}
+
+/// Attributes of an element in the XML DOM, in the order they are written.
+fn element_attributes<'a>(
+    props: &'a SaveUIElement,
+    runtime_id: &'a str,
+    z_order: usize,
+    xml_attributes: &[XmlAttribute],
+) -> Vec<(&'static str, Cow<'a, str>)> {
+    let mut attributes = vec![
+        ("RtID", Cow::Borrowed(runtime_id)),
+        ("z-order", Cow::Owned(z_order.to_string())),
+        ("Name", Cow::Borrowed(props.get_name())),
+    ];
+    if props.get_control_type().is_empty() {
+        attributes.push(("ControlType", Cow::Borrowed("No control type defined")));
+    } else {
+        attributes.push(("ControlType", Cow::Borrowed(props.get_control_type())));
+    }
+
+    for attribute in XmlAttribute::ALL {
+        if !xml_attributes.contains(&attribute) {
+            continue;
+        }
+        match attribute {
+            XmlAttribute::AutomationId => {
+                push_non_empty(&mut attributes, "AutomationId", props.get_automation_id())
+            }
+            XmlAttribute::ClassName => {
+                push_non_empty(&mut attributes, "ClassName", props.get_classname())
+            }
+            XmlAttribute::FrameworkId => {
+                push_non_empty(&mut attributes, "FrameworkId", props.get_framework_id())
+            }
+            XmlAttribute::LocalizedControlType => push_non_empty(
+                &mut attributes,
+                "LocalizedControlType",
+                props.get_localized_control_type(),
+            ),
+            XmlAttribute::Level => {
+                attributes.push(("Level", Cow::Owned(props.get_level().to_string())))
+            }
+            XmlAttribute::BoundingRectangle => {
+                let rect = props.get_bounding_rectangle();
+                let width = rect.get_right() - rect.get_left();
+                let height = rect.get_bottom() - rect.get_top();
+                attributes.push(("X", Cow::Owned(rect.get_left().to_string())));
+                attributes.push(("Y", Cow::Owned(rect.get_top().to_string())));
+                attributes.push(("Width", Cow::Owned(width.to_string())));
+                attributes.push(("Height", Cow::Owned(height.to_string())));
+            }
+        }
+    }
+    attributes
+}
+
+fn push_non_empty<'a>(
+    attributes: &mut Vec<(&'static str, Cow<'a, str>)>,
+    name: &'static str,
+    value: &'a str,
+) {
+    if !value.is_empty() {
+        attributes.push((name, Cow::Borrowed(value)));
+    }
+}
+
+/// Builds the XML start tag for an element: the fixed `RtID`, `z-order`, `Name`
+/// and `ControlType` attributes, followed by the optional attributes selected
+/// in `options`. Optional string attributes are left out when empty.
+fn element_start_tag<'a>(
+    tag: &'a str,
+    props: &SaveUIElement,
+    runtime_id: &str,
+    z_order: usize,
+    options: &WalkOptions,
+) -> BytesStart<'a> {
+    let mut start = BytesStart::new(tag);
+    for (key, value) in element_attributes(props, runtime_id, z_order, options.get_xml_attributes())
+    {
+        start.push_attribute((key, value.as_ref()));
+    }
+    start
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::backend::FakeBackend;
+    use crate::progress::{ProgressUpdate, WalkProgress};
+    use crate::walk_options::{PruningPolicy, WindowScope};
 
     const TEST_XML: &str = r#"<Window RtID="1.2.3" Name="MainWindow" ControlType="Window" z-order="999">
   <Panel RtID="4.5.6" Name="Header" ControlType="Panel" z-order="0">
@@ -961,15 +1706,41 @@ mod tests {
         let content = tree.add_child(0, "Content", "13.14.15", ());
         tree.add_child(content, "Username", "16.17.18", ());
 
+        // same properties as in TEST_XML, in tree order
+        let props = [
+            ("MainWindow", "Window", 999),
+            ("Header", "Panel", 0),
+            ("OK", "Button", 0),
+            ("Cancel", "Button", 1),
+            ("Content", "Panel", 1),
+            ("Username", "Edit", 0),
+        ];
         let mut elements = Vec::new();
-        for i in 0..tree.node_count() {
-            let elem = SaveUIElement::default();
-            elements.push(UIElementInTree::new(elem, i));
+        for (i, (name, control_type, z_order)) in props.into_iter().enumerate() {
+            let mut elem = SaveUIElement::default()
+                .with_name(name)
+                .with_control_type(control_type);
+            elem.set_position(0, z_order);
+            elements.push(
+                UIElementInTree::new(elem, i).with_rt_id(tree.node(i).runtime_id.to_string()),
+            );
         }
 
         UITree::new(tree, TEST_XML.to_string(), elements)
     }
 
+    #[test]
+    fn test_pretty_string_indents_children() {
+        let tree = build_test_tree();
+        let pretty = tree.to_pretty_string();
+        let lines: Vec<&str> = pretty.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[0].starts_with("MainWindow: "));
+        assert!(lines[1].starts_with("  Header: "));
+        assert!(lines[2].starts_with("    OK: "));
+        assert!(lines[5].starts_with("    Username: "));
+    }
+
     #[test]
     fn test_xpath_generation_returns_valid_xpath() {
         let tree = build_test_tree();
@@ -1009,6 +1780,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_xpath_candidates_resolve_to_their_element() {
+        let tree = build_test_tree();
+        for idx in 1..tree.get_tree().node_count() {
+            let candidates = tree.get_xpath_candidates_for_element(idx).unwrap();
+            let best = &candidates[0];
+            assert!(best.is_unique(), "no unique locator for node {}", idx);
+            let found = tree.get_element_by_xpath(best.get_xpath()).unwrap();
+            assert!(std::ptr::eq(
+                found,
+                tree.element_for_node(idx).get_element_props()
+            ));
+        }
+    }
+
     #[test]
     fn test_get_element_by_xpath_not_found() {
         let tree = build_test_tree();
@@ -1025,6 +1811,32 @@ mod tests {
         assert_eq!(found.unwrap().len(), 2);
     }
 
+    #[test]
+    fn test_get_element_by_xpath_with_variables() {
+        let tree = build_test_tree();
+        let vars = XpathVariables::new().with("name", "Cancel");
+        let found = tree.get_element_by_xpath_with_variables("//Button[@Name=$name]", &vars);
+        assert_eq!(found.map(|e| e.get_name()), Some("Cancel"));
+
+        // a quote-laden value is compared literally instead of rewriting the query
+        let vars = XpathVariables::new().with("name", "' or '1'='1");
+        assert!(
+            tree.get_elements_by_xpath_with_variables("//Button[@Name=$name]", &vars)
+                .is_none()
+        );
+        // without a binding the expression does not compile
+        assert!(tree.get_element_by_xpath("//Button[@Name=$name]").is_none());
+    }
+
+    #[test]
+    fn test_get_element_by_xpath_with_locator_functions() {
+        let tree = build_test_tree();
+        let found = tree.get_element_by_xpath("//Button[b:icontains(@Name, 'CANC')]");
+        assert_eq!(found.map(|e| e.get_name()), Some("Cancel"));
+        let found = tree.get_elements_by_xpath("//*[b:matches-ci(@Name, '^(ok|username)$')]");
+        assert_eq!(found.map(|v| v.len()), Some(2));
+    }
+
     #[test]
     fn test_get_elements_by_xpath_none() {
         let tree = build_test_tree();
@@ -1065,4 +1877,870 @@ mod tests {
         assert!(found.is_some());
         assert_eq!(found.unwrap().len(), 2);
     }
+
+    #[test]
+    fn test_clone_shares_data_until_mutated() {
+        let tree = build_test_tree();
+        let mut cloned = tree.clone();
+        assert!(std::ptr::eq(tree.get_elements(), cloned.get_elements()));
+        assert!(std::ptr::eq(
+            tree.get_xml_dom_tree(),
+            cloned.get_xml_dom_tree()
+        ));
+
+        cloned.get_elements_mut().pop();
+        assert_eq!(cloned.get_elements().len() + 1, tree.get_elements().len());
+        assert!(!std::ptr::eq(tree.get_elements(), cloned.get_elements()));
+    }
+
+    fn fake_elem(name: &str, control_type: &str, rtid: i32) -> SaveUIElement {
+        SaveUIElement::default()
+            .with_name(name)
+            .with_control_type(control_type)
+            .with_runtime_id(vec![42, rtid])
+    }
+
+    /// Desktop with three top-level windows:
+    /// `App` (OK button, edit), `UI Explore` (button) and `Other` (no children).
+    fn build_fake_desktop() -> FakeBackend {
+        let mut backend = FakeBackend::new(fake_elem("Desktop", "Pane", 1));
+        let app = backend.add_child(0, fake_elem("App", "Window", 2));
+        backend.add_child(app, fake_elem("OK", "Button", 3));
+        backend.add_child(app, fake_elem("Username", "Edit", 4));
+        let explore = backend.add_child(0, fake_elem("UI Explore", "Window", 5));
+        backend.add_child(explore, fake_elem("Refresh", "Button", 6));
+        backend.add_child(0, fake_elem("Other", "Pane", 7));
+        backend
+    }
+
+    fn walk_fake(
+        backend: &FakeBackend,
+        max_depth: Option<usize>,
+        calling_window_caption: Option<&str>,
+        target_window_caption: Option<&str>,
+        cancel: Option<Arc<AtomicBool>>,
+    ) -> Result<UITree, UITreeError> {
+        let (tx, rx) = channel();
+        get_all_elements_xml_with_backend(
+            tx,
+            backend,
+            None,
+            &WalkOptions::default()
+                .with_max_depth(max_depth)
+                .with_calling_window_caption(calling_window_caption.map(str::to_string))
+                .with_target_window_caption(target_window_caption.map(str::to_string)),
+            cancel,
+        );
+        rx.recv().unwrap()
+    }
+
+    fn count(tree: &UITree, xpath: &str) -> usize {
+        tree.get_elements_by_xpath(xpath).map_or(0, |v| v.len())
+    }
+
+    #[test]
+    fn test_walk_fake_backend_builds_xml() {
+        let tree = walk_fake(&build_fake_desktop(), None, None, None, None).unwrap();
+        let xml = tree.get_xml_dom_tree();
+        assert!(xml.starts_with(r#"<Pane RtID="42-1" z-order="999" Name="Desktop""#));
+        assert_eq!(count(&tree, "//Window"), 2);
+        assert_eq!(count(&tree, "//Button"), 2);
+        assert_eq!(count(&tree, "/Pane/Window[@Name='App']/Edit"), 1);
+
+        let edit = tree.get_element_by_xpath("//Edit").unwrap();
+        assert_eq!(edit.get_name(), "Username");
+        assert_eq!(edit.get_level(), 2);
+    }
+
+    #[test]
+    fn test_walk_interns_element_strings() {
+        let tree = walk_fake(&build_fake_desktop(), None, None, None, None).unwrap();
+        let buttons = tree.get_elements_by_xpath("//Button").unwrap();
+        assert_eq!(buttons.len(), 2);
+        assert!(std::ptr::eq(
+            buttons[0].get_control_type(),
+            buttons[1].get_control_type()
+        ));
+    }
+
+    #[test]
+    fn test_walk_fake_backend_z_order() {
+        let tree = walk_fake(&build_fake_desktop(), None, None, None, None).unwrap();
+        let z = |xpath: &str| tree.get_element_by_xpath(xpath).unwrap().get_z_order();
+        assert_eq!(z("//Window[@Name='App']"), 0);
+        assert_eq!(z("//Edit"), 0);
+        assert_eq!(z("//Window[@Name='UI Explore']"), 1);
+        assert_eq!(z("//Button[@Name='Refresh']"), 1);
+        assert_eq!(z("//Pane[@Name='Other']"), 2);
+    }
+
+    #[test]
+    fn test_walk_fake_backend_skips_calling_window() {
+        let tree = walk_fake(&build_fake_desktop(), None, Some("UI Explore"), None, None).unwrap();
+        assert_eq!(count(&tree, "//*[@Name='UI Explore']"), 0);
+        assert_eq!(count(&tree, "//Button"), 1);
+        // Skipped windows still consume a z-order slot
+        let other = tree.get_element_by_xpath("//Pane[@Name='Other']").unwrap();
+        assert_eq!(other.get_z_order(), 2);
+    }
+
+    #[test]
+    fn test_walk_fake_backend_target_window_filter() {
+        let tree = walk_fake(&build_fake_desktop(), None, None, Some("App"), None).unwrap();
+        assert_eq!(count(&tree, "//Window"), 1);
+        assert_eq!(count(&tree, "//Button[@Name='OK']"), 1);
+        assert_eq!(count(&tree, "//Button[@Name='Refresh']"), 0);
+        assert_eq!(count(&tree, "//Pane[@Name='Other']"), 0);
+    }
+
+    #[test]
+    fn test_walk_fake_backend_max_depth() {
+        let tree = walk_fake(&build_fake_desktop(), Some(1), None, None, None).unwrap();
+        assert_eq!(count(&tree, "//Window"), 2);
+        assert_eq!(count(&tree, "//Button"), 0);
+        assert_eq!(count(&tree, "//Edit"), 0);
+    }
+
+    #[test]
+    fn test_walk_fake_backend_cancelled() {
+        let cancel = Arc::new(AtomicBool::new(true));
+        let result = walk_fake(&build_fake_desktop(), None, None, None, Some(cancel));
+        assert!(matches!(result, Err(UITreeError::Cancelled)));
+    }
+
+    #[test]
+    fn test_walk_fake_backend_from_root_element() {
+        let backend = build_fake_desktop();
+        let (tx, rx) = channel();
+        get_all_elements_xml_with_backend(
+            tx,
+            &backend,
+            Some(fake_elem("App", "Window", 2)),
+            &WalkOptions::default(),
+            None,
+        );
+        let tree = rx.recv().unwrap().unwrap();
+        assert!(tree.get_xml_dom_tree().starts_with("<Window"));
+        assert_eq!(count(&tree, "//Button"), 1);
+        assert_eq!(count(&tree, "//Pane"), 0);
+    }
+
+    #[test]
+    fn test_par_walk_fake_backend_merges_windows() {
+        let backend = build_fake_desktop();
+        let (tx, rx) = channel();
+        get_all_elements_par_xml_with_backend(
+            tx,
+            move || Ok(backend.clone()),
+            WalkOptions::default().with_calling_window_caption(Some("UI Explore".to_string())),
+            None,
+        );
+        let tree = rx.recv().unwrap().unwrap();
+        assert_eq!(count(&tree, "//Window[@Name='App']"), 1);
+        assert_eq!(count(&tree, "//Window[@Name='App']/Button"), 1);
+        assert_eq!(count(&tree, "//Window[@Name='App']/Edit"), 1);
+        assert_eq!(count(&tree, "//*[@Name='UI Explore']"), 0);
+        assert_eq!(count(&tree, "//Pane[@Name='Other']"), 1);
+    }
+
+    #[test]
+    fn test_xpath_dom_matches_rendered_xml() {
+        let tree = par_walk(build_fake_desktop(), WalkOptions::default());
+        // merged windows keep their place among their siblings
+        let first = tree.get_element_by_xpath("/Pane/*[1]").unwrap();
+        assert_eq!(first.get_name(), "App");
+        for xpath in [
+            "//Button",
+            "/Pane/Window[2]/Button",
+            "//*[@Level='0']",
+            "//Edit/..",
+        ] {
+            let in_xml = xmlutil::eval_xpath(xpath, tree.get_xml_dom_tree()).get_result_count();
+            assert_eq!(count(&tree, xpath), in_xml, "{}", xpath);
+        }
+    }
+
+    fn par_walk(backend: FakeBackend, options: WalkOptions) -> UITree {
+        let (tx, rx) = channel();
+        get_all_elements_par_xml_with_backend(tx, move || Ok(backend.clone()), options, None);
+        rx.recv().unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_par_walk_bounded_pool_is_deterministic() {
+        let single = par_walk(
+            build_fake_desktop(),
+            WalkOptions::default().with_max_workers(1),
+        );
+        let many = par_walk(
+            build_fake_desktop(),
+            WalkOptions::default().with_max_workers(8),
+        );
+        assert_eq!(single.get_xml_dom_tree(), many.get_xml_dom_tree());
+        assert_eq!(single.get_elements().len(), many.get_elements().len());
+        assert!(single.is_complete());
+        assert_eq!(count(&single, "//Window[@Name='App']/Edit"), 1);
+    }
+
+    #[test]
+    fn test_par_walk_reports_windows_over_budget() {
+        let tree = par_walk(
+            build_fake_desktop(),
+            WalkOptions::default().with_window_budget(Some(std::time::Duration::ZERO)),
+        );
+        // the windows themselves are kept, their content is missing
+        assert_eq!(count(&tree, "//Window"), 2);
+        assert_eq!(count(&tree, "//Button"), 0);
+        let incomplete: Vec<_> = tree
+            .get_incomplete_windows()
+            .iter()
+            .map(|w| (w.get_name(), w.get_reason().clone()))
+            .collect();
+        // `Other` has no children and is complete
+        assert_eq!(
+            incomplete,
+            vec![
+                ("App", IncompleteReason::TimedOut),
+                ("UI Explore", IncompleteReason::TimedOut)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serial_walk_keeps_windows_over_budget() {
+        let (tx, rx) = channel();
+        get_all_elements_xml_with_backend(
+            tx,
+            &build_fake_desktop(),
+            None,
+            &WalkOptions::default().with_window_budget(Some(Duration::ZERO)),
+            None,
+        );
+        let tree = rx.recv().unwrap().unwrap();
+        assert_eq!(count(&tree, "//Window"), 2);
+        assert_eq!(count(&tree, "//Button"), 0);
+        assert!(!tree.is_complete());
+        let names: Vec<_> = tree
+            .get_incomplete_windows()
+            .iter()
+            .map(|w| w.get_name())
+            .collect();
+        assert_eq!(names, vec!["App", "UI Explore"]);
+    }
+
+    /// Blocks forever when asked for the children of one element, like a
+    /// UI Automation call into a hung application.
+    #[derive(Clone)]
+    struct HangingBackend {
+        inner: FakeBackend,
+        hang_on: usize,
+    }
+
+    impl UIBackend for HangingBackend {
+        type Element = usize;
+
+        fn root(&self) -> Result<usize, UITreeError> {
+            self.inner.root()
+        }
+
+        fn resolve(&self, props: &SaveUIElement) -> Result<usize, UITreeError> {
+            self.inner.resolve(props)
+        }
+
+        fn first_child(&self, element: &usize) -> Option<usize> {
+            if *element == self.hang_on {
+                loop {
+                    std::thread::park();
+                }
+            }
+            self.inner.first_child(element)
+        }
+
+        fn next_sibling(&self, element: &usize) -> Option<usize> {
+            self.inner.next_sibling(element)
+        }
+
+        fn name(&self, element: &usize) -> String {
+            self.inner.name(element)
+        }
+
+        fn properties(&self, element: &usize, level: usize, z_order: usize) -> SaveUIElement {
+            self.inner.properties(element, level, z_order)
+        }
+
+        fn executable_name(&self, process_id: u32) -> Option<String> {
+            self.inner.executable_name(process_id)
+        }
+    }
+
+    #[test]
+    fn test_par_walk_abandons_unresponsive_window() {
+        // node 1 is the `App` window
+        let backend = HangingBackend {
+            inner: build_fake_desktop(),
+            hang_on: 1,
+        };
+        let (tx, rx) = channel();
+        get_all_elements_par_xml_with_backend(
+            tx,
+            move || Ok(backend.clone()),
+            WalkOptions::default()
+                .with_max_workers(1)
+                .with_window_budget(Some(Duration::from_millis(100))),
+            None,
+        );
+        let tree = rx.recv().unwrap().unwrap();
+        // the replacement worker still walked the windows behind `App`
+        assert_eq!(count(&tree, "//Window[@Name='UI Explore']/Button"), 1);
+        assert_eq!(count(&tree, "//Window[@Name='App']/*"), 0);
+        let incomplete: Vec<_> = tree
+            .get_incomplete_windows()
+            .iter()
+            .map(|w| (w.get_name(), w.get_reason().clone()))
+            .collect();
+        assert_eq!(incomplete, vec![("App", IncompleteReason::Unresponsive)]);
+    }
+
+    /// Takes a while for every element, so walks run into their budget halfway.
+    #[derive(Clone)]
+    struct SlowBackend(FakeBackend);
+
+    impl UIBackend for SlowBackend {
+        type Element = usize;
+
+        fn root(&self) -> Result<usize, UITreeError> {
+            self.0.root()
+        }
+
+        fn resolve(&self, props: &SaveUIElement) -> Result<usize, UITreeError> {
+            self.0.resolve(props)
+        }
+
+        fn first_child(&self, element: &usize) -> Option<usize> {
+            self.0.first_child(element)
+        }
+
+        fn next_sibling(&self, element: &usize) -> Option<usize> {
+            self.0.next_sibling(element)
+        }
+
+        fn name(&self, element: &usize) -> String {
+            self.0.name(element)
+        }
+
+        fn properties(&self, element: &usize, level: usize, z_order: usize) -> SaveUIElement {
+            std::thread::sleep(Duration::from_millis(10));
+            self.0.properties(element, level, z_order)
+        }
+
+        fn executable_name(&self, process_id: u32) -> Option<String> {
+            self.0.executable_name(process_id)
+        }
+    }
+
+    #[test]
+    fn test_par_walk_keeps_partial_window_over_budget() {
+        let mut inner = FakeBackend::new(fake_elem("Desktop", "Pane", 1));
+        let app = inner.add_child(0, fake_elem("App", "Window", 2));
+        for i in 0..50 {
+            inner.add_child(app, fake_elem(&format!("Button {}", i), "Button", 10 + i));
+        }
+        let backend = SlowBackend(inner);
+        let (tx, rx) = channel();
+        get_all_elements_par_xml_with_backend(
+            tx,
+            move || Ok(backend.clone()),
+            WalkOptions::default().with_window_budget(Some(Duration::from_millis(100))),
+            None,
+        );
+        let tree = rx.recv().unwrap().unwrap();
+        // like the serial walk, the buttons walked in time are kept
+        let buttons = count(&tree, "//Window[@Name='App']/Button");
+        assert!(buttons > 0 && buttons < 50, "{} buttons", buttons);
+        let incomplete: Vec<_> = tree
+            .get_incomplete_windows()
+            .iter()
+            .map(|w| (w.get_name(), w.get_reason().clone()))
+            .collect();
+        assert_eq!(incomplete, vec![("App", IncompleteReason::TimedOut)]);
+        assert_eq!(tree.get_incomplete_windows()[0].get_runtime_id(), "42-2");
+    }
+
+    #[test]
+    fn test_par_walk_keeps_tree_when_workers_fail() {
+        let backend = build_fake_desktop();
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let (tx, rx) = channel();
+        get_all_elements_par_xml_with_backend(
+            tx,
+            move || {
+                // only the coordinator gets a backend
+                if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                    Ok(backend.clone())
+                } else {
+                    Err(UITreeError::NoUIAutomation)
+                }
+            },
+            WalkOptions::default().with_max_workers(2),
+            None,
+        );
+        let tree = rx.recv().unwrap().unwrap();
+        assert_eq!(count(&tree, "//Window"), 2);
+        assert_eq!(tree.get_incomplete_windows().len(), 3);
+        assert!(
+            tree.get_incomplete_windows()
+                .iter()
+                .all(|w| matches!(w.get_reason(), IncompleteReason::Failed(_)))
+        );
+    }
+
+    #[test]
+    fn test_par_walk_reports_progress_and_partial_trees() {
+        let (tx_progress, rx_progress) = channel();
+        let tree = par_walk(
+            build_fake_desktop(),
+            WalkOptions::default()
+                .with_progress(tx_progress)
+                .with_progress_interval(1)
+                .with_partial_trees(true),
+        );
+        let events: Vec<WalkProgress> = rx_progress.try_iter().collect();
+        let updates: Vec<&ProgressUpdate> = events
+            .iter()
+            .filter_map(|e| match e {
+                WalkProgress::Update(u) => Some(u),
+                WalkProgress::WindowCompleted(_) => None,
+            })
+            .collect();
+        assert!(updates.iter().any(|u| u.current_window == "App"));
+        let last = updates.iter().map(|u| u.elements_processed).max().unwrap();
+        assert!(last >= tree.get_elements().len() - 1);
+
+        let mut completed: Vec<String> = events
+            .iter()
+            .filter_map(|e| match e {
+                WalkProgress::WindowCompleted(t) => Some(t.node(t.root()).1.get_name().to_string()),
+                WalkProgress::Update(_) => None,
+            })
+            .collect();
+        completed.sort();
+        assert_eq!(completed, vec!["App", "Other", "UI Explore"]);
+    }
+
+    #[test]
+    fn test_merge_subtrees_skips_invalid_subtree() {
+        let backend = build_fake_desktop();
+        let mut tree = walk_fake(&backend, Some(1), None, None, None).unwrap();
+        let progress = ProgressTracker::new(&WalkOptions::default());
+        let app = walk_tree(
+            &backend,
+            Some(fake_elem("App", "Window", 2)),
+            None,
+            &WalkOptions::default(),
+            None,
+            None,
+            &progress,
+        )
+        .unwrap();
+        // the tree root itself cannot be replaced
+        let broken = walk_tree(
+            &backend,
+            Some(fake_elem("Desktop", "Pane", 1)),
+            None,
+            &WalkOptions::default(),
+            None,
+            None,
+            &progress,
+        )
+        .unwrap();
+
+        let root = tree.root();
+        let results = tree.merge_subtrees(root, vec![broken, app]).unwrap();
+        assert!(results[0].is_err());
+        assert!(results[1].is_ok());
+        assert_eq!(count(&tree, "//Window[@Name='App']/Button"), 1);
+        assert_eq!(count(&tree, "//Window[@Name='UI Explore']/Button"), 0);
+        assert!(tree.get_xml_dom_tree().contains(r#"Name="OK""#));
+        for (index, _) in tree
+            .get_tree()
+            .nodes()
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| n.is_alive)
+        {
+            let (name, _) = tree.node(index);
+            assert!(!name.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_merge_patches_xpath_dom_in_place() {
+        let backend = build_fake_desktop();
+        let mut tree = walk_fake(&backend, Some(1), None, None, None).unwrap();
+        // builds the XPath document
+        assert_eq!(count(&tree, "//Button"), 0);
+        let progress = ProgressTracker::new(&WalkOptions::default());
+        let app = walk_tree(
+            &backend,
+            Some(fake_elem("App", "Window", 2)),
+            None,
+            &WalkOptions::default(),
+            None,
+            None,
+            &progress,
+        )
+        .unwrap();
+
+        let root = tree.root();
+        tree.merge_subtrees(root, vec![app]).unwrap();
+        assert!(tree.xpath_cache.lock().unwrap().is_some());
+        assert_eq!(count(&tree, "/Pane/Window[1]/Button"), 1);
+
+        // a clone starts without a document and builds it from scratch
+        let rebuilt = tree.clone();
+        for xpath in ["//*", "//Window", "//Window[@Name='App']/*", "//Pane"] {
+            assert_eq!(count(&tree, xpath), count(&rebuilt, xpath), "{}", xpath);
+        }
+    }
+
+    /// Desktop → Browser (Win32) → render widget (Chrome) → Document → Group → Link,
+    /// plus a Win32 `Notepad` window with an Edit.
+    fn browser_desktop() -> FakeBackend {
+        let chrome =
+            |name: &str, ct: &str, rtid: i32| fake_elem(name, ct, rtid).with_framework_id("Chrome");
+        let mut backend = FakeBackend::new(fake_elem("Desktop", "Pane", 1));
+        let browser = backend.add_child(
+            0,
+            fake_elem("Browser", "Window", 2).with_framework_id("Win32"),
+        );
+        let widget = backend.add_child(
+            browser,
+            chrome("Page", "Pane", 3).with_classname("Chrome_RenderWidgetHostHWND"),
+        );
+        let document = backend.add_child(widget, chrome("Doc", "Document", 4));
+        let group = backend.add_child(document, chrome("Group", "Group", 5));
+        backend.add_child(group, chrome("Link", "Hyperlink", 6));
+        let notepad = backend.add_child(
+            0,
+            fake_elem("Notepad", "Window", 7).with_framework_id("Win32"),
+        );
+        backend.add_child(
+            notepad,
+            fake_elem("Text", "Edit", 8).with_framework_id("Win32"),
+        );
+        backend
+    }
+
+    fn walk_pruned(pruning: PruningPolicy) -> UITree {
+        let (tx, rx) = channel();
+        get_all_elements_xml_with_backend(
+            tx,
+            &browser_desktop(),
+            None,
+            &WalkOptions::default().with_pruning(pruning),
+            None,
+        );
+        rx.recv().unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_walk_prunes_by_class_name() {
+        let tree =
+            walk_pruned(PruningPolicy::default().prune_class_name("Chrome_RenderWidgetHostHWND"));
+        // the pruned element itself is kept
+        assert_eq!(count(&tree, "//Pane[@Name='Page']"), 1);
+        assert_eq!(count(&tree, "//Document"), 0);
+        assert_eq!(count(&tree, "//Edit"), 1);
+    }
+
+    #[test]
+    fn test_walk_prunes_by_control_type_and_framework() {
+        let tree = walk_pruned(PruningPolicy::default().prune_control_type("Document"));
+        assert_eq!(count(&tree, "//Document"), 1);
+        assert_eq!(count(&tree, "//Group"), 0);
+
+        let tree = walk_pruned(PruningPolicy::default().prune_framework_id("Win32"));
+        assert_eq!(count(&tree, "//Window"), 2);
+        assert_eq!(count(&tree, "//Pane[@Name='Page']"), 0);
+        assert_eq!(count(&tree, "//Edit"), 0);
+    }
+
+    #[test]
+    fn test_walk_caps_depth_per_framework() {
+        let tree = walk_pruned(PruningPolicy::default().with_framework_max_depth("Chrome", 2));
+        assert_eq!(count(&tree, "//Group"), 1);
+        assert_eq!(count(&tree, "//Hyperlink"), 0);
+        assert_eq!(count(&tree, "//Edit"), 1);
+    }
+
+    #[test]
+    fn test_par_walk_applies_pruning() {
+        let backend = browser_desktop();
+        let (tx, rx) = channel();
+        get_all_elements_par_xml_with_backend(
+            tx,
+            move || Ok(backend.clone()),
+            WalkOptions::default()
+                .with_pruning(PruningPolicy::default().with_framework_max_depth("Chrome", 1)),
+            None,
+        );
+        let tree = rx.recv().unwrap().unwrap();
+        assert_eq!(count(&tree, "//Document"), 1);
+        assert_eq!(count(&tree, "//Group"), 0);
+        assert_eq!(count(&tree, "//Edit"), 1);
+    }
+
+    fn save_button_desktop() -> FakeBackend {
+        let mut backend = FakeBackend::new(fake_elem("Desktop", "Pane", 1));
+        let app = backend.add_child(0, fake_elem("Editor", "Window", 2));
+        backend.add_child(
+            app,
+            fake_elem("Speichern", "Button", 3)
+                .with_automation_id("btnSave")
+                .with_classname("Button")
+                .with_framework_id("WPF")
+                .with_localized_control_type("Schaltfläche")
+                .with_bounding_rectangle(crate::Rect::new(10, 20, 110, 50)),
+        );
+        backend
+    }
+
+    #[test]
+    fn test_walk_emits_locator_attributes_by_default() {
+        let tree = walk_fake(&save_button_desktop(), None, None, None, None).unwrap();
+        let xml = tree.get_xml_dom_tree();
+        assert!(xml.contains(
+            r#"<Button RtID="42-3" z-order="0" Name="Speichern" ControlType="Button" AutomationId="btnSave" ClassName="Button">"#
+        ));
+        // Empty string properties are left out
+        assert!(!xml.contains(r#"AutomationId="""#));
+        assert_eq!(tree.get_xml_attributes(), XmlAttribute::DEFAULT);
+
+        let found = tree.get_element_by_xpath("//Button[@AutomationId='btnSave']");
+        assert_eq!(found.unwrap().get_name(), "Speichern");
+        assert_eq!(count(&tree, "//*[@Width]"), 0);
+    }
+
+    #[test]
+    fn test_walk_emits_all_attributes() {
+        let (tx, rx) = channel();
+        get_all_elements_xml_with_backend(
+            tx,
+            &save_button_desktop(),
+            None,
+            &WalkOptions::default().with_xml_attributes(&XmlAttribute::ALL),
+            None,
+        );
+        let tree = rx.recv().unwrap().unwrap();
+        assert!(tree.get_xml_dom_tree().contains(
+            r#"<Button RtID="42-3" z-order="0" Name="Speichern" ControlType="Button" AutomationId="btnSave" ClassName="Button" FrameworkId="WPF" LocalizedControlType="Schaltfläche" Level="2" X="10" Y="20" Width="100" Height="30""#
+        ));
+        assert_eq!(count(&tree, "//*[@Width > 50 and @Level = 2]"), 1);
+    }
+
+    #[test]
+    fn test_walk_emits_only_selected_attributes() {
+        let backend = save_button_desktop();
+        let walk_with = |attributes: &[XmlAttribute]| {
+            let (tx, rx) = channel();
+            get_all_elements_xml_with_backend(
+                tx,
+                &backend,
+                None,
+                &WalkOptions::default().with_xml_attributes(attributes),
+                None,
+            );
+            rx.recv().unwrap().unwrap()
+        };
+
+        let tree = walk_with(&[XmlAttribute::AutomationId]);
+        assert_eq!(count(&tree, "//Button[@AutomationId='btnSave']"), 1);
+        assert!(!tree.get_xml_dom_tree().contains("ClassName="));
+        assert!(!tree.get_xml_dom_tree().contains("Width="));
+
+        let tree = walk_with(&[]);
+        assert!(
+            tree.get_xml_dom_tree().contains(
+                r#"<Button RtID="42-3" z-order="0" Name="Speichern" ControlType="Button">"#
+            )
+        );
+        assert_eq!(count(&tree, "//Button[@AutomationId]"), 0);
+    }
+
+    /// Desktop with Word (two windows of one process), Notepad and a tool window.
+    fn scoped_desktop() -> FakeBackend {
+        let window = |name: &str, class: &str, rtid: i32, pid: u32| {
+            fake_elem(name, "Window", rtid)
+                .with_classname(class)
+                .with_handle(rtid as isize * 16)
+                .with_process_id(pid)
+        };
+        let mut backend = FakeBackend::new(fake_elem("Desktop", "Pane", 1))
+            .with_executable(10, "WINWORD.EXE")
+            .with_executable(20, "notepad.exe");
+        let report = backend.add_child(0, window("Report.docx - Word", "OpusApp", 2, 10));
+        backend.add_child(report, fake_elem("Save", "Button", 3));
+        let letter = backend.add_child(0, window("Letter.docx - Word", "OpusApp", 4, 10));
+        backend.add_child(letter, fake_elem("Save", "Button", 5));
+        let notepad = backend.add_child(0, window("Untitled - Notepad", "Notepad", 6, 20));
+        backend.add_child(notepad, fake_elem("Text", "Edit", 7));
+        backend.add_child(0, window("Word Helper", "ToolWindow", 8, 30));
+        backend
+    }
+
+    fn walk_scoped(scope: WindowScope) -> UITree {
+        let (tx, rx) = channel();
+        get_all_elements_xml_with_backend(
+            tx,
+            &scoped_desktop(),
+            None,
+            &WalkOptions::default().with_window_scope(Some(scope)),
+            None,
+        );
+        rx.recv().unwrap().unwrap()
+    }
+
+    fn window_names(tree: &UITree) -> Vec<String> {
+        tree.get_elements_by_xpath("/Pane/Window")
+            .unwrap_or_default()
+            .iter()
+            .map(|w| w.get_name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_walk_scoped_by_process_and_executable() {
+        let tree = walk_scoped(WindowScope::default().with_process_id(10));
+        assert_eq!(
+            window_names(&tree),
+            ["Report.docx - Word", "Letter.docx - Word"]
+        );
+        assert_eq!(count(&tree, "//Button"), 2);
+
+        let tree = walk_scoped(WindowScope::default().with_executable("notepad"));
+        assert_eq!(window_names(&tree), ["Untitled - Notepad"]);
+        assert_eq!(count(&tree, "//Edit"), 1);
+    }
+
+    #[test]
+    fn test_walk_scoped_by_handle_class_and_title() {
+        let tree = walk_scoped(WindowScope::default().with_handle(4 * 16));
+        assert_eq!(window_names(&tree), ["Letter.docx - Word"]);
+
+        let tree = walk_scoped(WindowScope::default().with_class_name("OpusApp"));
+        assert_eq!(window_names(&tree).len(), 2);
+
+        // a caption substring would also match "Word Helper"
+        let title = regex::Regex::new(r" - Word$").unwrap();
+        let tree = walk_scoped(WindowScope::default().with_title(title));
+        assert_eq!(
+            window_names(&tree),
+            ["Report.docx - Word", "Letter.docx - Word"]
+        );
+    }
+
+    #[test]
+    fn test_par_walk_applies_window_scope() {
+        let (tx, rx) = channel();
+        get_all_elements_par_xml_with_backend(
+            tx,
+            || Ok(scoped_desktop()),
+            WalkOptions::default()
+                .with_max_workers(2)
+                .with_window_scope(Some(WindowScope::default().with_class_name("OpusApp"))),
+            None,
+        );
+        let tree = rx.recv().unwrap().unwrap();
+        assert_eq!(
+            window_names(&tree),
+            ["Report.docx - Word", "Letter.docx - Word"]
+        );
+        // the scope only filters the top-level windows, not their content
+        assert_eq!(count(&tree, "//Button"), 2);
+    }
+
+    fn no_id(name: &str, control_type: &str) -> SaveUIElement {
+        SaveUIElement::default()
+            .with_name(name)
+            .with_control_type(control_type)
+    }
+
+    /// Desktop whose `App` and `Tools` windows hold elements without runtime IDs.
+    fn build_desktop_without_runtime_ids() -> FakeBackend {
+        let mut backend = FakeBackend::new(fake_elem("Desktop", "Pane", 1));
+        let app = backend.add_child(0, fake_elem("App", "Window", 2));
+        for name in ["First", "Second"] {
+            let button = backend.add_child(app, no_id(name, "Button"));
+            backend.add_child(button, no_id(&format!("{} text", name), "Text"));
+        }
+        let tools = backend.add_child(0, fake_elem("Tools", "Window", 3));
+        backend.add_child(tools, no_id("Inner", "Text"));
+        backend
+    }
+
+    fn assert_addressable(tree: &UITree) {
+        for elem in tree.get_elements() {
+            let node = tree.get_tree().node(elem.get_tree_index());
+            assert_eq!(&*node.runtime_id, elem.get_rt_id());
+        }
+        let second = tree
+            .get_element_by_xpath("//Button[@Name='Second']/Text")
+            .unwrap();
+        assert_eq!(second.get_name(), "Second text");
+        let inner = tree.get_element_by_xpath("//Window[@Name='Tools']/Text");
+        assert_eq!(inner.unwrap().get_name(), "Inner");
+        let first = tree
+            .get_element_in_tree_by_xpath("//Button[@Name='First']")
+            .unwrap();
+        assert!(first.get_rt_id().contains("syn-"));
+        assert_eq!(
+            tree.fingerprint_by_rt_id(first.get_rt_id()),
+            Some(tree.fingerprint(first.get_tree_index()))
+        );
+    }
+
+    #[test]
+    fn test_walk_assigns_synthetic_ids() {
+        let mut backend = build_desktop_without_runtime_ids();
+        let untitled = backend.add_child(0, no_id("Untitled", "Window"));
+        backend.add_child(untitled, no_id("Caption", "Text"));
+        let tree = walk_fake(&backend, None, None, None, None).unwrap();
+        let synthetic: HashSet<&str> = tree
+            .get_elements()
+            .iter()
+            .map(|e| e.get_rt_id())
+            .filter(|rt_id| rt_id.starts_with("syn-"))
+            .collect();
+        assert_eq!(synthetic.len(), 7);
+        assert!(!tree.get_xml_dom_tree().contains("0-0-0-0"));
+        assert_addressable(&tree);
+        let caption = tree.get_element_by_xpath("//Window[@Name='Untitled']/Text");
+        assert_eq!(caption.unwrap().get_name(), "Caption");
+    }
+
+    #[test]
+    fn test_par_walk_keeps_synthetic_ids_apart() {
+        let (tx, rx) = channel();
+        get_all_elements_par_xml_with_backend(
+            tx,
+            || Ok(build_desktop_without_runtime_ids()),
+            WalkOptions::default().with_max_workers(2),
+            None,
+        );
+        let tree = rx.recv().unwrap().unwrap();
+        assert!(tree.is_complete());
+        assert_eq!(count(&tree, "//Text"), 3);
+        // each window walk numbers its synthetic IDs below the window's key
+        assert!(
+            tree.get_elements()
+                .iter()
+                .any(|e| e.get_rt_id() == "42-2:syn-1")
+        );
+        assert!(
+            tree.get_elements()
+                .iter()
+                .any(|e| e.get_rt_id() == "42-3:syn-1")
+        );
+        assert_addressable(&tree);
+    }
 }
@@ -0,0 +1,618 @@
+use crate::progress::WalkProgress;
+use crate::save_ui_element::SaveUIElement;
+
+use regex::Regex;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+/// Optional element properties that can be written to the XML DOM as attributes.
+///
+/// `RtID`, `z-order`, `Name` and `ControlType` are always written; these are
+/// emitted in addition so that XPath locators can use them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum XmlAttribute {
+    AutomationId,
+    ClassName,
+    FrameworkId,
+    LocalizedControlType,
+    Level,
+    /// Written as the four attributes `X`, `Y`, `Width` and `Height`.
+    BoundingRectangle,
+}
+
+impl XmlAttribute {
+    /// All optional attributes, in the order they are written.
+    pub const ALL: [XmlAttribute; 6] = [
+        XmlAttribute::AutomationId,
+        XmlAttribute::ClassName,
+        XmlAttribute::FrameworkId,
+        XmlAttribute::LocalizedControlType,
+        XmlAttribute::Level,
+        XmlAttribute::BoundingRectangle,
+    ];
+
+    /// Attributes written unless others are selected: the ones generated
+    /// locators use, so the DOM stays small.
+    pub const DEFAULT: [XmlAttribute; 2] = [XmlAttribute::AutomationId, XmlAttribute::ClassName];
+
+    /// Name of the attribute, e.g. `"AutomationId"`.
+    pub fn name(self) -> &'static str {
+        match self {
+            XmlAttribute::AutomationId => "AutomationId",
+            XmlAttribute::ClassName => "ClassName",
+            XmlAttribute::FrameworkId => "FrameworkId",
+            XmlAttribute::LocalizedControlType => "LocalizedControlType",
+            XmlAttribute::Level => "Level",
+            XmlAttribute::BoundingRectangle => "BoundingRectangle",
+        }
+    }
+
+    /// Parses an attribute name as returned by [`XmlAttribute::name`].
+    pub fn from_name(name: &str) -> Option<XmlAttribute> {
+        XmlAttribute::ALL
+            .into_iter()
+            .find(|attribute| attribute.name() == name)
+    }
+}
+
+/// UI Automation view of the element tree that the walker follows.
+///
+/// The control view is what most automation needs. The raw view also holds
+/// elements the control view hides, which is where some legacy Win32 and
+/// custom-drawn applications expose their content; the content view keeps
+/// only elements that carry information for the user and gives smaller trees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TreeView {
+    Raw,
+    #[default]
+    Control,
+    Content,
+}
+
+impl TreeView {
+    pub const ALL: [TreeView; 3] = [TreeView::Raw, TreeView::Control, TreeView::Content];
+
+    /// Lower-case name of the view, e.g. `"control"`.
+    pub fn name(self) -> &'static str {
+        match self {
+            TreeView::Raw => "raw",
+            TreeView::Control => "control",
+            TreeView::Content => "content",
+        }
+    }
+
+    /// Parses a view name as returned by [`TreeView::name`], ignoring case.
+    pub fn from_name(name: &str) -> Option<TreeView> {
+        TreeView::ALL
+            .into_iter()
+            .find(|view| view.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// Rules for subtrees the walker does not descend into.
+///
+/// A pruned element is still recorded in the tree, only its descendants are
+/// skipped. This keeps large subtrees that are never automated (for example
+/// web content rendered by a browser) out of the walk.
+#[derive(Debug, Clone, Default)]
+pub struct PruningPolicy {
+    control_types: Vec<String>,
+    class_names: Vec<String>,
+    framework_ids: Vec<String>,
+    process_ids: Vec<u32>,
+    offscreen: bool,
+    framework_max_depth: Vec<(String, usize)>,
+}
+
+impl PruningPolicy {
+    /// Does not descend into elements of this control type (e.g. `"Document"`).
+    pub fn prune_control_type(mut self, control_type: impl Into<String>) -> Self {
+        self.control_types.push(control_type.into());
+        self
+    }
+
+    /// Does not descend into elements of this class (e.g. `"Chrome_RenderWidgetHostHWND"`).
+    pub fn prune_class_name(mut self, class_name: impl Into<String>) -> Self {
+        self.class_names.push(class_name.into());
+        self
+    }
+
+    /// Does not descend into elements of this UI framework (e.g. `"Chrome"`).
+    pub fn prune_framework_id(mut self, framework_id: impl Into<String>) -> Self {
+        self.framework_ids.push(framework_id.into());
+        self
+    }
+
+    /// Does not descend into elements owned by this process.
+    pub fn prune_process_id(mut self, process_id: u32) -> Self {
+        self.process_ids.push(process_id);
+        self
+    }
+
+    /// Does not descend into elements reported as offscreen.
+    pub fn prune_offscreen(mut self, prune: bool) -> Self {
+        self.offscreen = prune;
+        self
+    }
+
+    /// Walks at most `max_depth` levels below the outermost element of a
+    /// framework. With `0` only that outermost element is recorded.
+    pub fn with_framework_max_depth(
+        mut self,
+        framework_id: impl Into<String>,
+        max_depth: usize,
+    ) -> Self {
+        let framework_id = framework_id.into();
+        self.framework_max_depth
+            .retain(|(id, _)| *id != framework_id);
+        self.framework_max_depth.push((framework_id, max_depth));
+        self
+    }
+
+    /// Returns `true` when no rule is set.
+    pub fn is_empty(&self) -> bool {
+        self.control_types.is_empty()
+            && self.class_names.is_empty()
+            && self.framework_ids.is_empty()
+            && self.process_ids.is_empty()
+            && !self.offscreen
+            && self.framework_max_depth.is_empty()
+    }
+
+    /// Decides whether the walker descends into the children of `props`.
+    /// `framework_depth` is the number of ancestors between the element and
+    /// the outermost ancestor of the same framework (0 for that ancestor itself).
+    pub fn should_descend(&self, props: &SaveUIElement, framework_depth: usize) -> bool {
+        let framework_id = props.get_framework_id();
+        if self
+            .control_types
+            .iter()
+            .any(|c| c == props.get_control_type())
+            || self.class_names.iter().any(|c| c == props.get_classname())
+            || self.framework_ids.iter().any(|f| f == framework_id)
+            || self.process_ids.contains(&props.get_process_id())
+            || (self.offscreen && props.is_offscreen())
+        {
+            return false;
+        }
+        self.framework_max_depth
+            .iter()
+            .find(|(id, _)| id == framework_id)
+            .is_none_or(|(_, max_depth)| framework_depth < *max_depth)
+    }
+}
+
+/// Selects the top-level windows a walk from the desktop descends into.
+///
+/// All criteria that are set must match. Unlike the target window caption,
+/// the scope is checked against the properties of the top-level window only,
+/// so it keeps working when the window title changes.
+#[derive(Debug, Clone, Default)]
+pub struct WindowScope {
+    process_id: Option<u32>,
+    executable: Option<String>,
+    handle: Option<isize>,
+    class_name: Option<String>,
+    title: Option<Regex>,
+}
+
+impl WindowScope {
+    /// Windows owned by the process with this id.
+    pub fn with_process_id(mut self, process_id: u32) -> Self {
+        self.process_id = Some(process_id);
+        self
+    }
+
+    /// Windows owned by a process started from this executable, e.g.
+    /// `"notepad.exe"`. Compared case-insensitively; the `.exe` suffix is optional.
+    pub fn with_executable(mut self, executable: impl Into<String>) -> Self {
+        self.executable = Some(executable.into());
+        self
+    }
+
+    /// The window with this native handle (HWND).
+    pub fn with_handle(mut self, handle: isize) -> Self {
+        self.handle = Some(handle);
+        self
+    }
+
+    /// Windows of this window class (e.g. `"Notepad"`).
+    pub fn with_class_name(mut self, class_name: impl Into<String>) -> Self {
+        self.class_name = Some(class_name.into());
+        self
+    }
+
+    /// Windows whose title matches `title` anywhere.
+    pub fn with_title(mut self, title: Regex) -> Self {
+        self.title = Some(title);
+        self
+    }
+
+    pub fn get_process_id(&self) -> Option<u32> {
+        self.process_id
+    }
+
+    pub fn get_executable(&self) -> Option<&str> {
+        self.executable.as_deref()
+    }
+
+    pub fn get_handle(&self) -> Option<isize> {
+        self.handle
+    }
+
+    pub fn get_class_name(&self) -> Option<&str> {
+        self.class_name.as_deref()
+    }
+
+    pub fn get_title(&self) -> Option<&Regex> {
+        self.title.as_ref()
+    }
+
+    /// Decides whether the top-level window `props` is in scope.
+    /// `executable_name` is only called when an executable is set.
+    pub fn matches(
+        &self,
+        props: &SaveUIElement,
+        executable_name: impl FnOnce(u32) -> Option<String>,
+    ) -> bool {
+        if self
+            .process_id
+            .is_some_and(|pid| pid != props.get_process_id())
+            || self.handle.is_some_and(|h| h != props.get_handle())
+            || self
+                .class_name
+                .as_deref()
+                .is_some_and(|c| c != props.get_classname())
+            || self
+                .title
+                .as_ref()
+                .is_some_and(|t| !t.is_match(props.get_name()))
+        {
+            return false;
+        }
+        match &self.executable {
+            Some(wanted) => executable_name(props.get_process_id())
+                .is_some_and(|actual| same_executable(wanted, &actual)),
+            None => true,
+        }
+    }
+}
+
+fn same_executable(wanted: &str, actual: &str) -> bool {
+    let strip = |name: &str| {
+        let name = name.to_lowercase();
+        match name.strip_suffix(".exe") {
+            Some(stem) => stem.to_string(),
+            None => name,
+        }
+    };
+    strip(wanted) == strip(actual)
+}
+
+/// Settings for a single tree walk, built from `WalkOptions::default()` with
+/// the `with_*` methods.
+#[derive(Debug, Clone)]
+pub struct WalkOptions {
+    max_depth: Option<usize>,
+    calling_window_caption: Option<String>,
+    target_window_caption: Option<String>,
+    window_scope: Option<WindowScope>,
+    tree_view: TreeView,
+    xml_attributes: Vec<XmlAttribute>,
+    element_state: bool,
+    pruning: PruningPolicy,
+    max_workers: usize,
+    window_budget: Option<Duration>,
+    progress: Option<Sender<WalkProgress>>,
+    progress_interval: usize,
+    partial_trees: bool,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        WalkOptions {
+            max_depth: None,
+            calling_window_caption: None,
+            target_window_caption: None,
+            window_scope: None,
+            tree_view: TreeView::default(),
+            xml_attributes: XmlAttribute::DEFAULT.to_vec(),
+            element_state: false,
+            pruning: PruningPolicy::default(),
+            max_workers: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(4),
+            window_budget: None,
+            progress: None,
+            progress_interval: 100,
+            partial_trees: false,
+        }
+    }
+}
+
+impl WalkOptions {
+    /// Stops descending below the given level (the root is level 0).
+    pub fn with_max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Skips the window with this exact caption (typically the caller's own window).
+    pub fn with_calling_window_caption(mut self, caption: Option<String>) -> Self {
+        self.calling_window_caption = caption;
+        self
+    }
+
+    /// Only keeps elements whose tree path contains this caption.
+    pub fn with_target_window_caption(mut self, caption: Option<String>) -> Self {
+        self.target_window_caption = caption;
+        self
+    }
+
+    /// Only walks the top-level windows in `scope`. Applies to walks that start
+    /// at the desktop; a walk from a given root element is not filtered.
+    pub fn with_window_scope(mut self, scope: Option<WindowScope>) -> Self {
+        self.window_scope = scope;
+        self
+    }
+
+    /// Selects the UI Automation view the walker follows (control view by default).
+    pub fn with_tree_view(mut self, view: TreeView) -> Self {
+        self.tree_view = view;
+        self
+    }
+
+    /// Selects the optional attributes written to the XML DOM
+    /// ([`XmlAttribute::DEFAULT`] by default). Pass an empty slice to keep the
+    /// DOM as small as possible, or [`XmlAttribute::ALL`] to query every property.
+    pub fn with_xml_attributes(mut self, attributes: &[XmlAttribute]) -> Self {
+        self.xml_attributes = attributes.to_vec();
+        self
+    }
+
+    /// Also reads the element state (enabled, focus, help text, keys, value,
+    /// toggle state and supported patterns). Off by default since it costs
+    /// about twenty extra UI Automation calls per element.
+    pub fn with_element_state(mut self, element_state: bool) -> Self {
+        self.element_state = element_state;
+        self
+    }
+
+    /// Sets the subtrees the walker does not descend into.
+    pub fn with_pruning(mut self, pruning: PruningPolicy) -> Self {
+        self.pruning = pruning;
+        self
+    }
+
+    /// Number of worker threads used by the parallel walk (at least one).
+    /// Defaults to the available parallelism of the machine.
+    pub fn with_max_workers(mut self, max_workers: usize) -> Self {
+        self.max_workers = max_workers.max(1);
+        self
+    }
+
+    /// Time budget for walking a single top-level window. Windows exceeding it
+    /// are reported as incomplete instead of delaying the result. The budget is
+    /// checked between elements; only the parallel walk also gives up on
+    /// windows whose worker is stuck in a UI Automation call.
+    pub fn with_window_budget(mut self, budget: Option<Duration>) -> Self {
+        self.window_budget = budget;
+        self
+    }
+
+    /// Sends [`WalkProgress`] events over `progress` while the walk runs.
+    pub fn with_progress(mut self, progress: Sender<WalkProgress>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Number of processed elements between two progress updates (default 100).
+    pub fn with_progress_interval(mut self, interval: usize) -> Self {
+        self.progress_interval = interval.max(1);
+        self
+    }
+
+    /// Also sends the subtree of every completed top-level window over the
+    /// progress channel (parallel walk only).
+    pub fn with_partial_trees(mut self, partial_trees: bool) -> Self {
+        self.partial_trees = partial_trees;
+        self
+    }
+
+    pub fn get_max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    pub fn get_calling_window_caption(&self) -> Option<&str> {
+        self.calling_window_caption.as_deref()
+    }
+
+    pub fn get_target_window_caption(&self) -> Option<&str> {
+        self.target_window_caption.as_deref()
+    }
+
+    pub fn get_window_scope(&self) -> Option<&WindowScope> {
+        self.window_scope.as_ref()
+    }
+
+    pub fn get_tree_view(&self) -> TreeView {
+        self.tree_view
+    }
+
+    pub fn get_xml_attributes(&self) -> &[XmlAttribute] {
+        &self.xml_attributes
+    }
+
+    pub fn get_element_state(&self) -> bool {
+        self.element_state
+    }
+
+    pub fn get_pruning(&self) -> &PruningPolicy {
+        &self.pruning
+    }
+
+    pub fn get_max_workers(&self) -> usize {
+        self.max_workers
+    }
+
+    pub fn get_window_budget(&self) -> Option<Duration> {
+        self.window_budget
+    }
+
+    pub(crate) fn get_progress_sender(&self) -> Option<&Sender<WalkProgress>> {
+        self.progress.as_ref()
+    }
+
+    pub fn get_progress_interval(&self) -> usize {
+        self.progress_interval
+    }
+
+    pub fn get_partial_trees(&self) -> bool {
+        self.partial_trees
+    }
+
+    pub fn emits(&self, attribute: XmlAttribute) -> bool {
+        self.xml_attributes.contains(&attribute)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn elem(control_type: &str, framework_id: &str) -> SaveUIElement {
+        SaveUIElement::default()
+            .with_control_type(control_type)
+            .with_framework_id(framework_id)
+            .with_classname("Chrome_RenderWidgetHostHWND")
+            .with_process_id(7)
+    }
+
+    #[test]
+    fn test_empty_policy_descends_everywhere() {
+        let policy = PruningPolicy::default();
+        assert!(policy.is_empty());
+        assert!(policy.should_descend(&elem("Pane", "Chrome").with_offscreen(true), 100));
+    }
+
+    #[test]
+    fn test_policy_rules() {
+        let pane = elem("Pane", "Win32");
+        assert!(
+            !PruningPolicy::default()
+                .prune_control_type("Pane")
+                .should_descend(&pane, 0)
+        );
+        assert!(
+            !PruningPolicy::default()
+                .prune_class_name("Chrome_RenderWidgetHostHWND")
+                .should_descend(&pane, 0)
+        );
+        assert!(
+            !PruningPolicy::default()
+                .prune_framework_id("Win32")
+                .should_descend(&pane, 0)
+        );
+        assert!(
+            !PruningPolicy::default()
+                .prune_process_id(7)
+                .should_descend(&pane, 0)
+        );
+        assert!(
+            PruningPolicy::default()
+                .prune_process_id(8)
+                .should_descend(&pane, 0)
+        );
+
+        let offscreen = PruningPolicy::default().prune_offscreen(true);
+        assert!(offscreen.should_descend(&pane, 0));
+        assert!(!offscreen.should_descend(&pane.clone().with_offscreen(true), 0));
+    }
+
+    #[test]
+    fn test_framework_max_depth() {
+        let policy = PruningPolicy::default()
+            .with_framework_max_depth("Chrome", 5)
+            .with_framework_max_depth("Chrome", 2);
+        let doc = elem("Document", "Chrome");
+        assert!(policy.should_descend(&doc, 0));
+        assert!(policy.should_descend(&doc, 1));
+        assert!(!policy.should_descend(&doc, 2));
+        assert!(policy.should_descend(&elem("Pane", "Win32"), 10));
+    }
+
+    #[test]
+    fn test_tree_view_names_roundtrip() {
+        for view in TreeView::ALL {
+            assert_eq!(TreeView::from_name(view.name()), Some(view));
+        }
+        assert_eq!(TreeView::from_name("Raw"), Some(TreeView::Raw));
+        assert_eq!(TreeView::from_name("logical"), None);
+        assert_eq!(WalkOptions::default().get_tree_view(), TreeView::Control);
+    }
+
+    #[test]
+    fn test_element_state_is_opt_in() {
+        assert!(!WalkOptions::default().get_element_state());
+        let options = WalkOptions::default().with_element_state(true);
+        assert!(options.get_element_state());
+    }
+
+    #[test]
+    fn test_window_scope_matches_all_criteria() {
+        let window = SaveUIElement::default()
+            .with_name("Report.docx - Word")
+            .with_classname("OpusApp")
+            .with_handle(0x1234)
+            .with_process_id(42);
+        let no_lookup = |_| -> Option<String> { panic!("executable looked up") };
+
+        assert!(WindowScope::default().matches(&window, no_lookup));
+        assert!(
+            WindowScope::default()
+                .with_process_id(42)
+                .with_handle(0x1234)
+                .with_class_name("OpusApp")
+                .with_title(Regex::new(r" - Word$").unwrap())
+                .matches(&window, no_lookup)
+        );
+        assert!(
+            !WindowScope::default()
+                .with_process_id(7)
+                .matches(&window, no_lookup)
+        );
+        assert!(
+            !WindowScope::default()
+                .with_handle(1)
+                .matches(&window, no_lookup)
+        );
+        assert!(
+            !WindowScope::default()
+                .with_title(Regex::new("^Document1").unwrap())
+                .matches(&window, no_lookup)
+        );
+    }
+
+    #[test]
+    fn test_window_scope_executable() {
+        let window = SaveUIElement::default().with_process_id(42);
+        let lookup = |pid: u32| (pid == 42).then(|| "WINWORD.EXE".to_string());
+        for wanted in ["winword.exe", "WinWord"] {
+            assert!(
+                WindowScope::default()
+                    .with_executable(wanted)
+                    .matches(&window, lookup)
+            );
+        }
+        assert!(
+            !WindowScope::default()
+                .with_executable("word.exe")
+                .matches(&window, lookup)
+        );
+        assert!(
+            !WindowScope::default()
+                .with_executable("winword.exe")
+                .matches(&window, |_| None)
+        );
+    }
+}
@@ -1,5 +1,6 @@
-use crate::save_ui_element::SaveUIElement;
 use crate::common_types::UIElementInTree;
+use crate::save_ui_element::SaveUIElement;
+use bromium_common::format_runtime_id;
 
 pub const MAX_SIBLINGS: usize = 10_000;
 
@@ -30,3 +31,30 @@ pub fn format_node_item(props: &SaveUIElement, runtime_id: &str) -> String {
         runtime_id
     )
 }
+
+/// Hands out the keys that identify elements in the `UITreeMap`, the XML
+/// `RtID` attribute and `UIElementInTree`. Elements without a runtime ID would
+/// all share `"0-0-0-0"` (CF-28), so they get a synthetic ID instead.
+pub struct RtIdAllocator {
+    namespace: String,
+    next: usize,
+}
+
+impl RtIdAllocator {
+    /// Synthetic IDs are `"<namespace>syn-<n>"`. Subtrees walked separately
+    /// and merged later need distinct namespaces.
+    pub fn new(namespace: impl Into<String>) -> Self {
+        RtIdAllocator {
+            namespace: namespace.into(),
+            next: 0,
+        }
+    }
+
+    pub fn rt_id(&mut self, runtime_id: &[i32]) -> String {
+        if !runtime_id.is_empty() {
+            return format_runtime_id(runtime_id);
+        }
+        self.next += 1;
+        format!("{}syn-{}", self.namespace, self.next)
+    }
+}
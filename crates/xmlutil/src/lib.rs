@@ -1,11 +1,15 @@
 pub mod pretty_print;
 pub mod xml;
 pub mod xml_dom_manager;
+pub mod xpath_dom;
 pub mod xpath_eval;
+pub mod xpath_functions;
 pub mod xpath_gen;
 
 pub use xml::*;
+pub use xpath_dom::*;
 pub use xpath_eval::*;
+pub use xpath_functions::*;
 pub use xpath_gen::*;
 // pub use pretty_print::*;
 
@@ -158,4 +162,37 @@ mod tests {
             &"1-2".to_string()
         );
     }
+
+    #[test]
+    fn test_eval_xpath_with_variables() {
+        let xml = r#"<Window Name="Notepad">
+                        <Button Name="Save" />
+                        <Button Name="Don't save" />
+                        <Button Name='Say "hi"' />
+                    </Window>"#;
+
+        let vars = XpathVariables::new().with("name", "Don't save");
+        let res = eval_xpath_with_variables("//Button[@Name=$name]/@Name", xml, &vars);
+        assert!(res.is_success(), "{}", res.get_error_msg());
+        assert_eq!(res.get_result_count(), 1);
+        assert_eq!(res.get_result_items()[0].get_item_value(), "Don't save");
+
+        let vars = vars.with("name", r#"Say "hi""#);
+        assert_eq!(vars.len(), 1);
+        let res = eval_xpath_with_variables("//Button[@Name=$name]/@Name", xml, &vars);
+        assert_eq!(res.get_result_items()[0].get_item_value(), r#"Say "hi""#);
+
+        // quotes in a value are data, never part of the expression
+        let vars = XpathVariables::new().with("name", "x' or '1'='1");
+        let res = eval_xpath_with_variables("//Button[@Name=$name]", xml, &vars);
+        assert!(res.is_success());
+        assert_eq!(res.get_result_count(), 0);
+
+        let vars = XpathVariables::new().with("count", 3i64).with("all", true);
+        let res = eval_xpath_with_variables("count(//Button) = $count and $all", xml, &vars);
+        assert_eq!(res.get_result_items()[0].get_item_value(), "true");
+
+        let res = eval_xpath("//Button[@Name=$name]", xml);
+        assert!(!res.is_success());
+    }
 }
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+
+use crate::xpath_eval::{XpathVariables, make_static_context_builder, render_error};
+use crate::xpath_functions::expand_functions;
+use xee_xpath::Itemable;
+use xee_xpath::Query;
+
+/// XPath document assembled element by element instead of parsed from an XML
+/// string.
+///
+/// Every element is tagged with an id chosen by the caller; query results are
+/// returned as those ids, so they can point straight back into the caller's
+/// own data. Elements can be inserted, replaced and removed by id after the
+/// document was built, so it can be kept up to date instead of rebuilt.
+pub struct XpathDom {
+    documents: xee_xpath::Documents,
+    doc_handle: xee_xpath::DocumentHandle,
+    root: xot::Node,
+    ids: HashMap<xot::Node, usize>,
+    nodes: HashMap<usize, xot::Node>,
+    /// xee caches the document order of nodes per document collection, so a
+    /// changed document is registered again before the next query.
+    changed: bool,
+}
+
+// SAFETY: like `XpathDocCache`, an XpathDom is only used by the thread that
+// currently owns it; the inner Rc<RefCell<…>> is never shared across threads.
+unsafe impl Send for XpathDom {}
+
+impl std::fmt::Debug for XpathDom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("XpathDom")
+            .field("elements", &self.ids.len())
+            .finish()
+    }
+}
+
+impl XpathDom {
+    /// Creates an empty document.
+    pub fn new() -> Result<Self, String> {
+        let mut documents = xee_xpath::Documents::new();
+        let root = documents.xot_mut().new_document();
+        let doc_handle = register(&documents, root)?;
+        Ok(XpathDom {
+            documents,
+            doc_handle,
+            root,
+            ids: HashMap::new(),
+            nodes: HashMap::new(),
+            changed: false,
+        })
+    }
+
+    /// Evaluates `expr` and returns the ids of the selected elements, in
+    /// document order. Attribute results resolve to their element; results
+    /// that are not part of a tagged element are skipped.
+    pub fn eval(&mut self, expr: &str) -> Result<Vec<usize>, String> {
+        self.eval_with_variables(expr, &XpathVariables::default())
+    }
+
+    /// Like [`XpathDom::eval`], binding `variables` for `$name` references.
+    pub fn eval_with_variables(
+        &mut self,
+        expr: &str,
+        variables: &XpathVariables,
+    ) -> Result<Vec<usize>, String> {
+        if self.changed {
+            self.reregister()?;
+        }
+        let expr = expand_functions(expr)?;
+        let mut static_context_builder = make_static_context_builder(None, &[])
+            .map_err(|e| format!("Failed to build XPath context: {}", e))?;
+        variables.declare(&mut static_context_builder);
+        let queries = xee_xpath::Queries::new(static_context_builder);
+        let query = queries
+            .sequence(expr.as_str())
+            .map_err(|e| render_error(&expr, e))?;
+
+        let mut context_builder = query.dynamic_context_builder(&self.documents);
+        let doc_item = self
+            .doc_handle
+            .to_item(&self.documents)
+            .map_err(|e| render_error(&expr, e))?;
+        context_builder.context_item(doc_item);
+        if !variables.is_empty() {
+            context_builder.variables(variables.bind());
+        }
+        let context = context_builder.build();
+        let sequence = query
+            .execute_with_context(&mut self.documents, &context)
+            .map_err(|e| render_error(&expr, e))?;
+
+        let xot = self.documents.xot();
+        let mut ids = Vec::with_capacity(sequence.len());
+        for item in sequence.iter() {
+            let Ok(mut node) = item.to_node() else {
+                continue;
+            };
+            loop {
+                if let Some(&id) = self.ids.get(&node) {
+                    ids.push(id);
+                    break;
+                }
+                match xot.parent(node) {
+                    Some(parent) => node = parent,
+                    None => break,
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Appends an element tagged with `id` as the last child of the element
+    /// `parent`, or as the document element when `parent` is `None`.
+    pub fn append_element<'a>(
+        &mut self,
+        parent: Option<usize>,
+        tag: &str,
+        attributes: impl IntoIterator<Item = (&'a str, &'a str)>,
+        id: usize,
+    ) -> Result<(), String> {
+        let parent = match parent {
+            Some(parent) => self.node(parent)?,
+            None => self.root,
+        };
+        let element = self.new_element(tag, attributes);
+        self.documents
+            .xot_mut()
+            .append(parent, element)
+            .map_err(|e| format!("Failed to append element '{}': {}", tag, e))?;
+        self.track(element, id);
+        Ok(())
+    }
+
+    /// Replaces the element `id` and everything below it with a new, empty
+    /// element tagged with `new_id`, keeping its position.
+    pub fn replace_element<'a>(
+        &mut self,
+        id: usize,
+        tag: &str,
+        attributes: impl IntoIterator<Item = (&'a str, &'a str)>,
+        new_id: usize,
+    ) -> Result<(), String> {
+        let old = self.node(id)?;
+        let element = self.new_element(tag, attributes);
+        self.untrack(old);
+        self.documents
+            .xot_mut()
+            .replace(old, element)
+            .map_err(|e| format!("Failed to replace element {}: {}", id, e))?;
+        self.track(element, new_id);
+        Ok(())
+    }
+
+    /// Removes the element `id` and everything below it.
+    pub fn remove_element(&mut self, id: usize) -> Result<(), String> {
+        let node = self.node(id)?;
+        self.untrack(node);
+        self.changed = true;
+        self.documents
+            .xot_mut()
+            .remove(node)
+            .map_err(|e| format!("Failed to remove element {}: {}", id, e))
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        self.nodes.contains_key(&id)
+    }
+
+    /// Number of tagged elements in the document.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    fn node(&self, id: usize) -> Result<xot::Node, String> {
+        self.nodes
+            .get(&id)
+            .copied()
+            .ok_or_else(|| format!("No element with id {}", id))
+    }
+
+    fn new_element<'a>(
+        &mut self,
+        tag: &str,
+        attributes: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> xot::Node {
+        let xot = self.documents.xot_mut();
+        let name = xot.add_name(tag);
+        let element = xot.new_element(name);
+        for (key, value) in attributes {
+            let key = xot.add_name(key);
+            xot.attributes_mut(element).insert(key, value.to_string());
+        }
+        element
+    }
+
+    fn track(&mut self, element: xot::Node, id: usize) {
+        self.ids.insert(element, id);
+        self.nodes.insert(id, element);
+        self.changed = true;
+    }
+
+    fn untrack(&mut self, node: xot::Node) {
+        let xot = self.documents.xot();
+        for descendant in xot.descendants(node) {
+            if let Some(id) = self.ids.remove(&descendant) {
+                self.nodes.remove(&id);
+            }
+        }
+    }
+
+    /// Moves the arena into a fresh document collection, dropping the cached
+    /// document order of the old one.
+    fn reregister(&mut self) -> Result<(), String> {
+        let mut documents = xee_xpath::Documents::new();
+        std::mem::swap(documents.xot_mut(), self.documents.xot_mut());
+        self.doc_handle = register(&documents, self.root)?;
+        self.documents = documents;
+        self.changed = false;
+        Ok(())
+    }
+}
+
+fn register(
+    documents: &xee_xpath::Documents,
+    root: xot::Node,
+) -> Result<xee_xpath::DocumentHandle, String> {
+    documents
+        .documents()
+        .borrow_mut()
+        .add_root(None, root)
+        .map_err(|e| format!("Failed to register document: {}", e))
+}
+
+/// Builds an [`XpathDom`] from start/end element calls, mirroring how a
+/// streaming XML writer is driven.
+pub struct XpathDomBuilder {
+    dom: Result<XpathDom, String>,
+    open: Vec<usize>,
+}
+
+impl XpathDomBuilder {
+    pub fn new() -> Self {
+        XpathDomBuilder {
+            dom: XpathDom::new(),
+            open: Vec::new(),
+        }
+    }
+
+    /// Opens an element tagged with `id` below the currently open element.
+    pub fn start_element<'a>(
+        &mut self,
+        tag: &str,
+        attributes: impl IntoIterator<Item = (&'a str, &'a str)>,
+        id: usize,
+    ) -> Result<(), String> {
+        let dom = self.dom.as_mut().map_err(|e| e.clone())?;
+        dom.append_element(self.open.last().copied(), tag, attributes, id)?;
+        self.open.push(id);
+        Ok(())
+    }
+
+    /// Closes the most recently opened element.
+    pub fn end_element(&mut self) {
+        self.open.pop();
+    }
+
+    pub fn finish(self) -> Result<XpathDom, String> {
+        self.dom
+    }
+}
+
+impl Default for XpathDomBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_dom() -> XpathDom {
+        let mut builder = XpathDomBuilder::new();
+        builder
+            .start_element("Window", [("Name", "Main")], 10)
+            .unwrap();
+        builder
+            .start_element("Button", [("Name", "OK")], 11)
+            .unwrap();
+        builder.end_element();
+        builder
+            .start_element("Button", [("Name", "Cancel")], 12)
+            .unwrap();
+        builder.end_element();
+        builder.end_element();
+        builder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_eval_returns_element_ids() {
+        let mut dom = sample_dom();
+        assert_eq!(dom.len(), 3);
+        assert_eq!(dom.eval("//Button").unwrap(), vec![11, 12]);
+        assert_eq!(
+            dom.eval("/Window/Button[@Name='Cancel']").unwrap(),
+            vec![12]
+        );
+        assert_eq!(dom.eval("//Button/@Name").unwrap(), vec![11, 12]);
+        assert!(dom.eval("//Slider").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_eval_reports_invalid_expression() {
+        let mut dom = sample_dom();
+        assert!(dom.eval("//Button[").is_err());
+        // atomic results have no element to point at
+        assert!(dom.eval("count(//Button)").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_edits_are_visible_to_later_queries() {
+        let mut dom = sample_dom();
+        assert_eq!(dom.eval("//Button").unwrap(), vec![11, 12]);
+
+        dom.replace_element(11, "Pane", [("Name", "Toolbar")], 20)
+            .unwrap();
+        dom.append_element(Some(20), "Button", [("Name", "Save")], 21)
+            .unwrap();
+        assert!(!dom.contains(11));
+        // the replacement keeps the position of the replaced element
+        assert_eq!(dom.eval("//Button").unwrap(), vec![21, 12]);
+        assert_eq!(dom.eval("/Window/*[1]").unwrap(), vec![20]);
+
+        dom.remove_element(20).unwrap();
+        assert_eq!(dom.eval("//*").unwrap(), vec![10, 12]);
+        assert_eq!(dom.len(), 2);
+        assert!(dom.remove_element(21).is_err());
+    }
+
+    #[test]
+    fn test_eval_binds_variables() {
+        let mut dom = sample_dom();
+        let vars = XpathVariables::new().with("name", "Cancel");
+        assert_eq!(
+            dom.eval_with_variables("//Button[@Name=$name]", &vars)
+                .unwrap(),
+            vec![12]
+        );
+        let vars = XpathVariables::new().with("pos", 2i64);
+        assert_eq!(
+            dom.eval_with_variables("(//Button)[$pos]", &vars).unwrap(),
+            vec![12]
+        );
+        // unbound variables are a static error, not an empty match
+        assert!(dom.eval("//Button[@Name=$name]").is_err());
+    }
+}
@@ -1,12 +1,13 @@
 use crate::pretty_print::pretty_print_xml;
+use crate::xpath_functions::{BROMIUM_NS_PREFIX, BROMIUM_NS_URI, ExpandedXpath, expand_functions};
 use xee_xpath::Itemable;
 use xee_xpath::Query;
 use xee_xpath::context::StaticContextBuilder;
 use xee_xpath::error::Error;
-use xee_xpath::error::SourceSpan;
+use xot::xmlname::OwnedName;
 
 #[derive(Debug, thiserror::Error)]
-enum XpathEvalError {
+pub(crate) enum XpathEvalError {
     #[error("{0}")]
     XPath(#[from] xee_xpath::error::Error),
     #[error("{0}")]
@@ -44,10 +45,130 @@ impl XpathDocCache {
     }
 }
 
+/// A value that can be bound to an external XPath variable.
+#[derive(Debug, Clone, PartialEq)]
+pub enum XpathValue {
+    String(String),
+    Integer(i64),
+    Double(f64),
+    Boolean(bool),
+}
+
+impl XpathValue {
+    fn to_sequence(&self) -> xee_xpath::Sequence {
+        let atomic = match self {
+            XpathValue::String(s) => xee_xpath::Atomic::from(s.as_str()),
+            XpathValue::Integer(i) => xee_xpath::Atomic::from(*i),
+            XpathValue::Double(d) => xee_xpath::Atomic::from(*d),
+            XpathValue::Boolean(b) => xee_xpath::Atomic::from(*b),
+        };
+        xee_xpath::Item::from(atomic).into()
+    }
+}
+
+impl From<&str> for XpathValue {
+    fn from(value: &str) -> Self {
+        XpathValue::String(value.to_string())
+    }
+}
+
+impl From<String> for XpathValue {
+    fn from(value: String) -> Self {
+        XpathValue::String(value)
+    }
+}
+
+impl From<i64> for XpathValue {
+    fn from(value: i64) -> Self {
+        XpathValue::Integer(value)
+    }
+}
+
+impl From<f64> for XpathValue {
+    fn from(value: f64) -> Self {
+        XpathValue::Double(value)
+    }
+}
+
+impl From<bool> for XpathValue {
+    fn from(value: bool) -> Self {
+        XpathValue::Boolean(value)
+    }
+}
+
+/// External variables for an XPath expression, referenced as `$name`.
+///
+/// Values are passed through the dynamic context rather than spliced into
+/// the expression, so they need no quoting or escaping.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct XpathVariables {
+    values: Vec<(String, XpathValue)>,
+}
+
+impl XpathVariables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds `name` to `value`, replacing any earlier binding of `name`.
+    pub fn with(mut self, name: impl Into<String>, value: impl Into<XpathValue>) -> Self {
+        self.set(name, value);
+        self
+    }
+
+    /// Binds `name` to `value`, replacing any earlier binding of `name`.
+    pub fn set(&mut self, name: impl Into<String>, value: impl Into<XpathValue>) {
+        let name = name.into();
+        let value = value.into();
+        match self.values.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, v)) => *v = value,
+            None => self.values.push((name, value)),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&XpathValue> {
+        self.values.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Declares the variable names so expressions referencing them compile.
+    pub(crate) fn declare(&self, static_context_builder: &mut StaticContextBuilder<'_>) {
+        static_context_builder.variable_names(self.values.iter().map(|(n, _)| owned_name(n)));
+    }
+
+    /// Builds the variable bindings for the dynamic context.
+    pub(crate) fn bind(&self) -> xee_xpath::context::Variables {
+        self.values
+            .iter()
+            .map(|(n, v)| (owned_name(n), v.to_sequence()))
+            .collect()
+    }
+}
+
+fn owned_name(name: &str) -> OwnedName {
+    OwnedName::new(name.to_string(), String::new(), String::new())
+}
+
 /// Evaluate an XPath expression against a pre-parsed XML document cache.
 /// This skips the XML parsing step, reusing the already-parsed DOM.
 pub fn eval_xpath_on_cache(expr: &str, cache: &mut XpathDocCache) -> XpathResult {
-    let static_context_builder = match make_static_context_builder(None, &[]) {
+    eval_xpath_on_cache_with_variables(expr, cache, &XpathVariables::default())
+}
+
+/// Like [`eval_xpath_on_cache`], binding `variables` for `$name` references.
+pub fn eval_xpath_on_cache_with_variables(
+    expr: &str,
+    cache: &mut XpathDocCache,
+    variables: &XpathVariables,
+) -> XpathResult {
+    let mut static_context_builder = match make_static_context_builder(None, &[]) {
         Ok(ctx) => ctx,
         Err(e) => {
             return XpathResult::new(
@@ -59,12 +180,15 @@ pub fn eval_xpath_on_cache(expr: &str, cache: &mut XpathDocCache) -> XpathResult
         }
     };
 
+    variables.declare(&mut static_context_builder);
+
     let queries = xee_xpath::Queries::new(static_context_builder);
     match execute_query(
         expr,
         &queries,
         &mut cache.documents,
         Some(cache.doc_handle),
+        variables,
     ) {
         Ok(res) => res,
         Err(e) => XpathResult::new(
@@ -157,6 +281,15 @@ impl XpathResult {
 }
 
 pub fn eval_xpath(expr: &str, srcxml: &str) -> XpathResult {
+    eval_xpath_with_variables(expr, srcxml, &XpathVariables::default())
+}
+
+/// Like [`eval_xpath`], binding `variables` for `$name` references.
+pub fn eval_xpath_with_variables(
+    expr: &str,
+    srcxml: &str,
+    variables: &XpathVariables,
+) -> XpathResult {
     let mut documents = xee_xpath::Documents::new();
     let doc = match documents.add_string_without_uri(srcxml) {
         Ok(doc) => doc,
@@ -170,7 +303,7 @@ pub fn eval_xpath(expr: &str, srcxml: &str) -> XpathResult {
         }
     };
 
-    let static_context_builder = match make_static_context_builder(None, &[]) {
+    let mut static_context_builder = match make_static_context_builder(None, &[]) {
         Ok(ctx) => ctx,
         Err(e) => {
             return XpathResult::new(
@@ -181,9 +314,10 @@ pub fn eval_xpath(expr: &str, srcxml: &str) -> XpathResult {
             );
         }
     };
+    variables.declare(&mut static_context_builder);
 
     let queries = xee_xpath::Queries::new(static_context_builder);
-    match execute_query(expr, &queries, &mut documents, Some(doc)) {
+    match execute_query(expr, &queries, &mut documents, Some(doc), variables) {
         Ok(res) => res,
         Err(e) => XpathResult::new(
             false,
@@ -199,14 +333,22 @@ fn execute_query(
     queries: &xee_xpath::Queries<'_>,
     documents: &mut xee_xpath::Documents,
     doc: Option<xee_xpath::DocumentHandle>,
+    variables: &XpathVariables,
 ) -> Result<XpathResult, XpathEvalError> {
     let mut no_result = XpathResult::new(false, None, 0, vec![XpathQueryResult::default()]);
 
-    let sequence_query = queries.sequence(xpath);
+    let xpath = match expand_functions(xpath) {
+        Ok(xpath) => xpath,
+        Err(err_msg) => {
+            no_result.set_error_msg(err_msg);
+            return Ok(no_result);
+        }
+    };
+    let sequence_query = queries.sequence(xpath.as_str());
     let sequence_query = match sequence_query {
         Ok(sequence_query) => sequence_query,
         Err(e) => {
-            let err_msg = render_error(xpath, e);
+            let err_msg = render_error(&xpath, e);
             no_result.set_success(false);
             no_result.set_error_msg(err_msg);
             return Ok(no_result);
@@ -216,13 +358,16 @@ fn execute_query(
     if let Some(doc) = doc {
         context_builder.context_item(doc.to_item(documents)?);
     }
+    if !variables.is_empty() {
+        context_builder.variables(variables.bind());
+    }
     let context = context_builder.build();
 
     let sequence = sequence_query.execute_with_context(documents, &context);
     let sequence = match sequence {
         Ok(sequence) => sequence,
         Err(e) => {
-            let err_msg = render_error(xpath, e);
+            let err_msg = render_error(&xpath, e);
             no_result.set_success(false);
             no_result.set_error_msg(err_msg);
             return Ok(no_result);
@@ -249,11 +394,14 @@ fn execute_query(
     Ok(result)
 }
 
-fn make_static_context_builder<'a>(
+pub(crate) fn make_static_context_builder<'a>(
     default_namespace_uri: Option<&'a str>,
     namespaces: &'a [String],
 ) -> Result<StaticContextBuilder<'a>, XpathEvalError> {
     let mut static_context_builder = xee_xpath::context::StaticContextBuilder::default();
+    // Only the prefix is bound here; the builder cannot register functions, so
+    // `b:` calls are expanded before compiling (see `crate::xpath_functions`)
+    static_context_builder.add_namespace(BROMIUM_NS_PREFIX, BROMIUM_NS_URI);
     if let Some(default_namespace_uri) = default_namespace_uri {
         static_context_builder.default_element_namespace(default_namespace_uri);
     }
@@ -267,6 +415,12 @@ fn make_static_context_builder<'a>(
             let uri = parts
                 .next()
                 .ok_or_else(|| XpathEvalError::NamespaceDecl("missing uri".to_string()))?;
+            if prefix == BROMIUM_NS_PREFIX {
+                return Err(XpathEvalError::NamespaceDecl(format!(
+                    "prefix '{}' is reserved for the locator helper functions",
+                    prefix
+                )));
+            }
             Ok((prefix, uri))
         })
         .collect::<Result<Vec<_>, XpathEvalError>>()?;
@@ -300,22 +454,28 @@ fn remove_trailing(s: String) -> String {
     s.lines().flat_map(|l| [l.trim_end(), "\n"]).collect()
 }
 
-fn render_error(src: &str, e: Error) -> String {
-    let primary_span: SourceSpan;
+/// Renders `e` against the expression as the caller wrote it, with the error
+/// span mapped back from the expanded text.
+pub(crate) fn render_error(xpath: &ExpandedXpath<'_>, e: Error) -> String {
+    let span = e.span.map(|span| xpath.source_range(span.range()));
+    render_report(xpath.source(), span, &e.error.code(), e.error.message())
+}
 
-    if let Some(e_span) = e.span {
-        primary_span = e_span;
-    } else {
-        primary_span = SourceSpan::from(0..0);
-    }
+pub(crate) fn render_report(
+    src: &str,
+    span: Option<std::ops::Range<usize>>,
+    code: &str,
+    message: &str,
+) -> String {
+    let primary_span = span.clone().unwrap_or(0..0);
 
-    let mut rpt = Report::build(ReportKind::Error, primary_span.range())
+    let mut rpt = Report::build(ReportKind::Error, primary_span)
         .with_config(no_color_and_ascii().with_index_type(IndexType::Byte))
-        .with_code(e.error.code())
+        .with_code(code)
         .with_message("invalid xpath expression");
 
-    if let Some(span) = e.span {
-        rpt = rpt.with_label(Label::new(span.range()).with_message(e.error.message()))
+    if let Some(span) = span {
+        rpt = rpt.with_label(Label::new(span).with_message(message))
     }
 
     let rpt_final = rpt.finish();
@@ -0,0 +1,487 @@
+//! Locator helper functions in the `b:` namespace.
+//!
+//! xee's function library is a fixed static table with no way to register
+//! functions: in xee-xpath 0.1.5 `StaticContextBuilder` only takes namespaces,
+//! variable names, the default namespaces and the base URI, and function
+//! names are resolved against the library built into xee-interpreter. Calls
+//! such as `b:icontains(@Name, 'save')` are therefore expanded into plain
+//! XPath 3.1 before the expression is compiled. The expansion runs on xee's
+//! own token stream, so `b:` text inside string literals and comments is left
+//! alone and nested calls are expanded from the inside out. The available
+//! functions are:
+//!
+//! - `b:icontains(haystack, needle)`: case-insensitive `contains`.
+//! - `b:matches-ci(input, pattern)`: case-insensitive `matches`.
+//! - `b:normalize-name([name])`: the name without accelerator ampersands, a
+//!   trailing counter such as ` (3)` or ` [2]` and extra whitespace. Defaults
+//!   to the context element's `@Name`.
+//! - `b:inside(x, y, w, h)`: the context element lies entirely within the
+//!   given screen rectangle.
+//! - `b:visible()`: the context element has a non-empty bounding rectangle.
+//!
+//! The `b` prefix is bound to [`BROMIUM_NS_URI`] in every query, and the
+//! functions can also be called by their full name, e.g.
+//! `Q{urn:bromium:functions}visible()`. Every argument is bound to a variable
+//! and evaluated once, however often the expansion uses it.
+//!
+//! `b:inside` and `b:visible` read the `X`, `Y`, `Width` and `Height`
+//! attributes and are false for elements written without them.
+
+use crate::xpath_eval::render_report;
+
+use std::borrow::Cow;
+use std::ops::Range;
+use xee_xpath_lexer::{Token, lexer};
+
+/// Prefix of the locator helper functions.
+pub const BROMIUM_NS_PREFIX: &str = "b";
+/// Namespace URI bound to [`BROMIUM_NS_PREFIX`].
+pub const BROMIUM_NS_URI: &str = "urn:bromium:functions";
+
+const FUNCTION_NAMES: [&str; 5] = [
+    "icontains",
+    "matches-ci",
+    "normalize-name",
+    "inside",
+    "visible",
+];
+
+/// An XPath expression with its `b:` calls expanded, together with the map
+/// from the expanded text back to the expression as written.
+#[derive(Debug)]
+pub struct ExpandedXpath<'a> {
+    source: &'a str,
+    text: Cow<'a, str>,
+    /// Consecutive pieces of `text` and the source range each one came from.
+    pieces: Vec<Piece>,
+}
+
+#[derive(Debug)]
+struct Piece {
+    text: Range<usize>,
+    source: Range<usize>,
+    /// Copied from the source unchanged, so offsets map one to one.
+    verbatim: bool,
+}
+
+impl<'a> ExpandedXpath<'a> {
+    /// The expression as written by the caller.
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    /// The expression to compile.
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Maps a byte range of the expanded text to the source expression.
+    /// Ranges inside generated code map to the whole call they came from.
+    pub fn source_range(&self, range: Range<usize>) -> Range<usize> {
+        if self.pieces.is_empty() {
+            return range;
+        }
+        let start = self.source_offset(range.start, false);
+        let end = self.source_offset(range.end, true).max(start);
+        start..end
+    }
+
+    fn source_offset(&self, offset: usize, is_end: bool) -> usize {
+        let pos = self.pieces.partition_point(|piece| {
+            if is_end {
+                piece.text.end < offset
+            } else {
+                piece.text.end <= offset
+            }
+        });
+        match self.pieces.get(pos) {
+            None => self.source.len(),
+            Some(piece) if piece.verbatim => piece.source.start + offset - piece.text.start,
+            Some(piece) if is_end => piece.source.end,
+            Some(piece) => piece.source.start,
+        }
+    }
+}
+
+/// Rewrites every `b:` function call in `expr` into standard XPath.
+/// String literals, comments, element tests and attributes that merely use
+/// the prefix are left untouched.
+pub fn expand_functions(expr: &str) -> Result<ExpandedXpath<'_>, String> {
+    let unchanged = ExpandedXpath {
+        source: expr,
+        text: Cow::Borrowed(expr),
+        pieces: Vec::new(),
+    };
+    if !expr.contains(':') {
+        return Ok(unchanged);
+    }
+    let tokens: Vec<(Token<'_>, Range<usize>)> = lexer(expr).collect();
+    if !(0..tokens.len()).any(|i| call_name(&tokens, i).is_some()) {
+        return Ok(unchanged);
+    }
+
+    let mut expander = Expander {
+        source: expr,
+        tokens: &tokens,
+        text: String::with_capacity(expr.len() * 2),
+        pieces: Vec::new(),
+    };
+    expander.expand(0..tokens.len(), 0..expr.len())?;
+    Ok(ExpandedXpath {
+        source: expr,
+        text: Cow::Owned(expander.text),
+        pieces: expander.pieces,
+    })
+}
+
+/// Name of the helper function called at token `i`, if any.
+fn call_name<'a>(tokens: &[(Token<'a>, Range<usize>)], i: usize) -> Option<&'a str> {
+    let name = match &tokens[i].0 {
+        Token::PrefixedQName(name) if name.prefix == BROMIUM_NS_PREFIX => name.local_name,
+        Token::URIQualifiedName(name) if name.uri == BROMIUM_NS_URI => name.local_name,
+        _ => return None,
+    };
+    // `$b:x (...)` is a variable followed by a parenthesized expression
+    let after_dollar = i > 0 && matches!(tokens[i - 1].0, Token::Dollar);
+    let opens_call = matches!(tokens.get(i + 1), Some((Token::LeftParen, _)));
+    (opens_call && !after_dollar).then_some(name)
+}
+
+struct Expander<'s, 't> {
+    source: &'s str,
+    tokens: &'t [(Token<'s>, Range<usize>)],
+    text: String,
+    pieces: Vec<Piece>,
+}
+
+impl Expander<'_, '_> {
+    /// Expands the tokens in `tokens`, which cover `source` of the expression.
+    fn expand(&mut self, tokens: Range<usize>, source: Range<usize>) -> Result<(), String> {
+        let mut copied = source.start;
+        let mut i = tokens.start;
+        while i < tokens.end {
+            let Some(name) = call_name(self.tokens, i) else {
+                i += 1;
+                continue;
+            };
+            let call_start = self.tokens[i].1.start;
+            self.push_verbatim(copied..call_start);
+            let (args, close) = self.split_args(i + 2, name, call_start)?;
+            let call = call_start..self.tokens[close].1.end;
+            self.expand_call(name, &args, call.clone())?;
+            copied = call.end;
+            i = close + 1;
+        }
+        self.push_verbatim(copied..source.end);
+        Ok(())
+    }
+
+    /// Splits the argument list starting at token `first` on its top-level
+    /// commas. Returns the token ranges of the arguments and the index of the
+    /// closing parenthesis.
+    fn split_args(
+        &self,
+        first: usize,
+        name: &str,
+        call_start: usize,
+    ) -> Result<(Vec<Range<usize>>, usize), String> {
+        let mut args = Vec::new();
+        let mut depth = 0usize;
+        let mut arg_start = first;
+        for i in first..self.tokens.len() {
+            match self.tokens[i].0 {
+                Token::LeftParen | Token::LeftBracket | Token::LeftBrace => depth += 1,
+                Token::RightParen if depth == 0 => {
+                    if arg_start < i || !args.is_empty() {
+                        args.push(arg_start..i);
+                    }
+                    if args.iter().any(|arg| arg.is_empty()) {
+                        return Err(self.error(
+                            call_start..self.tokens[i].1.end,
+                            "XPST0003",
+                            &format!("Empty argument in call to b:{}", name),
+                        ));
+                    }
+                    return Ok((args, i));
+                }
+                Token::RightParen | Token::RightBracket | Token::RightBrace => {
+                    depth = depth.saturating_sub(1)
+                }
+                Token::Comma if depth == 0 => {
+                    args.push(arg_start..i);
+                    arg_start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        Err(self.error(
+            call_start..self.source.len(),
+            "XPST0003",
+            &format!("Unclosed argument list in call to b:{}", name),
+        ))
+    }
+
+    /// Writes the expansion of a call; arguments are bound with `let` so each
+    /// one is evaluated once.
+    fn expand_call(
+        &mut self,
+        name: &str,
+        args: &[Range<usize>],
+        call: Range<usize>,
+    ) -> Result<(), String> {
+        let body = match (name, args.len()) {
+            ("icontains", 2) => {
+                "contains(lower-case(string($b:arg1)), lower-case(string($b:arg2)))".to_string()
+            }
+            ("matches-ci", 2) => "matches(string($b:arg1), string($b:arg2), 'i')".to_string(),
+            ("normalize-name", 0) => normalize_name("@Name"),
+            ("normalize-name", 1) => normalize_name("$b:arg1"),
+            ("inside", 4) => "number(@X) >= $b:arg1 and number(@Y) >= $b:arg2 \
+                 and number(@X) + number(@Width) <= $b:arg1 + $b:arg3 \
+                 and number(@Y) + number(@Height) <= $b:arg2 + $b:arg4"
+                .to_string(),
+            ("visible", 0) => "number(@Width) > 0 and number(@Height) > 0".to_string(),
+            _ if FUNCTION_NAMES.contains(&name) => {
+                return Err(self.error(
+                    call,
+                    "XPST0017",
+                    &format!("b:{} does not take {} argument(s)", name, args.len()),
+                ));
+            }
+            _ => {
+                return Err(self.error(call, "XPST0017", &format!("Unknown function b:{}", name)));
+            }
+        };
+
+        if args.is_empty() {
+            self.push_generated(&format!("({})", body), &call);
+            return Ok(());
+        }
+        for (n, arg) in args.iter().enumerate() {
+            let binding = if n == 0 { "(let " } else { ", " };
+            self.push_generated(&format!("{}$b:arg{} := ", binding, n + 1), &call);
+            let source = self.tokens[arg.start].1.start..self.tokens[arg.end - 1].1.end;
+            self.expand(arg.clone(), source)?;
+        }
+        self.push_generated(&format!(" return {})", body), &call);
+        Ok(())
+    }
+
+    fn push_verbatim(&mut self, source: Range<usize>) {
+        if source.is_empty() {
+            return;
+        }
+        let start = self.text.len();
+        self.text.push_str(&self.source[source.clone()]);
+        match self.pieces.last_mut() {
+            Some(last) if last.verbatim && last.source.end == source.start => {
+                last.text.end = self.text.len();
+                last.source.end = source.end;
+            }
+            _ => self.pieces.push(Piece {
+                text: start..self.text.len(),
+                source,
+                verbatim: true,
+            }),
+        }
+    }
+
+    fn push_generated(&mut self, text: &str, call: &Range<usize>) {
+        let start = self.text.len();
+        self.text.push_str(text);
+        self.pieces.push(Piece {
+            text: start..self.text.len(),
+            source: call.clone(),
+            verbatim: false,
+        });
+    }
+
+    fn error(&self, span: Range<usize>, code: &str, message: &str) -> String {
+        render_report(self.source, Some(span), code, message)
+    }
+}
+
+fn normalize_name(name: &str) -> String {
+    format!(
+        r"normalize-space(replace(replace(string({}), '&(.)', '$1'), '\s*[(\[]\d+[)\]]\s*$', ''))",
+        name
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xpath_eval::eval_xpath;
+
+    const XML: &str = r#"<Window Name="Editor" X="0" Y="0" Width="800" Height="600">
+        <Button Name="&amp;Save" X="10" Y="10" Width="80" Height="20" />
+        <Button Name="Save &amp;&amp; Close" X="100" Y="10" Width="80" Height="20" />
+        <TabItem Name="Inbox (3)" X="10" Y="40" Width="80" Height="20" />
+        <Button Name="Hidden" X="0" Y="0" Width="0" Height="0" />
+        <Button Name="Offscreen" X="900" Y="10" Width="80" Height="20" />
+    </Window>"#;
+
+    fn names(expr: &str) -> Vec<String> {
+        let res = eval_xpath(&format!("{}/@Name", expr), XML);
+        assert!(res.is_success(), "{}", res.get_error_msg());
+        res.get_result_items()
+            .iter()
+            .map(|item| item.get_item_value().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_plain_expressions_are_untouched() {
+        let expr = "//Button[@Name='Save']";
+        assert!(matches!(
+            expand_functions(expr).unwrap().text,
+            Cow::Borrowed(_)
+        ));
+        let expr = "//Button[@Name='b:icontains(x)']";
+        assert_eq!(expand_functions(expr).unwrap().as_str(), expr);
+        let expr = "//b:Button[@b:icontains='x'] (: b:visible() :)";
+        assert_eq!(expand_functions(expr).unwrap().as_str(), expr);
+    }
+
+    #[test]
+    fn test_icontains_and_matches_ci() {
+        assert_eq!(
+            names("//Button[b:icontains(@Name, 'CLOSE')]"),
+            ["Save && Close"]
+        );
+        assert_eq!(names("//*[b:matches-ci(@Name, '^inbox')]"), ["Inbox (3)"]);
+        // nested calls and quotes inside arguments
+        assert_eq!(
+            names("//*[b:icontains(b:normalize-name(@Name), \"save, c\")]"),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            names("//*[b:icontains(b:normalize-name(), 'SAVE & C')]"),
+            ["Save && Close"]
+        );
+    }
+
+    #[test]
+    fn test_nested_calls() {
+        assert_eq!(
+            names("//*[b:icontains(b:normalize-name(), b:normalize-name('INBOX (7)'))]"),
+            ["Inbox (3)"]
+        );
+        // a call in a predicate inside the argument of another call
+        assert_eq!(
+            names("//Window[b:icontains(string((Button[b:visible()])[2]/@Name), 'close')]"),
+            ["Editor"]
+        );
+        let expanded = expand_functions("b:icontains(b:normalize-name(b:normalize-name()), 'x')");
+        let expanded = expanded.unwrap();
+        assert!(!expanded.as_str().contains("b:icontains"));
+        assert!(!expanded.as_str().contains("b:normalize-name"));
+    }
+
+    #[test]
+    fn test_calls_inside_string_literals_are_text() {
+        let res = eval_xpath("b:icontains('see b:visible() here', \"B:VISIBLE()\")", XML);
+        assert!(res.is_success(), "{}", res.get_error_msg());
+        assert_eq!(res.get_result_items()[0].get_item_value(), "true");
+        // unknown functions and escaped quotes inside literals are not calls either
+        let expr = "b:icontains('it''s b:nope(', \"b:inside(\"\")\")";
+        let expanded = expand_functions(expr).unwrap();
+        assert!(expanded.as_str().contains("'it''s b:nope('"));
+        assert!(expanded.as_str().contains("\"b:inside(\"\")\""));
+        let res = eval_xpath(expr, XML);
+        assert!(res.is_success(), "{}", res.get_error_msg());
+        assert_eq!(res.get_result_items()[0].get_item_value(), "false");
+    }
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(names("//*[b:normalize-name() = 'Save']"), ["&Save"]);
+        assert_eq!(
+            names("//*[b:normalize-name(@Name) = 'Inbox']"),
+            ["Inbox (3)"]
+        );
+        let res = eval_xpath("b:normalize-name('  E&xit  [12] ')", XML);
+        assert_eq!(res.get_result_items()[0].get_item_value(), "Exit");
+    }
+
+    #[test]
+    fn test_geometry_functions() {
+        assert_eq!(names("//Button[b:visible()]").len(), 3);
+        assert_eq!(
+            names("//Button[b:inside(0, 0, 95, 35)]"),
+            ["&Save", "Hidden"]
+        );
+        assert_eq!(
+            names("//Button[b:visible() and b:inside(0, 0, 800, 600)]"),
+            ["&Save", "Save && Close"]
+        );
+    }
+
+    #[test]
+    fn test_invalid_calls_are_reported() {
+        assert!(
+            expand_functions("//*[b:nope()]")
+                .unwrap_err()
+                .contains("b:nope")
+        );
+        assert!(
+            expand_functions("//*[b:icontains(@Name)]")
+                .unwrap_err()
+                .contains("1 argument")
+        );
+        assert!(expand_functions("//*[b:visible(]").is_err());
+        let res = eval_xpath("//*[b:inside(1, 2, 3)]", XML);
+        assert!(!res.is_success());
+        assert!(res.get_error_msg().contains("b:inside"));
+    }
+
+    #[test]
+    fn test_arguments_are_evaluated_once() {
+        let expanded = expand_functions("//Button[b:inside(count(//Window), 0, 95, 35)]").unwrap();
+        assert_eq!(expanded.as_str().matches("count(//Window)").count(), 1);
+        assert_eq!(
+            names("//Button[b:inside(number(//Window/@X), 0, 95, 35)]"),
+            ["&Save", "Hidden"]
+        );
+    }
+
+    #[test]
+    fn test_full_names_and_unicode() {
+        assert_eq!(
+            names("//Button[Q{urn:bromium:functions}icontains(@Name, 'CLOSE')]"),
+            ["Save && Close"]
+        );
+        assert!(
+            expand_functions("//*[b:größe()]")
+                .unwrap_err()
+                .contains("Unknown function b:größe")
+        );
+        let expr = "//Schaltfläche[@Name='ä']/b:Größe";
+        assert_eq!(expand_functions(expr).unwrap().as_str(), expr);
+        // `$b:x` is a variable, not a call
+        let expr = "$b:x (1)";
+        assert_eq!(expand_functions(expr).unwrap().as_str(), expr);
+    }
+
+    #[test]
+    fn test_error_spans_point_into_the_source() {
+        let expr = "//*[b:visible() and @Name = = 'x']";
+        let expanded = expand_functions(expr).unwrap();
+        let at = expanded.as_str().find("@Name").unwrap();
+        assert_eq!(expanded.source_range(at..at + 5), 20..25);
+        // generated code maps to the whole call
+        let generated = expanded.as_str().find("@Width").unwrap();
+        assert_eq!(expanded.source_range(generated..generated + 6), 4..15);
+
+        let res = eval_xpath(expr, XML);
+        assert!(!res.is_success());
+        assert!(res.get_error_msg().contains(expr));
+        assert!(!res.get_error_msg().contains("@Width"));
+    }
+
+    #[test]
+    fn test_prefix_cannot_be_redeclared() {
+        let namespaces = ["b=urn:other".to_string()];
+        assert!(crate::xpath_eval::make_static_context_builder(None, &namespaces).is_err());
+    }
+}
@@ -2,6 +2,8 @@ use std::collections::HashMap;
 
 use roxmltree::{Document, Node};
 
+use crate::xpath_eval::{XpathDocCache, eval_xpath_on_cache};
+
 #[derive(Debug, thiserror::Error)]
 pub enum XpathGenError {
     #[error("XML parse error: {0}")]
@@ -15,7 +17,7 @@ pub enum XpathGenError {
 /// - If the value contains no single quotes, wrap in single quotes: `'value'`
 /// - If the value contains no double quotes, wrap in double quotes: `"value"`
 /// - If both, emit a `concat(...)` expression that splits around the quotes.
-fn xpath_string_literal(value: &str) -> String {
+pub fn xpath_string_literal(value: &str) -> String {
     if !value.contains('\'') {
         format!("'{}'", value)
     } else if !value.contains('"') {
@@ -40,42 +42,73 @@ fn xpath_string_literal(value: &str) -> String {
     }
 }
 
+/// Attribute combinations a locator step may use with their robustness
+/// score, most stable first. `Name` ranks low because it is localized and
+/// often carries volatile text; combined with a stable attribute it ranks
+/// above `Name` alone.
+const LOCATOR_ATTRIBUTES: [(&[&str], f64); 6] = [
+    (&["AutomationId"], 1.0),
+    (&["AutomationId", "ClassName"], 0.95),
+    (&["ClassName"], 0.7),
+    (&["AutomationId", "Name"], 0.65),
+    (&["ClassName", "Name"], 0.6),
+    (&["Name"], 0.5),
+];
+
+/// Score of a step that only names the tag of an only child.
+const TAG_SCORE: f64 = 0.6;
+/// Score of a step that selects by sibling position.
+const POSITION_SCORE: f64 = 0.3;
+/// Factor applied to locators that match volatile text exactly.
+const VOLATILE_SCORE_FACTOR: f64 = 0.5;
+
+type LocatorKey = (String, usize, Vec<String>);
+
 struct AttributeIndex {
     id_counts: HashMap<String, usize>,
     name_counts: HashMap<String, usize>,
-    ct_name_counts: HashMap<(String, String), usize>,
+    /// Occurrences of each (tag, `LOCATOR_ATTRIBUTES` entry, values) triple.
+    locator_counts: HashMap<LocatorKey, usize>,
 }
 
 impl AttributeIndex {
     fn build(doc: &Document) -> Self {
         let mut id_counts: HashMap<String, usize> = HashMap::new();
         let mut name_counts: HashMap<String, usize> = HashMap::new();
-        let mut ct_name_counts: HashMap<(String, String), usize> = HashMap::new();
+        let mut locator_counts: HashMap<LocatorKey, usize> = HashMap::new();
 
-        for node in doc.descendants() {
+        for node in doc.descendants().filter(|n| n.is_element()) {
             if let Some(id) = node.attribute("id") {
                 *id_counts.entry(id.to_string()).or_default() += 1;
             }
             if let Some(name) = node.attribute("name") {
                 *name_counts.entry(name.to_string()).or_default() += 1;
             }
-            if let Some(ct) = node.attribute("ControlType")
-                && let Some(name) = node.attribute("Name")
-            {
-                *ct_name_counts
-                    .entry((ct.to_string(), name.to_string()))
-                    .or_default() += 1;
+            for (set, (names, _)) in LOCATOR_ATTRIBUTES.iter().enumerate() {
+                if let Some(values) = attribute_values(node, names) {
+                    *locator_counts
+                        .entry(locator_key(node, set, &values))
+                        .or_default() += 1;
+                }
             }
         }
 
         Self {
             id_counts,
             name_counts,
-            ct_name_counts,
+            locator_counts,
         }
     }
 }
 
+/// Values of `names` on `node`, or `None` if any of them is missing or empty.
+fn attribute_values<'a>(node: Node<'a, '_>, names: &[&str]) -> Option<Vec<&'a str>> {
+    names
+        .iter()
+        .map(|name| node.attribute(*name).filter(|v| !v.is_empty()))
+        .collect()
+}
+
 fn is_attribute_unique(index: &AttributeIndex, node: Node, attr_name: &str) -> bool {
     let counts = match attr_name {
         "id" => &index.id_counts,
@@ -86,18 +119,369 @@ fn is_attribute_unique(index: &AttributeIndex, node: Node, attr_name: &str) -> b
         .is_some_and(|val| counts.get(val) == Some(&1))
 }
 
-fn is_attribute_with_ct_unique(index: &AttributeIndex, node: Node) -> bool {
-    if let Some(name) = node.attribute("Name")
-        && let Some(ct) = node.attribute("ControlType")
+/// Whether `//Tag[@A=.. and @B=..]` over `LOCATOR_ATTRIBUTES[set]` selects
+/// only `node` in the whole document.
+fn is_locator_unique(index: &AttributeIndex, node: Node, set: usize) -> bool {
+    attribute_values(node, LOCATOR_ATTRIBUTES[set].0).is_some_and(|values| {
+        index.locator_counts.get(&locator_key(node, set, &values)) == Some(&1)
+    })
+}
+
+fn locator_key(node: Node, set: usize, values: &[&str]) -> LocatorKey {
+    (
+        node.tag_name().name().to_string(),
+        set,
+        values.iter().map(|v| v.to_string()).collect(),
+    )
+}
+
+/// A piece of an attribute value, split into stable text and volatile numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextPart<'a> {
+    Text(&'a str),
+    /// Digits, possibly joined by `-./:,` as in dates, times and decimals.
+    Number(&'a str),
+}
+
+/// Splits `value` into stable text and the numbers in it, such as counters,
+/// dates and times. Returns `None` if `value` has no digits.
+fn split_volatile(value: &str) -> Option<Vec<TextPart<'_>>> {
+    let bytes = value.as_bytes();
+    if !bytes.iter().any(u8::is_ascii_digit) {
+        return None;
+    }
+    let mut parts = Vec::new();
+    let mut text_start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        if !bytes[i].is_ascii_digit() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        loop {
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+            let joined = i + 1 < bytes.len()
+                && b"-./:,".contains(&bytes[i])
+                && bytes[i + 1].is_ascii_digit();
+            if !joined {
+                break;
+            }
+            i += 1;
+        }
+        if text_start < start {
+            parts.push(TextPart::Text(&value[text_start..start]));
+        }
+        parts.push(TextPart::Number(&value[start..i]));
+        text_start = i;
+    }
+    if text_start < bytes.len() {
+        parts.push(TextPart::Text(&value[text_start..]));
+    }
+    Some(parts)
+}
+
+/// How a locator condition compares an attribute value.
+#[derive(Debug, Clone, PartialEq)]
+enum ValueMatch<'a> {
+    Equals(&'a str),
+    StartsWith(&'a str),
+    Contains(&'a str),
+    /// The same text with any numbers in place of the original ones.
+    Shape(Vec<TextPart<'a>>),
+}
+
+impl ValueMatch<'_> {
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            ValueMatch::Equals(expected) => value == *expected,
+            ValueMatch::StartsWith(prefix) => value.starts_with(prefix),
+            ValueMatch::Contains(part) => value.contains(part),
+            ValueMatch::Shape(parts) => matches_shape(parts, value),
+        }
+    }
+
+    fn render(&self, attr: &str) -> String {
+        match self {
+            ValueMatch::Equals(value) => format!("@{}={}", attr, xpath_string_literal(value)),
+            ValueMatch::StartsWith(prefix) => {
+                format!("starts-with(@{}, {})", attr, xpath_string_literal(prefix))
+            }
+            ValueMatch::Contains(part) => {
+                format!("contains(@{}, {})", attr, xpath_string_literal(part))
+            }
+            ValueMatch::Shape(parts) => {
+                let mut pattern = String::from("^");
+                for part in parts {
+                    match part {
+                        TextPart::Text(text) => pattern.push_str(&regex_escape(text)),
+                        TextPart::Number(number) => {
+                            let mut in_digits = false;
+                            for c in number.chars() {
+                                if c.is_ascii_digit() {
+                                    if !in_digits {
+                                        pattern.push_str("[0-9]+");
+                                    }
+                                } else {
+                                    pattern.push_str(&regex_escape(&c.to_string()));
+                                }
+                                in_digits = c.is_ascii_digit();
+                            }
+                        }
+                    }
+                }
+                pattern.push('$');
+                format!("matches(@{}, {})", attr, xpath_string_literal(&pattern))
+            }
+        }
+    }
+}
+
+/// Whether `value` has the shape of `parts`: the same text, and digit runs
+/// of any length where `parts` has numbers.
+fn matches_shape(parts: &[TextPart], value: &str) -> bool {
+    let mut rest = value;
+    for part in parts {
+        match part {
+            TextPart::Text(text) => match rest.strip_prefix(text) {
+                Some(r) => rest = r,
+                None => return false,
+            },
+            TextPart::Number(number) => {
+                let mut in_digits = false;
+                for c in number.chars() {
+                    if c.is_ascii_digit() {
+                        if !in_digits {
+                            let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+                            if digits == 0 {
+                                return false;
+                            }
+                            rest = &rest[digits..];
+                        }
+                    } else {
+                        match rest.strip_prefix(c) {
+                            Some(r) => rest = r,
+                            None => return false,
+                        }
+                    }
+                    in_digits = c.is_ascii_digit();
+                }
+            }
+        }
+    }
+    rest.is_empty()
+}
+
+fn regex_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\|.?*+(){}-[]^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn has_alphanumeric(text: &str) -> bool {
+    text.chars().any(char::is_alphanumeric)
+}
+
+/// Matches for a value with numbers in it that ignore those numbers, most
+/// robust first: its leading text, its longest text part, then its shape.
+/// Returns `None` if the value has no numbers.
+fn volatile_matches(value: &str) -> Option<Vec<ValueMatch<'_>>> {
+    let parts = split_volatile(value)?;
+    let mut matches = Vec::new();
+    let prefix = match parts.first() {
+        Some(TextPart::Text(prefix)) if has_alphanumeric(prefix) => Some(*prefix),
+        _ => None,
+    };
+    if let Some(prefix) = prefix {
+        matches.push(ValueMatch::StartsWith(prefix));
+    }
+    let longest = parts
+        .iter()
+        .filter_map(|part| match part {
+            TextPart::Text(text) if has_alphanumeric(text) => Some(text.trim()),
+            _ => None,
+        })
+        .max_by_key(|text| text.len());
+    if let Some(longest) = longest
+        && prefix.is_none_or(|prefix| prefix.trim() != longest)
     {
-        let key = (ct.to_string(), name.to_string());
-        return index.ct_name_counts.get(&key) == Some(&1);
+        matches.push(ValueMatch::Contains(longest));
+    }
+    matches.push(ValueMatch::Shape(parts));
+    Some(matches)
+}
+
+/// Exact conditions on the attributes `names` of `node`, or `None` if any of
+/// them is missing or empty.
+fn exact_conditions<'a>(node: Node<'a, '_>, names: &[&str]) -> Option<Vec<ValueMatch<'a>>> {
+    Some(
+        attribute_values(node, names)?
+            .into_iter()
+            .map(ValueMatch::Equals)
+            .collect(),
+    )
+}
+
+/// Condition lists on the attributes `names` of `node`, in order of
+/// preference. A `Name` with numbers in it is matched on its stable text
+/// instead of exactly, so counters, dates and times may change.
+fn attribute_conditions<'a>(node: Node<'a, '_>, names: &[&str]) -> Vec<Vec<ValueMatch<'a>>> {
+    let Some(exact) = exact_conditions(node, names) else {
+        return Vec::new();
+    };
+    let Some(name_pos) = names.iter().position(|name| *name == "Name") else {
+        return vec![exact];
+    };
+    let ValueMatch::Equals(name) = exact[name_pos] else {
+        return vec![exact];
+    };
+    match volatile_matches(name) {
+        Some(options) => options
+            .into_iter()
+            .map(|option| {
+                let mut conditions = exact.clone();
+                conditions[name_pos] = option;
+                conditions
+            })
+            .collect(),
+        None => vec![exact],
     }
-    false
+}
+
+enum Predicate<'a> {
+    None,
+    Attributes(&'static [&'static str], Vec<ValueMatch<'a>>),
+    Position(usize),
+}
+
+/// One `child::` step of a generated locator.
+struct Step<'a> {
+    tag: &'a str,
+    predicate: Predicate<'a>,
+}
+
+impl<'a> Step<'a> {
+    /// The most stable step that picks `node` out of its siblings, falling
+    /// back to its position among same-tag siblings.
+    fn for_node(node: Node<'a, '_>) -> Self {
+        let tag = node.tag_name().name();
+        let same_tag = |n: &Node| n.is_element() && n.tag_name().name() == tag;
+        let siblings: Vec<Node> = node
+            .parent()
+            .map_or_else(Vec::new, |p| p.children().filter(same_tag).collect());
+
+        for (names, _) in LOCATOR_ATTRIBUTES {
+            for conditions in attribute_conditions(node, names) {
+                let step = Step {
+                    tag,
+                    predicate: Predicate::Attributes(names, conditions),
+                };
+                if siblings.iter().filter(|s| step.matches(**s)).count() <= 1 {
+                    return step;
+                }
+            }
+        }
+
+        let predicate = if siblings.len() > 1 {
+            Predicate::Position(sibling_position(node))
+        } else {
+            Predicate::None
+        };
+        Step { tag, predicate }
+    }
+
+    fn matches(&self, node: Node) -> bool {
+        if !node.is_element() || node.tag_name().name() != self.tag {
+            return false;
+        }
+        match &self.predicate {
+            Predicate::None => true,
+            Predicate::Attributes(names, conditions) => {
+                names.iter().zip(conditions).all(|(name, condition)| {
+                    node.attribute(*name).is_some_and(|v| condition.matches(v))
+                })
+            }
+            Predicate::Position(position) => sibling_position(node) == *position,
+        }
+    }
+
+    fn score(&self) -> f64 {
+        match &self.predicate {
+            Predicate::None => TAG_SCORE,
+            Predicate::Attributes(names, _) => LOCATOR_ATTRIBUTES
+                .iter()
+                .find(|(n, _)| n == names)
+                .map_or(0.0, |(_, score)| *score),
+            Predicate::Position(_) => POSITION_SCORE,
+        }
+    }
+
+    fn render(&self) -> String {
+        match &self.predicate {
+            Predicate::None => self.tag.to_string(),
+            Predicate::Attributes(names, conditions) => {
+                let conditions: Vec<String> = names
+                    .iter()
+                    .zip(conditions)
+                    .map(|(name, condition)| condition.render(name))
+                    .collect();
+                format!("{}[{}]", self.tag, conditions.join(" and "))
+            }
+            Predicate::Position(position) => format!("{}[{}]", self.tag, position),
+        }
+    }
+}
+
+/// 1-based position of `node` among its siblings with the same tag.
+fn sibling_position(node: Node) -> usize {
+    let tag = node.tag_name().name();
+    1 + node
+        .prev_siblings()
+        .skip(1)
+        .filter(|s| s.is_element() && s.tag_name().name() == tag)
+        .count()
+}
+
+/// Number of elements selected by `steps`, as `//s1/s2/..` or `/s1/s2/..`.
+fn count_matches(doc: &Document, steps: &[Step], absolute: bool) -> usize {
+    doc.descendants()
+        .filter(|node| {
+            let mut current = Some(*node);
+            for step in steps.iter().rev() {
+                match current {
+                    Some(n) if step.matches(n) => current = n.parent(),
+                    _ => return false,
+                }
+            }
+            !absolute || current.is_some_and(|n| n.is_root())
+        })
+        .count()
+}
+
+fn render_path(steps: &[Step]) -> String {
+    steps.iter().map(Step::render).collect::<Vec<_>>().join("/")
 }
 
 /// Generate a robust, ROBULA+-like XPath for the given node.
-fn get_xpath_robula(index: &AttributeIndex, node: Node, simple_xpath: bool) -> String {
+///
+/// Attribute combinations are tried in order of stability (see
+/// `LOCATOR_ATTRIBUTES`) and the first one unique in the document wins.
+/// Otherwise ancestors are added until an attribute-anchored relative path
+/// is unique, ending in an absolute path at worst. With `simple_xpath` the
+/// result is a purely positional absolute path.
+fn get_xpath_robula(
+    doc: &Document,
+    index: &AttributeIndex,
+    node: Node,
+    simple_xpath: bool,
+) -> String {
     for attr in ["id", "name"] {
         if is_attribute_unique(index, node, attr) {
             let lit = xpath_string_literal(node.attribute(attr).unwrap());
@@ -105,44 +489,95 @@ fn get_xpath_robula(index: &AttributeIndex, node: Node, simple_xpath: bool) -> S
         }
     }
 
-    let mut path_parts = Vec::new();
-    let mut current = Some(node);
-
-    while let Some(n) = current {
-        if n.is_element() {
-            let tag = n.tag_name().name();
+    if simple_xpath {
+        return get_xpath_positional(node);
+    }
 
-            if !simple_xpath && is_attribute_with_ct_unique(index, n) {
-                let lit = xpath_string_literal(n.attribute("Name").unwrap());
-                path_parts.push(format!("{}[@Name={}]", tag, lit));
+    for (set, (names, _)) in LOCATOR_ATTRIBUTES.iter().enumerate() {
+        for conditions in attribute_conditions(node, names) {
+            let exact = conditions
+                .iter()
+                .all(|c| matches!(c, ValueMatch::Equals(_)));
+            let step = attribute_step(node, names, conditions);
+            let unique = if exact {
+                is_locator_unique(index, node, set)
             } else {
-                let parent = n.parent();
-                let same_tag_count = parent.map_or(1, |p| {
-                    p.children()
-                        .filter(|c| c.is_element() && c.tag_name().name() == tag)
-                        .count()
-                });
-
-                if same_tag_count > 1 {
-                    let mut index = 1;
-                    let mut prev = n.prev_sibling();
-                    while let Some(sib) = prev {
-                        if sib.is_element() && sib.tag_name().name() == tag {
-                            index += 1;
-                        }
-                        prev = sib.prev_sibling();
-                    }
-                    path_parts.push(format!("{}[{}]", tag, index));
-                } else {
-                    path_parts.push(tag.to_string());
-                }
+                count_matches(doc, std::slice::from_ref(&step), false) == 1
+            };
+            if unique {
+                return format!("//{}", step.render());
             }
         }
-        current = n.parent();
     }
 
-    path_parts.reverse();
-    format!("/{}", path_parts.join("/"))
+    let (steps, relative) = anchored_steps(doc, node);
+    format!(
+        "{}{}",
+        if relative { "//" } else { "/" },
+        render_path(&steps)
+    )
+}
+
+/// Single step selecting `node` by `conditions` on the attributes `names`.
+fn attribute_step<'a>(
+    node: Node<'a, '_>,
+    names: &'static [&'static str],
+    conditions: Vec<ValueMatch<'a>>,
+) -> Step<'a> {
+    Step {
+        tag: node.tag_name().name(),
+        predicate: Predicate::Attributes(names, conditions),
+    }
+}
+
+/// Steps from the nearest ancestor that makes the path unique down to
+/// `node`, and whether they form a relative (`//`) path. Ancestors are added
+/// until an attribute-anchored relative path is unique; without one the
+/// steps reach the root element and form an absolute path.
+fn anchored_steps<'a>(doc: &Document, node: Node<'a, '_>) -> (Vec<Step<'a>>, bool) {
+    let mut steps = vec![Step::for_node(node)];
+    let mut current = node.parent().filter(|p| p.is_element());
+    loop {
+        let anchored = steps
+            .iter()
+            .any(|s| matches!(s.predicate, Predicate::Attributes(..)));
+        if anchored && count_matches(doc, &steps, false) == 1 {
+            return (steps, true);
+        }
+        let Some(parent) = current else {
+            return (steps, false);
+        };
+        steps.insert(0, Step::for_node(parent));
+        current = parent.parent().filter(|p| p.is_element());
+    }
+}
+
+/// Absolute path using only tags and sibling positions.
+fn get_xpath_positional(node: Node) -> String {
+    format!("/{}", render_path(&positional_steps(node)))
+}
+
+fn positional_steps<'a>(node: Node<'a, '_>) -> Vec<Step<'a>> {
+    let mut steps: Vec<Step> = node
+        .ancestors()
+        .filter(|n| n.is_element())
+        .map(|n| {
+            let tag = n.tag_name().name();
+            let same_tag_count = n.parent().map_or(1, |p| {
+                p.children()
+                    .filter(|c| c.is_element() && c.tag_name().name() == tag)
+                    .count()
+            });
+            let predicate = if same_tag_count > 1 {
+                Predicate::Position(sibling_position(n))
+            } else {
+                Predicate::None
+            };
+            Step { tag, predicate }
+        })
+        .collect();
+    steps.reverse();
+    steps
 }
 
 pub fn get_xpath_full_from_runtime_id(
@@ -150,17 +585,200 @@ pub fn get_xpath_full_from_runtime_id(
     xml: &str,
     simple_path: bool,
 ) -> Result<String, XpathGenError> {
-    let doc = Document::parse(xml).map_err(|e| XpathGenError::XmlParseError(e.to_string()))?;
-    let index = AttributeIndex::build(&doc);
+    get_xpath_on_doc(runtime_id, &XpathGenDoc::new(xml)?, simple_path)
+}
+
+/// An XML document parsed and indexed once, for generating the XPaths of
+/// many of its elements.
+pub struct XpathGenDoc<'input> {
+    doc: Document<'input>,
+    index: AttributeIndex,
+    runtime_ids: HashMap<String, roxmltree::NodeId>,
+}
+
+impl std::fmt::Debug for XpathGenDoc<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("XpathGenDoc")
+            .field("elements", &self.runtime_ids.len())
+            .finish()
+    }
+}
 
-    if let Some(node_id) = doc
+impl<'input> XpathGenDoc<'input> {
+    pub fn new(xml: &'input str) -> Result<Self, XpathGenError> {
+        let doc = Document::parse(xml).map_err(|e| XpathGenError::XmlParseError(e.to_string()))?;
+        let index = AttributeIndex::build(&doc);
+        let mut runtime_ids = HashMap::new();
+        for node in doc.descendants() {
+            if let Some(runtime_id) = node.attribute("RtID") {
+                runtime_ids
+                    .entry(runtime_id.to_string())
+                    .or_insert(node.id());
+            }
+        }
+        Ok(XpathGenDoc {
+            doc,
+            index,
+            runtime_ids,
+        })
+    }
+}
+
+/// Like [`get_xpath_full_from_runtime_id`], on an already parsed document.
+pub fn get_xpath_on_doc(
+    runtime_id: &str,
+    doc: &XpathGenDoc,
+    simple_path: bool,
+) -> Result<String, XpathGenError> {
+    let node = doc
+        .runtime_ids
+        .get(runtime_id)
+        .and_then(|&id| doc.doc.get_node(id))
+        .ok_or_else(|| XpathGenError::ElementNotFound(runtime_id.to_string()))?;
+    Ok(get_xpath_robula(&doc.doc, &doc.index, node, simple_path))
+}
+
+/// How a locator candidate identifies its element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LocatorStrategy {
+    /// A single step matching the element's own attributes anywhere in the document.
+    Attribute,
+    /// A relative path from an attribute-anchored ancestor down to the element.
+    AnchoredRelative,
+    /// An absolute path from the root element.
+    Positional,
+}
+
+impl LocatorStrategy {
+    /// Lower-case name of the strategy, e.g. `"attribute"`.
+    pub fn name(self) -> &'static str {
+        match self {
+            LocatorStrategy::Attribute => "attribute",
+            LocatorStrategy::AnchoredRelative => "anchored",
+            LocatorStrategy::Positional => "positional",
+        }
+    }
+}
+
+/// A generated XPath together with how it was built and how many elements
+/// it actually selects in the document it was generated from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct XpathCandidate {
+    xpath: String,
+    strategy: LocatorStrategy,
+    score: f64,
+    match_count: usize,
+}
+
+impl XpathCandidate {
+    pub fn get_xpath(&self) -> &str {
+        &self.xpath
+    }
+
+    pub fn get_strategy(&self) -> LocatorStrategy {
+        self.strategy
+    }
+
+    /// Robustness between 0 and 1; higher scores rely on more stable
+    /// attributes and fewer steps.
+    pub fn get_score(&self) -> f64 {
+        self.score
+    }
+
+    /// Number of elements the XPath selects in the source document.
+    pub fn get_match_count(&self) -> usize {
+        self.match_count
+    }
+
+    /// Whether the XPath selects exactly one element.
+    pub fn is_unique(&self) -> bool {
+        self.match_count == 1
+    }
+}
+
+fn path_score(steps: &[Step]) -> f64 {
+    steps.iter().map(Step::score).product()
+}
+
+/// Generate several locator candidates for the element with `runtime_id`.
+///
+/// Every candidate is evaluated against `xml` to record its match count.
+/// Unique candidates come first, then candidates are ordered by descending
+/// score and length.
+pub fn get_xpath_candidates_from_runtime_id(
+    runtime_id: &str,
+    xml: &str,
+) -> Result<Vec<XpathCandidate>, XpathGenError> {
+    let doc = Document::parse(xml).map_err(|e| XpathGenError::XmlParseError(e.to_string()))?;
+    let node = doc
         .descendants()
         .find(|n| n.attribute("RtID") == Some(runtime_id))
-    {
-        Ok(get_xpath_robula(&index, node_id, simple_path))
-    } else {
-        Err(XpathGenError::ElementNotFound(runtime_id.to_string()))
+        .ok_or_else(|| XpathGenError::ElementNotFound(runtime_id.to_string()))?;
+
+    let mut generated: Vec<(String, LocatorStrategy, f64)> = Vec::new();
+    for (attr, score) in [("id", 1.0), ("name", 0.5)] {
+        if let Some(value) = node.attribute(attr) {
+            let xpath = format!("//*[@{}={}]", attr, xpath_string_literal(value));
+            generated.push((xpath, LocatorStrategy::Attribute, score));
+        }
+    }
+    for (names, score) in LOCATOR_ATTRIBUTES {
+        let conditions = attribute_conditions(node, names);
+        let exact = exact_conditions(node, names);
+        // an exact match on volatile text is listed too, ranked down
+        let volatile_exact = exact.filter(|exact| !conditions.contains(exact));
+        for conditions in conditions {
+            let xpath = format!("//{}", attribute_step(node, names, conditions).render());
+            generated.push((xpath, LocatorStrategy::Attribute, score));
+        }
+        if let Some(exact) = volatile_exact {
+            let xpath = format!("//{}", attribute_step(node, names, exact).render());
+            generated.push((
+                xpath,
+                LocatorStrategy::Attribute,
+                score * VOLATILE_SCORE_FACTOR,
+            ));
+        }
+    }
+    let (steps, relative) = anchored_steps(&doc, node);
+    if relative && steps.len() > 1 {
+        let xpath = format!("//{}", render_path(&steps));
+        generated.push((xpath, LocatorStrategy::AnchoredRelative, path_score(&steps)));
+    }
+    let steps = positional_steps(node);
+    generated.push((
+        format!("/{}", render_path(&steps)),
+        LocatorStrategy::Positional,
+        path_score(&steps),
+    ));
+
+    let mut cache = XpathDocCache::new(xml)
+        .ok_or_else(|| XpathGenError::XmlParseError("document cannot be queried".to_string()))?;
+    let mut candidates: Vec<XpathCandidate> = Vec::with_capacity(generated.len());
+    for (xpath, strategy, score) in generated {
+        if candidates.iter().any(|c| c.xpath == xpath) {
+            continue;
+        }
+        let result = eval_xpath_on_cache(&xpath, &mut cache);
+        let match_count = if result.is_success() {
+            result.get_result_count()
+        } else {
+            0
+        };
+        candidates.push(XpathCandidate {
+            xpath,
+            strategy,
+            score,
+            match_count,
+        });
     }
+    candidates.sort_by(|a, b| {
+        b.is_unique()
+            .cmp(&a.is_unique())
+            .then(b.score.total_cmp(&a.score))
+            .then(a.xpath.len().cmp(&b.xpath.len()))
+    });
+    Ok(candidates)
 }
 
 #[cfg(test)]
@@ -183,6 +801,13 @@ mod tests {
   </List>
 </Root>"#;
 
+    fn locator_set(names: &[&str]) -> usize {
+        LOCATOR_ATTRIBUTES
+            .iter()
+            .position(|(s, _)| *s == names)
+            .unwrap()
+    }
+
     fn find_node_by_rtid<'a>(doc: &'a Document<'a>, rtid: &str) -> Node<'a, 'a> {
         doc.descendants()
             .find(|n| n.attribute("RtID") == Some(rtid))
@@ -195,7 +820,7 @@ mod tests {
         let index = AttributeIndex::build(&doc);
         let node = find_node_by_rtid(&doc, "rt-user");
         assert!(is_attribute_unique(&index, node, "id"));
-        let xpath = get_xpath_robula(&index, node, false);
+        let xpath = get_xpath_robula(&doc, &index, node, false);
         assert_eq!(xpath, "//*[@id='txt-user']");
     }
 
@@ -212,7 +837,7 @@ mod tests {
         let doc = Document::parse(TEST_XML).unwrap();
         let index = AttributeIndex::build(&doc);
         let button_ok = find_node_by_rtid(&doc, "rt-ok");
-        assert!(is_attribute_with_ct_unique(&index, button_ok));
+        assert!(is_locator_unique(&index, button_ok, locator_set(&["Name"])));
     }
 
     #[test]
@@ -220,7 +845,7 @@ mod tests {
         let doc = Document::parse(TEST_XML).unwrap();
         let index = AttributeIndex::build(&doc);
         let text_ok = find_node_by_rtid(&doc, "rt-ok-text");
-        assert!(is_attribute_with_ct_unique(&index, text_ok));
+        assert!(is_locator_unique(&index, text_ok, locator_set(&["Name"])));
     }
 
     #[test]
@@ -228,25 +853,19 @@ mod tests {
         let doc = Document::parse(TEST_XML).unwrap();
         let index = AttributeIndex::build(&doc);
         let item = find_node_by_rtid(&doc, "rt-item1");
-        assert!(!is_attribute_with_ct_unique(&index, item));
+        assert!(!is_locator_unique(&index, item, locator_set(&["Name"])));
     }
 
     #[test]
     fn test_full_path_with_ct_name() {
         let result = get_xpath_full_from_runtime_id("rt-ok", TEST_XML, false).unwrap();
-        assert_eq!(
-            result,
-            "/Root[@Name='MainWindow']/Panel[@Name='Header']/Button[@Name='OK']"
-        );
+        assert_eq!(result, "//Button[@Name='OK']");
     }
 
     #[test]
     fn test_full_path_unique_name_cancel() {
         let result = get_xpath_full_from_runtime_id("rt-cancel", TEST_XML, false).unwrap();
-        assert_eq!(
-            result,
-            "/Root[@Name='MainWindow']/Panel[@Name='Header']/Button[@Name='Cancel']"
-        );
+        assert_eq!(result, "//Button[@Name='Cancel']");
     }
 
     #[test]
@@ -261,10 +880,7 @@ mod tests {
         let full = get_xpath_full_from_runtime_id("rt-item2", TEST_XML, false).unwrap();
         assert_ne!(simple, full);
         assert_eq!(simple, "/Root/List/Item[2]");
-        assert_eq!(
-            full,
-            "/Root[@Name='MainWindow']/List[@Name='Items']/Item[2]"
-        );
+        assert_eq!(full, "//List[@Name='Items']/Item[2]");
     }
 
     #[test]
@@ -295,7 +911,7 @@ mod tests {
     #[test]
     fn test_root_element_xpath() {
         let result = get_xpath_full_from_runtime_id("rt-root", TEST_XML, false).unwrap();
-        assert_eq!(result, "/Root[@Name='MainWindow']");
+        assert_eq!(result, "//Root[@Name='MainWindow']");
     }
 
     #[test]
@@ -340,6 +956,249 @@ mod tests {
   <Button ControlType="Button" Name="Click" RtID="rt-btn"/>
 </Root>"#;
         let result = get_xpath_full_from_runtime_id("rt-root", xml, false).unwrap();
-        assert_eq!(result, r#"/Root[@Name="Bob's App"]"#);
+        assert_eq!(result, r#"//Root[@Name="Bob's App"]"#);
+    }
+
+    // ─── attribute-aware generation ──────────────────────────────────────────
+
+    const UI_XML: &str = r#"<Window ControlType="Window" Name="Editor" ClassName="EditorFrame" RtID="rt-win">
+  <Pane ControlType="Pane" Name="Links" AutomationId="left" RtID="rt-left">
+    <Button ControlType="Button" Name="OK" ClassName="Btn" RtID="rt-left-ok"/>
+    <Button ControlType="Button" Name="Speichern" AutomationId="SaveButton" RtID="rt-save"/>
+  </Pane>
+  <Pane ControlType="Pane" Name="Rechts" AutomationId="right" RtID="rt-right">
+    <Button ControlType="Button" Name="OK" ClassName="Btn" RtID="rt-right-ok"/>
+    <Edit ControlType="Edit" Name="Pfad" AutomationId="path" ClassName="Edit" RtID="rt-path1"/>
+    <Edit ControlType="Edit" Name="Datei" AutomationId="path" ClassName="RichEdit" RtID="rt-path2"/>
+  </Pane>
+  <Pane ControlType="Pane" RtID="rt-anon">
+    <Text ControlType="Text" RtID="rt-t1"/>
+    <Text ControlType="Text" RtID="rt-t2"/>
+  </Pane>
+</Window>"#;
+
+    fn assert_unique(xpath: &str, rtid: &str) {
+        let res = crate::eval_xpath(&format!("{}/@RtID", xpath), UI_XML);
+        assert_eq!(res.get_result_count(), 1, "{} is not unique", xpath);
+        assert_eq!(res.get_result_items()[0].get_item_value(), rtid);
+    }
+
+    #[test]
+    fn test_automation_id_preferred_over_localized_name() {
+        let result = get_xpath_full_from_runtime_id("rt-save", UI_XML, false).unwrap();
+        assert_eq!(result, "//Button[@AutomationId='SaveButton']");
+        assert_unique(&result, "rt-save");
+    }
+
+    #[test]
+    fn test_automation_id_combined_with_class_name() {
+        let result = get_xpath_full_from_runtime_id("rt-path2", UI_XML, false).unwrap();
+        assert_eq!(
+            result,
+            "//Edit[@AutomationId='path' and @ClassName='RichEdit']"
+        );
+        assert_unique(&result, "rt-path2");
+    }
+
+    #[test]
+    fn test_automation_id_with_name_preferred_over_name() {
+        let xml = r#"<Window ControlType="Window" RtID="rt-win">
+  <Button ControlType="Button" Name="Save" AutomationId="btn" RtID="rt-save"/>
+  <Button ControlType="Button" Name="Close" AutomationId="btn" RtID="rt-close"/>
+</Window>"#;
+        let result = get_xpath_full_from_runtime_id("rt-save", xml, false).unwrap();
+        assert_eq!(result, "//Button[@AutomationId='btn' and @Name='Save']");
+    }
+
+    #[test]
+    fn test_duplicate_name_anchored_on_ancestor() {
+        let result = get_xpath_full_from_runtime_id("rt-right-ok", UI_XML, false).unwrap();
+        assert_eq!(
+            result,
+            "//Pane[@AutomationId='right']/Button[@ClassName='Btn']"
+        );
+        assert_unique(&result, "rt-right-ok");
+    }
+
+    #[test]
+    fn test_no_attributes_uses_positions_below_nearest_anchor() {
+        let result = get_xpath_full_from_runtime_id("rt-t2", UI_XML, false).unwrap();
+        assert_eq!(result, "//Window[@ClassName='EditorFrame']/Pane[3]/Text[2]");
+        assert_unique(&result, "rt-t2");
+    }
+
+    #[test]
+    fn test_generated_xpaths_are_unique() {
+        let doc = Document::parse(UI_XML).unwrap();
+        for node in doc.descendants().filter(|n| n.is_element()) {
+            let rtid = node.attribute("RtID").unwrap();
+            for simple in [false, true] {
+                let xpath = get_xpath_full_from_runtime_id(rtid, UI_XML, simple).unwrap();
+                assert_unique(&xpath, rtid);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parsed_doc_gives_same_xpaths() {
+        let parsed = XpathGenDoc::new(UI_XML).unwrap();
+        for rtid in ["rt-save", "rt-right-ok", "rt-t2"] {
+            assert_eq!(
+                get_xpath_on_doc(rtid, &parsed, false).unwrap(),
+                get_xpath_full_from_runtime_id(rtid, UI_XML, false).unwrap()
+            );
+        }
+        assert!(matches!(
+            get_xpath_on_doc("nonexistent", &parsed, false),
+            Err(XpathGenError::ElementNotFound(_))
+        ));
+    }
+
+    // ─── locator candidates ──────────────────────────────────────────────────
+
+    #[test]
+    fn test_candidates_are_verified_and_ranked() {
+        let candidates = get_xpath_candidates_from_runtime_id("rt-right-ok", UI_XML).unwrap();
+        let summary: Vec<(&str, LocatorStrategy, usize)> = candidates
+            .iter()
+            .map(|c| (c.get_xpath(), c.get_strategy(), c.get_match_count()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (
+                    "//Pane[@AutomationId='right']/Button[@ClassName='Btn']",
+                    LocatorStrategy::AnchoredRelative,
+                    1
+                ),
+                ("/Window/Pane[2]/Button", LocatorStrategy::Positional, 1),
+                ("//Button[@ClassName='Btn']", LocatorStrategy::Attribute, 2),
+                (
+                    "//Button[@ClassName='Btn' and @Name='OK']",
+                    LocatorStrategy::Attribute,
+                    2
+                ),
+                ("//Button[@Name='OK']", LocatorStrategy::Attribute, 2),
+            ]
+        );
+        assert!(candidates[0].is_unique());
+        assert!(candidates[0].get_score() > candidates[1].get_score());
+    }
+
+    #[test]
+    fn test_best_candidate_prefers_stable_attribute() {
+        let candidates = get_xpath_candidates_from_runtime_id("rt-save", UI_XML).unwrap();
+        let best = &candidates[0];
+        assert_eq!(best.get_xpath(), "//Button[@AutomationId='SaveButton']");
+        assert_eq!(best.get_strategy(), LocatorStrategy::Attribute);
+        assert_eq!(best.get_score(), 1.0);
+        assert!(candidates.iter().all(|c| c.is_unique()));
+        // the generator's own choice is always among the verified candidates
+        let xpath = get_xpath_full_from_runtime_id("rt-save", UI_XML, false).unwrap();
+        assert_eq!(best.get_xpath(), xpath);
+    }
+
+    // ─── dynamic text ────────────────────────────────────────────────────────
+
+    const MAIL_XML: &str = r#"<Window ControlType="Window" Name="Mail" RtID="rt-mail">
+  <TabItem ControlType="TabItem" Name="Inbox (23)" RtID="rt-inbox"/>
+  <TabItem ControlType="TabItem" Name="Drafts (2)" RtID="rt-drafts"/>
+  <Text ControlType="Text" Name="3 unread in Inbox" RtID="rt-unread"/>
+  <Text ControlType="Text" Name="Sync: 3 min ago" RtID="rt-sync"/>
+  <Window ControlType="Window" Name="Report_2026-10-18.xlsx - Excel" RtID="rt-report"/>
+  <Window ControlType="Window" Name="Report_2026-10-18.xlsx - Excel (2)" RtID="rt-report2"/>
+</Window>"#;
+
+    #[test]
+    fn test_split_volatile() {
+        use TextPart::*;
+        assert_eq!(split_volatile("Save"), None);
+        assert_eq!(
+            split_volatile("Inbox (23)").unwrap(),
+            [Text("Inbox ("), Number("23"), Text(")")]
+        );
+        assert_eq!(
+            split_volatile("Report_2026-10-18.xlsx - Excel").unwrap(),
+            [Text("Report_"), Number("2026-10-18"), Text(".xlsx - Excel")]
+        );
+        assert_eq!(
+            split_volatile("Sync: 12:30, 1.5 MB").unwrap(),
+            [
+                Text("Sync: "),
+                Number("12:30"),
+                Text(", "),
+                Number("1.5"),
+                Text(" MB")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shape_matches_other_numbers() {
+        let shape = ValueMatch::Shape(split_volatile("Report_2026-10-18.xlsx").unwrap());
+        assert!(shape.matches("Report_2026-10-18.xlsx"));
+        assert!(shape.matches("Report_2027-1-9.xlsx"));
+        assert!(!shape.matches("Report_2026-10.xlsx"));
+        assert!(!shape.matches("Report_2026-10-18.xlsx (2)"));
+        assert_eq!(
+            shape.render("Name"),
+            r"matches(@Name, '^Report_[0-9]+\-[0-9]+\-[0-9]+\.xlsx$')"
+        );
+    }
+
+    #[test]
+    fn test_volatile_names_match_on_stable_text() {
+        let expected = [
+            ("rt-inbox", "//TabItem[starts-with(@Name, 'Inbox (')]"),
+            ("rt-unread", "//Text[contains(@Name, 'unread in Inbox')]"),
+            ("rt-sync", "//Text[starts-with(@Name, 'Sync: ')]"),
+            (
+                "rt-report",
+                r"//Window[matches(@Name, '^Report_[0-9]+\-[0-9]+\-[0-9]+\.xlsx \- Excel$')]",
+            ),
+            ("rt-report2", "//Window[contains(@Name, '.xlsx - Excel (')]"),
+        ];
+        // the same window a few minutes and one new mail later
+        let later = MAIL_XML
+            .replace("(23)", "(24)")
+            .replace("3 unread", "4 unread")
+            .replace("3 min", "5 min")
+            .replace("2026-10-18", "2026-10-19");
+        for (rtid, xpath) in expected {
+            let result = get_xpath_full_from_runtime_id(rtid, MAIL_XML, false).unwrap();
+            assert_eq!(result, xpath);
+            for xml in [MAIL_XML, later.as_str()] {
+                let res = crate::eval_xpath(&format!("{}/@RtID", xpath), xml);
+                assert_eq!(res.get_result_count(), 1, "{} is not unique", xpath);
+                assert_eq!(res.get_result_items()[0].get_item_value(), rtid);
+            }
+        }
+    }
+
+    #[test]
+    fn test_exact_volatile_name_candidate_is_ranked_down() {
+        let candidates = get_xpath_candidates_from_runtime_id("rt-inbox", MAIL_XML).unwrap();
+        let score = |xpath: &str| {
+            candidates
+                .iter()
+                .find(|c| c.get_xpath() == xpath)
+                .unwrap()
+                .get_score()
+        };
+        assert_eq!(
+            candidates[0].get_xpath(),
+            "//TabItem[starts-with(@Name, 'Inbox (')]"
+        );
+        assert!(candidates.iter().all(|c| c.is_unique()));
+        assert!(
+            score("//TabItem[@Name='Inbox (23)']")
+                < score("//TabItem[matches(@Name, '^Inbox \\([0-9]+\\)$')]")
+        );
+    }
+
+    #[test]
+    fn test_candidates_for_unknown_runtime_id() {
+        let result = get_xpath_candidates_from_runtime_id("nonexistent", UI_XML);
+        assert!(matches!(result, Err(XpathGenError::ElementNotFound(_))));
     }
 }